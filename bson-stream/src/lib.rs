@@ -0,0 +1,640 @@
+//! Reading and writing BSON documents one after another, as in a .bson file or on a socket: framing
+//! them by the size at the head of each, checking it against a limit, and keeping track of where
+//! each document starts. Reading asynchronously needs the tokio feature.
+
+use std::{
+    io::{BufRead, Read, Write},
+    result::Result,
+};
+
+/// A document's bytes, as read from its input.
+pub struct BsonBytes {
+    pub size: u32,
+    pub bytes: Vec<u8>,
+    /// Where the document starts in its input.
+    pub position: Position,
+}
+
+/// Where a document starts in its input: its index among the input's documents, counting from 0,
+/// and its byte offset.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub index: u64,
+    pub offset: u64,
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "document #{} at offset {:#x}", self.index, self.offset)
+    }
+}
+
+pub struct Source<'reader, R: Read> {
+    reader: &'reader mut R,
+    position: Position,
+    max_size: u32,
+}
+
+pub fn source<R: Read>(reader: &mut R) -> Source<'_, R> {
+    source_at(reader, Position::default())
+}
+
+/// A source whose reader has already been read up to `position`, so that the positions of the
+/// documents it reads are counted from there.
+pub fn source_at<R: Read>(reader: &mut R, position: Position) -> Source<'_, R> {
+    Source { reader, position, max_size: DEFAULT_MAX_BSON_SIZE }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IOError(std::io::Error),
+    TooSmallError(u8),
+    TooLargeError(u32, u32),
+    NegativeSizeError(i32),
+    /// The input ended partway through a document: the bytes of it that were read, and its size,
+    /// if enough of it was read to know.
+    TruncatedError(Vec<u8>, Option<u32>),
+    /// A document of the given size whose last byte isn't the null terminator.
+    UnterminatedError(u32),
+}
+
+/// The largest document a source reads unless told otherwise. BSON itself has no maximum size,
+/// but mongod's is 16mb, and 16kb + 16mb is the maximum size we would get when dumping the oplog
+/// itself. See https://jira.mongodb.org/browse/TOOLS-3001.
+pub const DEFAULT_MAX_BSON_SIZE: u32 = (16 * 1024 * 1024) + (16 * 1024);
+
+/// A capacity for the buffered reader that `read_next_buffered` reads from, large enough that most
+/// documents are copied out of its buffer whole, and only the few spanning its end are read in two.
+pub const READ_BUFFER_SIZE: usize = 1 << 20;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::TooSmallError(bson_size) => write!(
+                f,
+                "invalid BSONSize: {} bytes is less than {} bytes",
+                bson_size, MIN_BSON_SIZE
+            ),
+            Error::TooLargeError(bson_size, max_size) => write!(
+                f,
+                "invalid BSONSize: {} bytes is larger than than maximum of {} bytes",
+                bson_size, max_size
+            ),
+            Error::NegativeSizeError(bson_size) => write!(f, "invalid BSONSize: {} bytes is negative", bson_size),
+            Error::TruncatedError(bytes, Some(bson_size)) => write!(
+                f,
+                "truncated document: read {} of {} bytes, {} missing",
+                bytes.len(),
+                bson_size,
+                *bson_size as usize - bytes.len()
+            ),
+            Error::TruncatedError(bytes, None) => {
+                write!(f, "truncated document: read {} bytes, too few for its size", bytes.len())
+            }
+            Error::UnterminatedError(bson_size) => {
+                write!(f, "unterminated document: the last of its {} bytes isn't 0", bson_size)
+            }
+
+            Error::IOError(ref err) => err.fmt(f),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// 4 bytes for the size + 1 byte for the null terminator
+const MIN_BSON_SIZE: u32 = 5;
+
+impl<'r, R: Read> Source<'r, R> {
+    /// Read documents of up to `max_size` bytes rather than DEFAULT_MAX_BSON_SIZE, e.g. from
+    /// streams that weren't written by mongod.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The position of the next document. After an error, the position of the document that
+    /// couldn't be read.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    // Reads and validates the size at the head of the next document; None at the end of input.
+    fn read_size(&mut self) -> Option<Result<u32, Error>> {
+        let mut size_bytes: [u8; 4] = [0, 0, 0, 0];
+        match read_fully(self.reader, &mut size_bytes) {
+            Err(err) => return Some(Err(Error::IOError(err))),
+            Ok(0) => return None,
+            Ok(read) if read < size_bytes.len() => {
+                return Some(Err(Error::TruncatedError(size_bytes[..read].to_vec(), None)));
+            }
+            Ok(_) => (),
+        }
+        Some(check_size(size_bytes, self.max_size))
+    }
+
+    /// Skip over up to `count` documents, reading each into one reused buffer rather than
+    /// allocating it, returning how many were skipped before the end of input. A document the
+    /// input ends partway through is a TruncatedError, as when reading.
+    pub fn skip_documents(&mut self, count: u64) -> Result<u64, Error> {
+        let mut buf = Vec::new();
+        for skipped in 0..count {
+            match self.read_next_into(&mut buf) {
+                None => return Ok(skipped),
+                Some(result) => result?,
+            };
+        }
+        Ok(count)
+    }
+
+    fn advance(&mut self, size: u32) {
+        self.position.index += 1;
+        self.position.offset += size as u64;
+    }
+
+    /// Read the next document into `buf`, replacing what it held, and return its size; None at the
+    /// end of input. Unlike iterating, which allocates each document, this lets a loop reuse one
+    /// buffer for them all.
+    pub fn read_next_into(&mut self, buf: &mut Vec<u8>) -> Option<Result<u32, Error>> {
+        let size = match self.read_size()? {
+            Err(err) => return Some(Err(err)),
+            Ok(size) => size,
+        };
+        let size_bytes = size.to_le_bytes();
+
+        buf.clear();
+        buf.resize(size as usize, 0);
+        buf[..size_bytes.len()].copy_from_slice(&size_bytes);
+        match read_fully(self.reader, &mut buf[size_bytes.len()..]) {
+            Err(err) => return Some(Err(Error::IOError(err))),
+            Ok(read) if read < buf.len() - size_bytes.len() => {
+                buf.truncate(size_bytes.len() + read);
+                return Some(Err(Error::TruncatedError(buf.clone(), Some(size))));
+            }
+            Ok(_) => (),
+        }
+        self.advance(size);
+        Some(Ok(size))
+    }
+}
+
+impl<'r, R: BufRead> Source<'r, R> {
+    /// Read the next document into `buf`, like `read_next_into`, but copy it straight out of the
+    /// reader's buffer when it's all there, which for small documents is most of the time, rather
+    /// than reading its size and then the rest. Documents that span the end of the buffer are read
+    /// the usual way, so the reader's buffer should be large, e.g. `READ_BUFFER_SIZE`. Nothing past
+    /// the document is consumed, so another source can carry on from where this one stopped.
+    pub fn read_next_buffered(&mut self, buf: &mut Vec<u8>) -> Option<Result<u32, Error>> {
+        let buffered = match self.reader.fill_buf() {
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => return self.read_next_into(buf),
+            Err(err) => return Some(Err(Error::IOError(err))),
+            Ok(buffered) => buffered,
+        };
+        let size_bytes = match buffered.get(..4) {
+            None => return self.read_next_into(buf),
+            Some(size_bytes) => [size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]],
+        };
+        let size = match check_size(size_bytes, self.max_size) {
+            Err(err) => {
+                // As if the size had been read.
+                self.reader.consume(size_bytes.len());
+                return Some(Err(err));
+            }
+            Ok(size) => size,
+        };
+        let document = match buffered.get(..size as usize) {
+            None => return self.read_next_into(buf),
+            Some(document) => document,
+        };
+        buf.clear();
+        buf.extend_from_slice(document);
+        self.reader.consume(size as usize);
+        self.advance(size);
+        Some(Ok(size))
+    }
+
+    /// The next document, read with `read_next_buffered`.
+    pub fn next_buffered(&mut self) -> Option<Result<BsonBytes, Error>> {
+        let position = self.position;
+        let mut bytes = Vec::new();
+        let size = self.read_next_buffered(&mut bytes)?;
+        Some(size.map(|size| BsonBytes { size, bytes, position }))
+    }
+}
+
+impl<'r, R: Read> std::iter::Iterator for Source<'r, R> {
+    type Item = Result<BsonBytes, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.position;
+        let mut bytes = Vec::new();
+        let size = self.read_next_into(&mut bytes)?;
+        Some(size.map(|size| BsonBytes { size, bytes, position }))
+    }
+}
+
+/// Documents read straight out of `bytes`, e.g. a memory-mapped file's, as slices of it rather
+/// than copies. Since there's no telling where the next document starts after one that can't be
+/// read, they end after an error.
+pub fn slice_source(bytes: &[u8]) -> SliceSource<'_> {
+    slice_source_at(bytes, Position::default())
+}
+
+/// A slice source whose documents before `position` have already been read, so that reading
+/// starts at its offset into `bytes`.
+pub fn slice_source_at(bytes: &[u8], position: Position) -> SliceSource<'_> {
+    SliceSource { bytes, position, max_size: DEFAULT_MAX_BSON_SIZE, failed: false }
+}
+
+pub struct SliceSource<'a> {
+    bytes: &'a [u8],
+    position: Position,
+    max_size: u32,
+    failed: bool,
+}
+
+impl<'a> SliceSource<'a> {
+    /// Read documents of up to `max_size` bytes rather than DEFAULT_MAX_BSON_SIZE.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The position of the next document. After an error, the position of the document that
+    /// couldn't be read.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    fn read_document(&mut self) -> Result<&'a bson::RawDocument, Error> {
+        let rest = &self.bytes[self.position.offset as usize..];
+        let size_bytes: [u8; 4] = match rest.get(..4) {
+            None => return Err(Error::TruncatedError(rest.to_vec(), None)),
+            Some(size_bytes) => size_bytes.try_into().unwrap(),
+        };
+        let size = check_size(size_bytes, self.max_size)?;
+        let bytes = match rest.get(..size as usize) {
+            None => return Err(Error::TruncatedError(rest.to_vec(), Some(size))),
+            Some(bytes) => bytes,
+        };
+        if bytes[bytes.len() - 1] != 0 {
+            return Err(Error::UnterminatedError(size));
+        }
+        // Its size and terminator, all that's checked, were just checked.
+        let document = bson::RawDocument::from_bytes(bytes).unwrap();
+        self.position.index += 1;
+        self.position.offset += size as u64;
+        Ok(document)
+    }
+}
+
+impl<'a> std::iter::Iterator for SliceSource<'a> {
+    type Item = Result<&'a bson::RawDocument, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position.offset >= self.bytes.len() as u64 {
+            return None;
+        }
+        let result = self.read_document();
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Writes documents one after another, the way `Source` reads them.
+pub struct Sink<'writer, W: Write> {
+    writer: &'writer mut W,
+    position: Position,
+    max_size: u32,
+    flush_every: Option<u64>,
+}
+
+pub fn sink<W: Write>(writer: &mut W) -> Sink<'_, W> {
+    Sink { writer, position: Position::default(), max_size: DEFAULT_MAX_BSON_SIZE, flush_every: None }
+}
+
+impl<'w, W: Write> Sink<'w, W> {
+    /// Refuse documents of more than `max_size` bytes rather than DEFAULT_MAX_BSON_SIZE.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Flush the writer after every `documents` documents, e.g. 1 so that a reader at the other
+    /// end of a pipe sees each one as soon as it's written. Otherwise it's left to the writer.
+    pub fn flush_every(mut self, documents: u64) -> Self {
+        self.flush_every = Some(documents.max(1));
+        self
+    }
+
+    /// The position the next document will be written at.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn write_doc(&mut self, document: &bson::RawDocument) -> Result<(), Error> {
+        let size = check_write_size(document, self.max_size)?;
+        self.writer.write_all(document.as_bytes()).map_err(Error::IOError)?;
+        self.position.index += 1;
+        self.position.offset += size as u64;
+        if self.flush_every.is_some_and(|documents| self.position.index % documents == 0) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(Error::IOError)
+    }
+}
+
+// The size of a document to be written, if it's no more than `max_size` bytes.
+fn check_write_size(document: &bson::RawDocument, max_size: u32) -> Result<u32, Error> {
+    let size = u32::try_from(document.as_bytes().len()).unwrap_or(u32::MAX);
+    if size > max_size {
+        return Err(Error::TooLargeError(size, max_size));
+    }
+    Ok(size)
+}
+
+/// Writes documents one after another to an asynchronous writer, e.g. a socket, the way `Sink`
+/// writes them to a synchronous one.
+#[cfg(feature = "tokio")]
+pub struct AsyncSink<'writer, W: tokio::io::AsyncWrite + Unpin> {
+    writer: &'writer mut W,
+    position: Position,
+    max_size: u32,
+}
+
+#[cfg(feature = "tokio")]
+pub fn async_sink<W: tokio::io::AsyncWrite + Unpin>(writer: &mut W) -> AsyncSink<'_, W> {
+    AsyncSink { writer, position: Position::default(), max_size: DEFAULT_MAX_BSON_SIZE }
+}
+
+#[cfg(feature = "tokio")]
+impl<'w, W: tokio::io::AsyncWrite + Unpin> AsyncSink<'w, W> {
+    /// Refuse documents of more than `max_size` bytes rather than DEFAULT_MAX_BSON_SIZE.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The position the next document will be written at.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub async fn write_doc(&mut self, document: &bson::RawDocument) -> Result<(), Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let size = check_write_size(document, self.max_size)?;
+        self.writer.write_all(document.as_bytes()).await.map_err(Error::IOError)?;
+        self.position.index += 1;
+        self.position.offset += size as u64;
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        use tokio::io::AsyncWriteExt;
+
+        self.writer.flush().await.map_err(Error::IOError)
+    }
+}
+
+/// Why a document in a stream couldn't be deserialized.
+#[derive(Debug)]
+pub enum DeserializeError {
+    /// It couldn't be read.
+    Read(Error),
+    /// It was read, but doesn't fit the type, e.g. it lacks one of its fields.
+    Deserialize(Position, bson::de::Error),
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeserializeError::Read(err) => err.fmt(f),
+            DeserializeError::Deserialize(position, err) => write!(f, "{}: {}", position, err),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializeError::Read(err) => Some(err),
+            DeserializeError::Deserialize(_, err) => Some(err),
+        }
+    }
+}
+
+/// The documents read from `reader`, deserialized into `T`s. A document that doesn't fit `T` is an
+/// error of its own, but since there's no telling where the next document starts after one that
+/// can't be read, the documents end after a read error.
+pub fn deserialize_stream<T: serde::de::DeserializeOwned, R: Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<T, DeserializeError>> {
+    convert_each(reader, DeserializeError::Read, |document| {
+        bson::from_slice(&document.bytes).map_err(|err| DeserializeError::Deserialize(document.position, err))
+    })
+}
+
+/// Why a document in a stream couldn't be read.
+#[derive(Debug)]
+pub enum DocumentError {
+    /// Its bytes couldn't be read, or its size is wrong.
+    Read(Error),
+    /// Its bytes were read, but they aren't a valid document.
+    Parse(Position, bson::raw::Error),
+}
+
+impl std::fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocumentError::Read(err) => err.fmt(f),
+            DocumentError::Parse(position, err) => write!(f, "{}: {}", position, err),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DocumentError::Read(err) => Some(err),
+            DocumentError::Parse(_, err) => Some(err),
+        }
+    }
+}
+
+/// The documents read from `reader`, parsed into `RawDocumentBuf`s, with one error type for both
+/// steps. A document that doesn't parse is an error of its own, but the documents end after a read
+/// error, as with `deserialize_stream`.
+pub fn documents<R: Read>(reader: R) -> impl Iterator<Item = Result<bson::RawDocumentBuf, DocumentError>> {
+    convert_each(reader, DocumentError::Read, |document| {
+        bson::RawDocumentBuf::from_bytes(document.bytes).map_err(|err| DocumentError::Parse(document.position, err))
+    })
+}
+
+// The documents read from `reader`, each passed through `convert`, ending after a read error.
+fn convert_each<R: Read, T, E>(
+    mut reader: R,
+    read_error: impl Fn(Error) -> E,
+    convert: impl Fn(BsonBytes) -> Result<T, E>,
+) -> impl Iterator<Item = Result<T, E>> {
+    let mut position = Some(Position::default());
+    std::iter::from_fn(move || {
+        let mut documents = source_at(&mut reader, position?);
+        let result = documents.next();
+        position = Some(documents.position());
+        match result? {
+            Err(err) => {
+                position = None;
+                Some(Err(read_error(err)))
+            }
+            Ok(document) => Some(convert(document)),
+        }
+    })
+}
+
+// Validate the size at the head of a document.
+fn check_size(size_bytes: [u8; 4], max_size: u32) -> Result<u32, Error> {
+    let size = i32::from_le_bytes(size_bytes);
+    if size < 0 {
+        return Err(Error::NegativeSizeError(size));
+    }
+    let size = size as u32;
+
+    if size < MIN_BSON_SIZE {
+        return Err(Error::TooSmallError(size as u8));
+    }
+
+    if size > max_size {
+        return Err(Error::TooLargeError(size, max_size));
+    }
+    Ok(size)
+}
+
+/// The documents read from an asynchronous reader, e.g. a socket, framed the way `Source` frames
+/// them, with documents of up to `max_size` bytes. Since there's no telling where the next document
+/// starts after one that can't be read, the stream ends after an error.
+#[cfg(feature = "tokio")]
+pub fn async_source<R: tokio::io::AsyncRead + Unpin>(
+    reader: R,
+    max_size: u32,
+) -> impl futures_core::Stream<Item = Result<BsonBytes, Error>> {
+    futures_util::stream::unfold(Some((reader, Position::default())), move |state| async move {
+        let (mut reader, mut position) = state?;
+        let result = read_document_async(&mut reader, max_size, position).await?;
+        let state = match &result {
+            Err(_) => None,
+            Ok(document) => {
+                position.index += 1;
+                position.offset += document.size as u64;
+                Some((reader, position))
+            }
+        };
+        Some((result, state))
+    })
+}
+
+// Read the next document; None at the end of input.
+#[cfg(feature = "tokio")]
+async fn read_document_async<R: tokio::io::AsyncRead + Unpin>(
+    reader: &mut R,
+    max_size: u32,
+    position: Position,
+) -> Option<Result<BsonBytes, Error>> {
+    let mut size_bytes: [u8; 4] = [0, 0, 0, 0];
+    match read_fully_async(reader, &mut size_bytes).await {
+        Err(err) => return Some(Err(Error::IOError(err))),
+        Ok(0) => return None,
+        Ok(read) if read < size_bytes.len() => {
+            return Some(Err(Error::TruncatedError(size_bytes[..read].to_vec(), None)));
+        }
+        Ok(_) => (),
+    }
+    let size = match check_size(size_bytes, max_size) {
+        Err(err) => return Some(Err(err)),
+        Ok(size) => size,
+    };
+
+    let mut raw_data = vec![0u8; size as usize];
+    raw_data[..size_bytes.len()].copy_from_slice(&size_bytes);
+    match read_fully_async(reader, &mut raw_data[size_bytes.len()..]).await {
+        Err(err) => return Some(Err(Error::IOError(err))),
+        Ok(read) if read < raw_data.len() - size_bytes.len() => {
+            raw_data.truncate(size_bytes.len() + read);
+            return Some(Err(Error::TruncatedError(raw_data, Some(size))));
+        }
+        Ok(_) => (),
+    }
+    Some(Ok(BsonBytes { size, bytes: raw_data, position }))
+}
+
+#[cfg(feature = "tokio")]
+async fn read_fully_async<R: tokio::io::AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    use tokio::io::AsyncReadExt;
+
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]).await {
+            Ok(0) => break,
+            Ok(count) => read += count,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+// Read into `buf` until it's full or the input ends, returning how many bytes were read.
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(count) => read += count,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+/// The elements of a truncated document that were read in full, in a document of their own.
+/// Elements that were cut off, including embedded documents and arrays, are left out.
+pub fn recover_elements(bytes: &[u8]) -> bson::RawDocumentBuf {
+    let mut recovered = bson::RawDocumentBuf::new();
+    if bytes.len() < 4 {
+        return recovered;
+    }
+    // Close the elements read so far with a terminator, so they can be read as a document.
+    let mut closed = Vec::with_capacity(bytes.len() + 1);
+    closed.extend_from_slice(&(bytes.len() as i32 + 1).to_le_bytes());
+    closed.extend_from_slice(&bytes[4..]);
+    closed.push(0);
+    let document = match bson::RawDocument::from_bytes(&closed) {
+        Err(_) => return recovered,
+        Ok(document) => document,
+    };
+    // Past the document's size.
+    let mut offset = 4;
+    for element in document.iter_elements() {
+        let element = match element {
+            Err(_) => break,
+            Ok(element) => element,
+        };
+        // Past the element's type and its key's null terminator.
+        offset += 1 + element.key().len() + 1 + element.len();
+        // An element that runs into the terminator was cut off, even if it happens to parse.
+        let value = match element.value() {
+            Ok(value) if offset <= bytes.len() => value,
+            _ => break,
+        };
+        recovered.append_ref(element.key(), value);
+    }
+    recovered
+}
@@ -0,0 +1,142 @@
+mod tests {
+    use bson::{rawdoc, RawDocumentBuf};
+    use bson_stream::{sink, source, Error, Position};
+
+    fn documents() -> Vec<RawDocumentBuf> {
+        vec![rawdoc! { "a": 1 }, rawdoc! { "b": "two" }, rawdoc! { "c": [3.0] }]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut written = Vec::new();
+        let mut documents_sink = sink(&mut written);
+        for document in documents() {
+            documents_sink.write_doc(&document).expect("Failed to write document");
+        }
+        let end = documents_sink.position();
+        assert_eq!(end, Position { index: 3, offset: written.len() as u64 });
+
+        let mut reader = &written[..];
+        let read: Vec<_> = source(&mut reader).map(|document| document.unwrap()).collect();
+        assert_eq!(
+            read.iter().map(|document| document.bytes.clone()).collect::<Vec<_>>(),
+            documents().iter().map(|document| document.as_bytes().to_vec()).collect::<Vec<_>>()
+        );
+        assert_eq!(read[2].position, Position { index: 2, offset: read[0].size as u64 + read[1].size as u64 });
+
+        // Limits apply both ways.
+        let mut reader = &written[..];
+        assert!(matches!(source(&mut reader).max_size(read[0].size - 1).next(), Some(Err(Error::TooLargeError(..)))));
+        let mut unwritten = Vec::new();
+        let result = sink(&mut unwritten).max_size(5).write_doc(&documents()[0]);
+        assert!(matches!(result, Err(Error::TooLargeError(_, 5))));
+        assert!(unwritten.is_empty());
+    }
+
+    #[test]
+    fn skip_documents() {
+        let mut written = Vec::new();
+        let mut documents_sink = sink(&mut written);
+        for document in documents() {
+            documents_sink.write_doc(&document).expect("Failed to write document");
+        }
+        let mut reader = &written[..];
+        let mut documents_source = source(&mut reader);
+        assert_eq!(documents_source.skip_documents(2).unwrap(), 2);
+        assert_eq!(documents_source.next().unwrap().unwrap().bytes, documents()[2].as_bytes());
+        let mut reader = &written[..];
+        assert_eq!(source(&mut reader).skip_documents(5).unwrap(), 3);
+
+        // A document cut off partway is reported as when reading it.
+        let mut reader = &written[..written.len() - 2];
+        let result = source(&mut reader).skip_documents(3);
+        let size = documents()[2].as_bytes().len();
+        match result {
+            Err(Error::TruncatedError(bytes, Some(bson_size))) => {
+                assert_eq!((bytes.len(), bson_size as usize), (size - 2, size));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn buffered_reads() {
+        use bson_stream::source_at;
+        use std::io::{BufRead, BufReader, Read};
+
+        let mut written = Vec::new();
+        for document in documents() {
+            written.extend_from_slice(document.as_bytes());
+        }
+        written.extend_from_slice(b"tail");
+        // Small enough that some documents span the end of the buffer.
+        let mut reader = BufReader::with_capacity(16, &written[..]);
+        let mut position = Position::default();
+        let mut read = Vec::new();
+        for _ in 0..documents().len() {
+            // A new source each time carries on where the last one stopped.
+            let mut source = source_at(&mut reader, position);
+            let mut buf = Vec::new();
+            source.read_next_buffered(&mut buf).unwrap().expect("Failed to read document");
+            position = source.position();
+            read.push(buf);
+        }
+        let expected: Vec<Vec<u8>> = documents().iter().map(|document| document.as_bytes().to_vec()).collect();
+        assert_eq!(read, expected);
+        assert_eq!(position, Position { index: 3, offset: written.len() as u64 - 4 });
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"tail");
+
+        // A bad size is consumed, as it is by read_next_into.
+        let mut reader = BufReader::new(&[1u8, 0, 0, 0, 9][..]);
+        assert!(matches!(source(&mut reader).next_buffered(), Some(Err(Error::TooSmallError(1)))));
+        assert_eq!(reader.fill_buf().unwrap(), &[9]);
+    }
+
+    #[test]
+    fn parsed_documents() {
+        use bson_stream::{documents as parsed, DocumentError};
+
+        let mut written = Vec::new();
+        for document in documents() {
+            written.extend_from_slice(document.as_bytes());
+        }
+        let read: Vec<RawDocumentBuf> = parsed(&written[..]).map(Result::unwrap).collect();
+        assert_eq!(read, documents());
+
+        // An unterminated document doesn't parse, but the next one is still read.
+        let size = documents()[0].as_bytes().len();
+        written[size - 1] = 1;
+        let read: Vec<_> = parsed(&written[..]).collect();
+        assert_eq!(read.len(), 3);
+        assert!(matches!(read[0], Err(DocumentError::Parse(Position { index: 0, offset: 0 }, _))));
+        assert!(read[1].is_ok());
+
+        // The documents end after one that's cut off.
+        let read: Vec<_> = parsed(&written[..size + 3]).collect();
+        assert_eq!(read.len(), 2);
+        assert!(matches!(read[1], Err(DocumentError::Read(Error::TruncatedError(_, None)))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_round_trip() {
+        use bson_stream::{async_sink, async_source, DEFAULT_MAX_BSON_SIZE};
+        use futures_util::StreamExt;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().expect("Failed to start runtime");
+        let mut written = Vec::new();
+        runtime.block_on(async {
+            let mut documents_sink = async_sink(&mut written);
+            for document in documents() {
+                documents_sink.write_doc(&document).await.expect("Failed to write document");
+            }
+            documents_sink.flush().await.expect("Failed to flush");
+        });
+        let read: Vec<_> = runtime.block_on(async_source(&written[..], DEFAULT_MAX_BSON_SIZE).collect());
+        let read: Vec<Vec<u8>> = read.into_iter().map(|document| document.unwrap().bytes).collect();
+        let expected: Vec<Vec<u8>> = documents().iter().map(|document| document.as_bytes().to_vec()).collect();
+        assert_eq!(read, expected);
+    }
+}
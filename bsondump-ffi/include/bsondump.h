@@ -0,0 +1,50 @@
+/*
+ * A C interface to reading BSON documents one after another, as in a .bson file, and converting
+ * them to extended JSON. Link against the bsondump_ffi library that `cargo build -p bsondump-ffi`
+ * builds.
+ */
+#ifndef BSONDUMP_H
+#define BSONDUMP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct BsondumpStream bsondump_stream;
+
+/*
+ * Reads up to `len` bytes into `buf`, returning how many were read, 0 at the end of input, or a
+ * negative number on an error.
+ */
+typedef intptr_t (*bsondump_read_fn)(void *context, uint8_t *buf, size_t len);
+
+/*
+ * Start reading documents by calling `read` with `context`, converting them to relaxed extended
+ * JSON if `relaxed` is nonzero and canonical otherwise. Free the stream with bsondump_stream_free.
+ */
+bsondump_stream *bsondump_stream_new(bsondump_read_fn read, void *context, int relaxed);
+
+/*
+ * The next document as a NUL-terminated extended JSON string, which stays valid until the next
+ * call or until the stream is freed; NULL at the end of input or on an error, after which
+ * bsondump_stream_error says which.
+ */
+const char *bsondump_stream_next_json(bsondump_stream *stream);
+
+/*
+ * Why bsondump_stream_next_json returned NULL, as a NUL-terminated string that stays valid until
+ * the stream is freed, or NULL if it reached the end of input.
+ */
+const char *bsondump_stream_error(const bsondump_stream *stream);
+
+/* Free a stream. Freeing NULL does nothing. */
+void bsondump_stream_free(bsondump_stream *stream);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,140 @@
+//! A C interface to reading BSON documents one after another, as in a .bson file, and converting
+//! them to extended JSON, for programs that would otherwise run bsondump. See
+//! include/bsondump.h.
+
+use std::{
+    ffi::{c_char, c_int, c_void, CString},
+    io::{self, BufReader, Read},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+
+use bson::RawDocument;
+use bsondump::docbytes::{source_at, Position};
+
+/// Reads up to `len` bytes into `buf`, returning how many were read, 0 at the end of input, or a
+/// negative number on an error.
+pub type ReadFn = extern "C" fn(context: *mut c_void, buf: *mut u8, len: usize) -> isize;
+
+struct CallbackReader {
+    read: ReadFn,
+    context: *mut c_void,
+}
+
+impl Read for CallbackReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = (self.read)(self.context, buf.as_mut_ptr(), buf.len());
+        if count < 0 {
+            return Err(io::Error::other(format!("the read callback returned {}", count)));
+        }
+        Ok((count as usize).min(buf.len()))
+    }
+}
+
+/// The documents read through a callback, converted to extended JSON one at a time.
+pub struct BsondumpStream {
+    reader: BufReader<CallbackReader>,
+    position: Position,
+    relaxed: bool,
+    bytes: Vec<u8>,
+    // The last document's JSON, NUL-terminated.
+    json: Vec<u8>,
+    error: Option<CString>,
+    done: bool,
+}
+
+impl BsondumpStream {
+    fn next_json(&mut self) -> Result<bool, String> {
+        let mut source = source_at(&mut self.reader, self.position);
+        let result = source.read_next_buffered(&mut self.bytes);
+        let position = std::mem::replace(&mut self.position, source.position());
+        match result {
+            None => return Ok(false),
+            Some(Err(err)) => return Err(format!("{}: {}", position, err)),
+            Some(Ok(_)) => {}
+        }
+        self.json.clear();
+        RawDocument::from_bytes(&self.bytes)
+            .map_err(bsondump::Error::from)
+            .and_then(|document| bsondump::write_extjson(&mut self.json, document, self.relaxed))
+            .map_err(|err| format!("{}: {}", position, err))?;
+        self.json.push(0);
+        Ok(true)
+    }
+}
+
+/// Start reading documents by calling `read` with `context`, converting them to relaxed extended
+/// JSON if `relaxed` is nonzero and canonical otherwise. Free the stream with
+/// `bsondump_stream_free`.
+#[no_mangle]
+pub extern "C" fn bsondump_stream_new(read: ReadFn, context: *mut c_void, relaxed: c_int) -> *mut BsondumpStream {
+    let stream = BsondumpStream {
+        reader: BufReader::with_capacity(bsondump::docbytes::READ_BUFFER_SIZE, CallbackReader { read, context }),
+        position: Position::default(),
+        relaxed: relaxed != 0,
+        bytes: Vec::new(),
+        json: Vec::new(),
+        error: None,
+        done: false,
+    };
+    Box::into_raw(Box::new(stream))
+}
+
+/// The next document as a NUL-terminated extended JSON string, which stays valid until the next
+/// call or until the stream is freed; NULL at the end of input or on an error, after which
+/// `bsondump_stream_error` says which.
+///
+/// # Safety
+///
+/// `stream` must have come from `bsondump_stream_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bsondump_stream_next_json(stream: *mut BsondumpStream) -> *const c_char {
+    let stream = match stream.as_mut() {
+        None => return ptr::null(),
+        Some(stream) => stream,
+    };
+    if stream.done {
+        return ptr::null();
+    }
+    // Unwinding into C is undefined, so a panic ends the stream with an error instead.
+    let result = catch_unwind(AssertUnwindSafe(|| stream.next_json()))
+        .unwrap_or_else(|_| Err("bsondump panicked converting the document".to_string()));
+    match result {
+        Ok(true) => stream.json.as_ptr() as *const c_char,
+        Ok(false) => {
+            stream.done = true;
+            ptr::null()
+        }
+        Err(message) => {
+            stream.done = true;
+            stream.error = Some(CString::new(message.replace('\0', "")).unwrap_or_default());
+            ptr::null()
+        }
+    }
+}
+
+/// Why `bsondump_stream_next_json` returned NULL, as a NUL-terminated string that stays valid
+/// until the stream is freed, or NULL if it reached the end of input.
+///
+/// # Safety
+///
+/// `stream` must have come from `bsondump_stream_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bsondump_stream_error(stream: *const BsondumpStream) -> *const c_char {
+    match stream.as_ref().and_then(|stream| stream.error.as_ref()) {
+        None => ptr::null(),
+        Some(error) => error.as_ptr(),
+    }
+}
+
+/// Free a stream. Freeing NULL does nothing.
+///
+/// # Safety
+///
+/// `stream` must have come from `bsondump_stream_new` and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn bsondump_stream_free(stream: *mut BsondumpStream) {
+    if !stream.is_null() {
+        drop(Box::from_raw(stream));
+    }
+}
@@ -0,0 +1,64 @@
+mod tests {
+    use std::ffi::{c_void, CStr};
+
+    use bsondump_ffi::{bsondump_stream_error, bsondump_stream_free, bsondump_stream_new, bsondump_stream_next_json};
+
+    const SAMPLE_BSON: &[u8; 283] = include_bytes!("../../bsondump/tests/testdata/sample.bson");
+    const SAMPLE_JSON: &[u8; 575] = include_bytes!("../../bsondump/tests/testdata/sample.json");
+
+    // Reads from a `&[u8]` at `context`, a few bytes at a time, so that documents span reads.
+    extern "C" fn read_slice(context: *mut c_void, buf: *mut u8, len: usize) -> isize {
+        let remaining = unsafe { &mut *(context as *mut &[u8]) };
+        let count = remaining.len().min(len).min(7);
+        unsafe { std::ptr::copy_nonoverlapping(remaining.as_ptr(), buf, count) };
+        *remaining = &remaining[count..];
+        count as isize
+    }
+
+    extern "C" fn fail(_context: *mut c_void, _buf: *mut u8, _len: usize) -> isize {
+        -1
+    }
+
+    // The documents' JSON, and the error, if any.
+    fn read_all(bytes: &[u8]) -> (Vec<String>, Option<String>) {
+        let mut remaining = bytes;
+        let stream = bsondump_stream_new(read_slice, &mut remaining as *mut &[u8] as *mut c_void, 0);
+        let mut documents = Vec::new();
+        unsafe {
+            loop {
+                let json = bsondump_stream_next_json(stream);
+                if json.is_null() {
+                    break;
+                }
+                documents.push(CStr::from_ptr(json).to_str().unwrap().to_string());
+            }
+            // Ended streams stay ended.
+            assert!(bsondump_stream_next_json(stream).is_null());
+            let error = bsondump_stream_error(stream);
+            let error = (!error.is_null()).then(|| CStr::from_ptr(error).to_str().unwrap().to_string());
+            bsondump_stream_free(stream);
+            (documents, error)
+        }
+    }
+
+    #[test]
+    fn stream() {
+        let (documents, error) = read_all(SAMPLE_BSON);
+        assert_eq!(error, None);
+        let expected: Vec<&str> = std::str::from_utf8(SAMPLE_JSON).unwrap().lines().collect();
+        assert_eq!(documents, expected);
+
+        let (documents, error) = read_all(&SAMPLE_BSON[..200]);
+        assert_eq!(documents.len(), 2);
+        assert!(error.unwrap().starts_with("document #2 at offset 0xa4: "));
+
+        let stream = bsondump_stream_new(fail, std::ptr::null_mut(), 1);
+        unsafe {
+            assert!(bsondump_stream_next_json(stream).is_null());
+            let error = CStr::from_ptr(bsondump_stream_error(stream));
+            assert!(error.to_str().unwrap().contains("the read callback returned -1"));
+            bsondump_stream_free(stream);
+            bsondump_stream_free(std::ptr::null_mut());
+        }
+    }
+}
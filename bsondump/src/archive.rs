@@ -0,0 +1,193 @@
+use std::io::{self, Read};
+
+use bson::RawDocument;
+use glob::Pattern;
+
+// Every archive starts with this number, little-endian.
+const MAGIC: u32 = 0x8199e26d;
+
+// Ends the documents that follow a header.
+const TERMINATOR: u32 = 0xffffffff;
+
+// 4 bytes for the size + 1 byte for the null terminator
+const MIN_BSON_SIZE: u32 = 5;
+
+#[derive(Debug)]
+pub enum Error {
+    IOError(io::Error),
+    NotAnArchiveError,
+    InvalidHeaderError(String),
+    UnexpectedTerminatorError,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::IOError(ref err) => err.fmt(f),
+            Error::NotAnArchiveError => write!(f, "not a mongodump archive"),
+            Error::InvalidHeaderError(message) => write!(f, "invalid archive header: {}", message),
+            Error::UnexpectedTerminatorError => write!(f, "invalid archive: unexpected terminator"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::IOError(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::IOError(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+enum Item {
+    Document(Vec<u8>),
+    Terminator,
+}
+
+enum State {
+    // Expecting the header of the next run of documents, or the end of the archive.
+    Header,
+    // Reading the documents of one namespace, which are passed through when it's selected.
+    Documents { selected: bool },
+}
+
+/// Reads the documents of the selected namespaces out of a mongodump `--archive`, as a stream
+/// of BSON documents like a `.bson` file.
+///
+/// An archive starts with a prelude that lists every namespace in it, followed by runs of
+/// documents each preceded by a header naming the namespace they belong to. mongodump
+/// interleaves the runs of the collections it dumps concurrently.
+pub struct ArchiveReader<R: Read> {
+    reader: R,
+    namespaces: Vec<String>,
+    include: Vec<Pattern>,
+    state: State,
+    document: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    /// Read the prelude of the archive. Namespaces ("db.collection") matching any of the
+    /// `include` patterns are read, or all of them when there are none.
+    pub fn new(mut reader: R, include: Vec<Pattern>) -> Result<Self, Error> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if u32::from_le_bytes(magic) != MAGIC {
+            return Err(Error::NotAnArchiveError);
+        }
+        let mut archive = ArchiveReader {
+            reader,
+            namespaces: Vec::new(),
+            include,
+            state: State::Header,
+            document: Vec::new(),
+            position: 0,
+        };
+        // The archive header, with the versions of the tools that wrote it, is followed by a
+        // document for each collection.
+        match archive.read_item()? {
+            None => return Err(Error::IOError(io::ErrorKind::UnexpectedEof.into())),
+            Some(Item::Terminator) => return Err(Error::UnexpectedTerminatorError),
+            Some(Item::Document(_)) => (),
+        }
+        loop {
+            match archive.read_item()? {
+                None => return Err(Error::IOError(io::ErrorKind::UnexpectedEof.into())),
+                Some(Item::Terminator) => break,
+                Some(Item::Document(bytes)) => {
+                    let namespace = namespace(header(&bytes)?)?;
+                    archive.namespaces.push(namespace);
+                }
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Every namespace listed in the prelude, whether or not it's selected.
+    pub fn namespaces(&self) -> &[String] {
+        &self.namespaces
+    }
+
+    pub fn is_selected(&self, namespace: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches(namespace))
+    }
+
+    // The next document or terminator; None at the end of input.
+    fn read_item(&mut self) -> Result<Option<Item>, Error> {
+        let mut size_bytes = [0u8; 4];
+        if let Err(err) = self.reader.read_exact(&mut size_bytes) {
+            return match err.kind() {
+                io::ErrorKind::UnexpectedEof => Ok(None),
+                _ => Err(Error::IOError(err)),
+            };
+        }
+        let size = u32::from_le_bytes(size_bytes);
+        if size == TERMINATOR {
+            return Ok(Some(Item::Terminator));
+        }
+        if size < MIN_BSON_SIZE {
+            return Err(Error::InvalidHeaderError(format!("{} bytes is less than {} bytes", size, MIN_BSON_SIZE)));
+        }
+        let mut bytes = vec![0u8; size as usize];
+        bytes[..4].copy_from_slice(&size_bytes);
+        self.reader.read_exact(&mut bytes[4..])?;
+        Ok(Some(Item::Document(bytes)))
+    }
+
+    // Read until the next document of a selected namespace; false at the end of the archive.
+    fn next_document(&mut self) -> Result<bool, Error> {
+        loop {
+            let item = self.read_item()?;
+            match (&self.state, item) {
+                (State::Header, None) => return Ok(false),
+                (State::Header, Some(Item::Terminator)) => return Err(Error::UnexpectedTerminatorError),
+                (State::Header, Some(Item::Document(bytes))) => {
+                    let header = header(&bytes)?;
+                    // The last header of each namespace marks its end and has no documents.
+                    let eof = header.get_bool("EOF").unwrap_or(false);
+                    let selected = !eof && self.is_selected(&namespace(header)?);
+                    self.state = State::Documents { selected };
+                }
+                (State::Documents { .. }, None) => return Err(Error::IOError(io::ErrorKind::UnexpectedEof.into())),
+                (State::Documents { .. }, Some(Item::Terminator)) => self.state = State::Header,
+                (State::Documents { selected: false }, Some(Item::Document(_))) => (),
+                (State::Documents { selected: true }, Some(Item::Document(bytes))) => {
+                    self.document = bytes;
+                    self.position = 0;
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for ArchiveReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.document.len() && !self.next_document()? {
+            return Ok(0);
+        }
+        let count = buf.len().min(self.document.len() - self.position);
+        buf[..count].copy_from_slice(&self.document[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+fn header(bytes: &[u8]) -> Result<&RawDocument, Error> {
+    RawDocument::from_bytes(bytes).map_err(|err| Error::InvalidHeaderError(format!("{}", err)))
+}
+
+// The "db.collection" that a prelude document or header refers to.
+fn namespace(header: &RawDocument) -> Result<String, Error> {
+    let field = |key| header.get_str(key).map_err(|err| Error::InvalidHeaderError(format!("{}", err)));
+    Ok(format!("{}.{}", field("db")?, field("collection")?))
+}
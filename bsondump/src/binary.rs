@@ -0,0 +1,133 @@
+#[cfg(feature = "cli")]
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use serde_json::{json, Value};
+
+/// How binary UUIDs (subtypes 3 and 4) are rendered in extended JSON.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UuidFormat {
+    /// `{"$uuid": "00112233-4455-6677-8899-aabbccddeeff"}`
+    ExtJson,
+    /// `"00112233-4455-6677-8899-aabbccddeeff"`
+    String,
+}
+
+/// The byte order used by the driver that wrote a legacy (subtype 3) UUID.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum LegacyUuidRepresentation {
+    /// Bytes are stored in standard order.
+    #[default]
+    Python,
+    /// Each 8-byte half is stored reversed.
+    Java,
+    /// The first three groups are stored little-endian.
+    CSharp,
+}
+
+const UUID_SUBTYPE_LEGACY: &str = "03";
+const UUID_SUBTYPE: &str = "04";
+const UUID_LENGTH: usize = 16;
+
+fn to_standard_order(bytes: &mut [u8], subtype: &str, representation: LegacyUuidRepresentation) {
+    if subtype != UUID_SUBTYPE_LEGACY {
+        return;
+    }
+    match representation {
+        LegacyUuidRepresentation::Python => (),
+        LegacyUuidRepresentation::Java => {
+            bytes[0..8].reverse();
+            bytes[8..16].reverse();
+        }
+        LegacyUuidRepresentation::CSharp => {
+            bytes[0..4].reverse();
+            bytes[4..6].reverse();
+            bytes[6..8].reverse();
+        }
+    }
+}
+
+fn format_uuid(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+// Returns the UUID held by an extended JSON `$binary` wrapper, if it is one.
+fn extract_uuid(value: &Value, representation: LegacyUuidRepresentation) -> Option<String> {
+    let binary = value.as_object().filter(|map| map.len() == 1)?.get("$binary")?;
+    let subtype = binary.get("subType")?.as_str()?;
+    if subtype != UUID_SUBTYPE && subtype != UUID_SUBTYPE_LEGACY {
+        return None;
+    }
+    let mut bytes = base64::decode(binary.get("base64")?.as_str()?).ok()?;
+    if bytes.len() != UUID_LENGTH {
+        return None;
+    }
+    to_standard_order(&mut bytes, subtype, representation);
+    Some(format_uuid(&bytes))
+}
+
+/// Recursively replace `$binary` UUIDs in an extended JSON value with their textual form.
+pub fn render_uuids(value: &mut Value, format: UuidFormat, representation: LegacyUuidRepresentation) {
+    if let Some(uuid) = extract_uuid(value, representation) {
+        *value = match format {
+            UuidFormat::ExtJson => json!({ "$uuid": uuid }),
+            UuidFormat::String => Value::String(uuid),
+        };
+        return;
+    }
+    match value {
+        Value::Object(map) => map.values_mut().for_each(|entry| render_uuids(entry, format, representation)),
+        Value::Array(array) => array.iter_mut().for_each(|entry| render_uuids(entry, format, representation)),
+        _ => (),
+    }
+}
+
+/// Where binary values of at least `threshold` bytes are written instead of being inlined in
+/// extended JSON as base64.
+#[cfg(feature = "cli")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryDir {
+    pub path: PathBuf,
+    pub threshold: usize,
+}
+
+// The bytes of an extended JSON `$binary` wrapper of at least `threshold` bytes, and its subtype.
+#[cfg(feature = "cli")]
+fn extract_large_binary(value: &Value, threshold: usize) -> Option<(Vec<u8>, &Value)> {
+    let binary = value.as_object().filter(|map| map.len() == 1)?.get("$binary")?;
+    let base64 = binary.get("base64")?.as_str()?;
+    // Skip decoding values that are too small by their encoded length.
+    let padding = base64.bytes().rev().take_while(|byte| *byte == b'=').count();
+    if (base64.len() / 4 * 3).saturating_sub(padding) < threshold {
+        return None;
+    }
+    Some((base64::decode(base64).ok()?, binary.get("subType")?))
+}
+
+/// Recursively write the `$binary` values in an extended JSON value that are at least as large as
+/// the directory's threshold to files in it, named by the SHA-256 of their bytes so that equal
+/// values share a file, and replace them with `{"$binaryRef": path, "length": N, "subType": ...}`.
+#[cfg(feature = "cli")]
+pub fn externalize_binaries(value: &mut Value, dir: &BinaryDir) -> io::Result<()> {
+    if let Some((bytes, subtype)) = extract_large_binary(value, dir.threshold) {
+        let name: String = crate::checksum::sha256(&bytes).iter().map(|byte| format!("{:02x}", byte)).collect();
+        let path = dir.path.join(format!("{}.bin", name));
+        if !path.exists() {
+            // Written whole and then moved into place, so that a file that's there is complete, even
+            // while another thread writes the same value.
+            let mut file = tempfile::NamedTempFile::new_in(&dir.path)?;
+            file.write_all(&bytes)?;
+            file.persist(&path).map_err(|err| err.error)?;
+        }
+        *value = json!({ "$binaryRef": path.to_string_lossy(), "length": bytes.len(), "subType": subtype });
+        return Ok(());
+    }
+    match value {
+        Value::Object(map) => map.values_mut().try_for_each(|entry| externalize_binaries(entry, dir)),
+        Value::Array(array) => array.iter_mut().try_for_each(|entry| externalize_binaries(entry, dir)),
+        _ => Ok(()),
+    }
+}
@@ -1,9 +1,12 @@
-use bson::{RawArray, RawBsonRef, RawDocument};
+use bson::{RawArray, RawBsonRef, RawDocument, RawDocumentBuf};
 
+/// The number of bytes a value takes up in BSON. For an element's value, that's its bytes after
+/// its type and key, so the whole element takes 1 + the key's length + 1 + this many bytes.
 pub trait CountBytes {
     fn count_bytes(&self) -> usize;
 }
 
+/// Counted as a BSON string: its size, its bytes and a null terminator.
 impl CountBytes for &str {
     fn count_bytes(&self) -> usize {
         // i32 size + characters + null terminator
@@ -11,6 +14,11 @@ impl CountBytes for &str {
     }
 }
 
+// A key or a regular expression's pattern or options: its bytes and a null terminator.
+fn cstring_bytes(cstring: &str) -> usize {
+    cstring.len() + 1
+}
+
 impl CountBytes for RawDocument {
     fn count_bytes(&self) -> usize {
         self.as_bytes().len()
@@ -32,13 +40,10 @@ impl CountBytes for bson::RawBsonRef<'_> {
             RawBsonRef::Document(raw_document) => raw_document.count_bytes(),
             RawBsonRef::Boolean(_) => 1,
             RawBsonRef::Null => 0,
-            RawBsonRef::RegularExpression(regex) => {
-                regex.pattern.count_bytes() + regex.options.count_bytes()
-            }
+            RawBsonRef::RegularExpression(regex) => cstring_bytes(regex.pattern) + cstring_bytes(regex.options),
             RawBsonRef::JavaScriptCode(code) => code.count_bytes(),
-            RawBsonRef::JavaScriptCodeWithScope(cws) => {
-                cws.code.count_bytes() + cws.scope.count_bytes()
-            }
+            // i32 size of the whole + code + scope
+            RawBsonRef::JavaScriptCodeWithScope(cws) => 4 + cws.code.count_bytes() + cws.scope.count_bytes(),
             RawBsonRef::Int32(_) => 4,
             RawBsonRef::Int64(_) => 8,
             RawBsonRef::Timestamp(_) => 8,
@@ -50,7 +55,13 @@ impl CountBytes for bson::RawBsonRef<'_> {
             RawBsonRef::Undefined => 0,
             RawBsonRef::MaxKey => 0,
             RawBsonRef::MinKey => 0,
-            RawBsonRef::DbPointer(_) => "".count_bytes() + 12,
+            RawBsonRef::DbPointer(_) => {
+                // Its namespace isn't exposed, so measure it in an element of its own, less the
+                // document's size and terminator and the element's type and empty key.
+                let mut document = RawDocumentBuf::new();
+                document.append_ref("", *self);
+                document.as_bytes().len() - 4 - 1 - 1 - 1
+            }
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::io::{self, Write};
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Crc32,
+}
+
+impl Algorithm {
+    /// The name of the digest, which is also the extension of the sidecar file it's written to.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Crc32 => "crc32",
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+        }
+    }
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Crc32(crc32fast::Hasher),
+}
+
+impl Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Crc32(hasher) => hasher.update(bytes),
+        }
+    }
+
+    // The digest in hex.
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect(),
+            Hasher::Crc32(hasher) => format!("{:08x}", hasher.finalize()),
+        }
+    }
+}
+
+/// Digests of each document of an output and of the output as a whole, written as JSON lines to a
+/// sidecar file: `{"document": 0, "sha256": "..."}` for each document, then
+/// `{"documents": 4, "sha256": "..."}` for the whole output.
+pub struct Checksums<W: Write> {
+    algorithm: Algorithm,
+    sidecar: W,
+    document: Hasher,
+    stream: Hasher,
+    documents: u64,
+}
+
+impl<W: Write> Checksums<W> {
+    pub fn new(algorithm: Algorithm, sidecar: W) -> Self {
+        Checksums { algorithm, sidecar, document: algorithm.hasher(), stream: algorithm.hasher(), documents: 0 }
+    }
+
+    /// Add output bytes to the digest of the current document and of the whole output.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.document.update(bytes);
+        self.stream.update(bytes);
+    }
+
+    /// The bytes so far, e.g. a csv header, aren't part of any document.
+    pub fn end_header(&mut self) {
+        self.document = self.algorithm.hasher();
+    }
+
+    pub fn end_document(&mut self) -> io::Result<()> {
+        let digest = std::mem::replace(&mut self.document, self.algorithm.hasher()).finish();
+        writeln!(self.sidecar, "{}", json!({"document": self.documents, self.algorithm.name(): digest}))?;
+        self.documents += 1;
+        Ok(())
+    }
+
+    /// Write the digest of the whole output, returning the sidecar it was written to.
+    pub fn finish(mut self) -> io::Result<W> {
+        let digest = self.stream.finish();
+        writeln!(self.sidecar, "{}", json!({"documents": self.documents, self.algorithm.name(): digest}))?;
+        self.sidecar.flush()?;
+        Ok(self.sidecar)
+    }
+}
+
+/// The SHA-256 digest of `bytes`.
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// The HMAC-SHA-256 of `message` with `key`, as specified in RFC 2104.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
@@ -0,0 +1,2875 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs::File,
+    io::{stdin, stdout, BufRead, BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use crate::compression::{buffered, Compression};
+use clap::{ArgEnum, CommandFactory, Parser};
+use clap_verbosity_flag::{InfoLevel, Verbosity};
+use log::{info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::ser::{CompactFormatter, PrettyFormatter};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+enum CompressionType {
+    Gzip,
+    Zstd,
+    Snappy,
+}
+
+impl From<CompressionType> for Compression {
+    fn from(compression: CompressionType) -> Self {
+        match compression {
+            CompressionType::Gzip => Compression::Gzip,
+            CompressionType::Zstd => Compression::Zstd,
+            CompressionType::Snappy => Compression::Snappy,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+enum ChecksumType {
+    Sha256,
+    Crc32,
+}
+
+impl From<ChecksumType> for crate::checksum::Algorithm {
+    fn from(checksum: ChecksumType) -> Self {
+        match checksum {
+            ChecksumType::Sha256 => crate::checksum::Algorithm::Sha256,
+            ChecksumType::Crc32 => crate::checksum::Algorithm::Crc32,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[clap(rename_all = "camelCase")]
+enum LintRule {
+    DottedKeys,
+    LongKeys,
+    LongArrays,
+    DeepDocuments,
+    MixedTypes,
+}
+
+impl From<LintRule> for crate::lint::Rule {
+    fn from(rule: LintRule) -> Self {
+        match rule {
+            LintRule::DottedKeys => crate::lint::Rule::DottedKeys,
+            LintRule::LongKeys => crate::lint::Rule::LongKeys,
+            LintRule::LongArrays => crate::lint::Rule::LongArrays,
+            LintRule::DeepDocuments => crate::lint::Rule::DeepDocuments,
+            LintRule::MixedTypes => crate::lint::Rule::MixedTypes,
+        }
+    }
+}
+
+/// The format documents are output in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[clap(rename_all = "camelCase")]
+pub enum OutputType {
+    Debug,
+    Json,
+    PrettyJson,
+    RelaxedJson,
+    Csv,
+    Yaml,
+    Hex,
+    Bson,
+    Table,
+    Schema,
+    Stats,
+    Parquet,
+    Lint,
+}
+
+impl OutputType {
+    // File extension for output written next to each file of a dump directory.
+    fn extension(self) -> &'static str {
+        match self {
+            OutputType::Json | OutputType::PrettyJson | OutputType::RelaxedJson | OutputType::Lint => "json",
+            OutputType::Csv => "csv",
+            OutputType::Yaml => "yaml",
+            OutputType::Bson => "bson",
+            OutputType::Parquet => "parquet",
+            OutputType::Debug | OutputType::Hex | OutputType::Table | OutputType::Schema | OutputType::Stats => "txt",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[clap(rename_all = "camelCase")]
+enum UuidFormat {
+    ExtJson,
+    String,
+}
+
+impl From<UuidFormat> for crate::binary::UuidFormat {
+    fn from(format: UuidFormat) -> Self {
+        match format {
+            UuidFormat::ExtJson => crate::binary::UuidFormat::ExtJson,
+            UuidFormat::String => crate::binary::UuidFormat::String,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[clap(rename_all = "camelCase")]
+enum UuidRepresentation {
+    #[clap(name = "pythonLegacy")]
+    Python,
+    #[clap(name = "javaLegacy")]
+    Java,
+    #[clap(name = "csharpLegacy")]
+    CSharp,
+}
+
+impl From<UuidRepresentation> for crate::binary::LegacyUuidRepresentation {
+    fn from(representation: UuidRepresentation) -> Self {
+        match representation {
+            UuidRepresentation::Python => crate::binary::LegacyUuidRepresentation::Python,
+            UuidRepresentation::Java => crate::binary::LegacyUuidRepresentation::Java,
+            UuidRepresentation::CSharp => crate::binary::LegacyUuidRepresentation::CSharp,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[clap(rename_all = "camelCase")]
+enum DecimalFormat {
+    ExtJson,
+    Number,
+    String,
+}
+
+impl From<DecimalFormat> for crate::decimal::DecimalFormat {
+    fn from(format: DecimalFormat) -> Self {
+        match format {
+            DecimalFormat::ExtJson => crate::decimal::DecimalFormat::ExtJson,
+            DecimalFormat::Number => crate::decimal::DecimalFormat::Number,
+            DecimalFormat::String => crate::decimal::DecimalFormat::String,
+        }
+    }
+}
+
+// Outputs that have to be a single file.
+const SPLIT_CONFLICTS: &[&str] = &["jsonArray", "count", "fieldStats"];
+
+/// The command line, which `run` carries out.
+#[derive(Parser)]
+#[clap(
+    rename_all = "camelCase",
+    after_help = "Exits with 1 for a usage error, 2 for an I/O error, 3 for corrupt input, documents skipped by \
+                  --continueOnError, duplicate _ids or documents --verifyAgainst didn't find, and 4 for a document \
+                  over the size or nesting limits."
+)]
+pub struct Cli {
+    /// Paths, http URLs or S3 objects (s3://bucket/key) of BSON files to dump to JSON, read in order
+    /// as one stream, or paths to mongodump directories to dump each collection to its own file;
+    /// default is stdin
+    files: Vec<String>,
+
+    #[clap(long = "bsonFile", name = "bsonFile", conflicts_with = "files")]
+    /// Path to the BSON file to dump, as an alternative to the positional argument
+    bson_file: Option<String>,
+
+    #[clap(flatten)]
+    verbose: Verbosity<InfoLevel>,
+
+    #[clap(name="type", long="type", arg_enum, default_value_t = OutputType::Json)]
+    // type of output: debug, json, prettyJson, relaxedJson, csv, yaml, hex, bson, table, schema, stats, parquet,
+    // lint
+    output_type: OutputType,
+
+    #[clap(long, conflicts_with = "noobjcheck")]
+    /// Validate the structure of each document's BSON, and that it converts, during processing; this
+    /// is the default
+    objcheck: bool,
+
+    #[clap(long)]
+    /// Don't validate documents; ones that fail to convert are skipped
+    noobjcheck: bool,
+
+    #[clap(long = "validateReport", name = "validateReport", conflicts_with = "noobjcheck")]
+    /// Write a JSON report of whether each document is valid to this file, and skip invalid
+    /// documents instead of stopping at the first
+    validate_report: Option<String>,
+
+    #[clap(long = "maxDepth", name = "maxDepth", default_value_t = 200)]
+    /// Fail on documents nested more than this many levels deep, which could otherwise exhaust the
+    /// stack while converting them
+    max_depth: usize,
+
+    #[clap(long = "continueOnError", name = "continueOnError")]
+    /// Log and skip documents that are invalid or fail to convert instead of stopping at the first,
+    /// then exit with 3 if any were skipped
+    continue_on_error: bool,
+
+    #[clap(long = "allowPartial", name = "allowPartial")]
+    /// When an input ends partway through a document, output the elements of it that were read in
+    /// full instead of failing
+    allow_partial: bool,
+
+    #[clap(long = "maxBsonSize", name = "maxBsonSize", default_value = "16793600")]
+    /// Fail on documents larger than this, e.g. 64MB; the default is mongod's 16MB limit plus 16KB
+    /// for oplog entries, but BSON from elsewhere can be larger
+    max_bson_size: crate::units::ByteSize,
+
+    #[clap(long = "checkDuplicateIds", name = "checkDuplicateIds")]
+    /// Report documents that share an _id, which would fail a restore, and exit with 3 if any do
+    check_duplicate_ids: bool,
+
+    #[clap(long = "warnDeprecated", name = "warnDeprecated")]
+    /// Warn about each Symbol, Undefined and DBPointer element, which newer drivers may not read,
+    /// then log how many were found at each field
+    warn_deprecated: bool,
+
+    #[clap(long = "failDeprecated", name = "failDeprecated")]
+    /// Fail on documents with Symbol, Undefined or DBPointer elements
+    fail_deprecated: bool,
+
+    #[clap(long = "verifyAgainst", name = "verifyAgainst", requires = "verifyNamespace")]
+    /// Check that the _id of every document is in the collection --verifyNamespace names on the
+    /// cluster at this mongodb:// URI, and exit with 3 if any aren't; needs the client feature
+    verify_against: Option<String>,
+
+    #[clap(long = "verifyNamespace", name = "verifyNamespace", requires = "verifyAgainst")]
+    /// The db.collection to check the documents against
+    verify_namespace: Option<String>,
+
+    #[clap(long = "verifyDocuments", name = "verifyDocuments", requires = "verifyAgainst")]
+    /// Also check that the documents in the collection have the same bytes as the ones dumped
+    verify_documents: bool,
+
+    #[clap(long = "strictUtf8", name = "strictUtf8")]
+    /// Fail on string values that aren't valid UTF-8 instead of replacing the invalid bytes with
+    /// U+FFFD
+    strict_utf8: bool,
+
+    #[clap(long = "binaryFallback", name = "binaryFallback", conflicts_with = "strictUtf8")]
+    /// Output string values that aren't valid UTF-8 as $binary, so that none of their bytes are lost
+    binary_fallback: bool,
+
+    #[clap(long)]
+    /// Decompress gzip input; same as --compression gzip
+    gzip: bool,
+
+    #[clap(long, arg_enum, conflicts_with = "gzip")]
+    /// Decompress input: gzip, zstd or snappy (framed). Detected from the file extension or the
+    /// first bytes when not given
+    compression: Option<CompressionType>,
+
+    #[clap(long = "forceStdin", name = "forceStdin", alias = "force-stdin")]
+    /// Read from stdin even when it's a terminal
+    force_stdin: bool,
+
+    #[clap(long)]
+    /// Write binary output (bson, parquet or compressed) even when stdout is a terminal
+    force: bool,
+
+    #[clap(short, long, conflicts_with_all = &["tail", "sampleCount", "count", "fieldStats", "archive"])]
+    /// Keep reading the last file as documents are appended to it, like `tail -f`
+    follow: bool,
+
+    #[clap(long = "ioUring", name = "ioUring", conflicts_with = "follow")]
+    /// Read input files through io_uring, with several large reads in flight at once; needs
+    /// bsondump to be built for Linux with the io-uring feature
+    io_uring: bool,
+
+    #[clap(long, conflicts_with_all = &["follow", "ioUring"])]
+    /// Read uncompressed input files through a memory mapping, taking each document straight out
+    /// of it rather than copying it through a buffer. The files mustn't change while they're read
+    mmap: bool,
+
+    #[clap(long, conflicts_with_all = &["files", "skipBytes"])]
+    /// Path to a mongodump --archive file to read instead of BSON files
+    archive: Option<String>,
+
+    #[clap(long = "nsInclude", name = "nsInclude", requires = "archive", multiple_occurrences = true)]
+    /// Namespace to dump from the archive, e.g. "db.collection" or "db.*"; may be repeated. Default
+    /// is every namespace
+    ns_include: Vec<glob::Pattern>,
+
+    #[clap(long = "splitEvery", name = "splitEvery", requires = "outFile", conflicts_with_all = SPLIT_CONFLICTS)]
+    /// Split the output into files of this many documents each: out.json becomes out.0001.json,
+    /// out.0002.json, ...
+    split_every: Option<u64>,
+
+    #[clap(long = "splitSize", name = "splitSize", requires = "outFile", conflicts_with_all = SPLIT_CONFLICTS)]
+    /// Split the output into files of about this size before compression, e.g. 500MB or 1GB
+    split_size: Option<crate::units::ByteSize>,
+
+    #[clap(long = "gzipOutput", name = "gzipOutput")]
+    /// Compress the output with gzip; same as --compressOutput gzip
+    gzip_output: bool,
+
+    #[clap(long = "compressOutput", name = "compressOutput", arg_enum, conflicts_with = "gzipOutput")]
+    /// Compress the output as it's written: gzip, zstd or snappy (framed)
+    compress_output: Option<CompressionType>,
+
+    #[clap(long, arg_enum)]
+    /// Write a sha256 or crc32 digest of each document's output, and of the whole output before
+    /// compression, to a file beside the output, e.g. out.json.sha256
+    checksum: Option<ChecksumType>,
+
+    #[clap(long = "skipBytes", name = "skipBytes", default_value_t = 0)]
+    /// Byte offset of the document to start at, e.g. to resume an interrupted conversion
+    skip_bytes: u64,
+
+    #[clap(long, visible_alias = "startAtDoc")]
+    /// Number of documents to skip, without parsing them, before processing the rest
+    skip: Option<u64>,
+
+    #[clap(long, visible_alias = "head")]
+    /// Maximum number of documents to output
+    limit: Option<u32>,
+
+    #[clap(long, conflicts_with = "sampleCount")]
+    /// Output only the last N documents
+    tail: Option<usize>,
+
+    #[clap(long, conflicts_with = "sampleCount")]
+    /// Output each document with this probability, between 0 and 1
+    sample: Option<f64>,
+
+    #[clap(long = "sampleCount", name = "sampleCount")]
+    /// Output a uniformly random sample of this many documents, in their original order
+    sample_count: Option<usize>,
+
+    #[clap(long)]
+    /// Seed for --sample and --sampleCount, to draw the same sample again
+    seed: Option<u64>,
+
+    #[clap(long, conflicts_with = "jsonArray")]
+    /// Print only the number of documents found instead of the documents themselves
+    count: bool,
+
+    #[clap(long, value_parser = read_redactions)]
+    /// Redact the fields listed in this file, one per line, each followed by how: null (the
+    /// default), hash, mask or randomize
+    redact: Option<crate::redact::Rules>,
+
+    #[clap(long = "redactKey", name = "redactKey")]
+    /// The secret key that --redact hashes values with
+    redact_key: Option<String>,
+
+    #[clap(long)]
+    /// Only output documents matching this query, e.g. '{"status": "active", "n": {"$gt": 5}}'
+    filter: Option<crate::filter::Filter>,
+
+    #[clap(long)]
+    /// Only output documents with a string value matching this regular expression
+    grep: Option<regex::Regex>,
+
+    #[clap(long = "grepField", name = "grepField", requires = "grep")]
+    /// Only match --grep against string values at or under this dot-separated path
+    grep_field: Option<String>,
+
+    #[clap(long = "fieldStats", name = "fieldStats", conflicts_with = "count")]
+    /// Report how many documents contain, have null at, or are missing each field path, as JSON or a table
+    field_stats: bool,
+
+    #[clap(long, conflicts_with = "jsonArray")]
+    /// Output only the value at this dot-separated path, skipping documents without it; JSON output types only
+    extract: Option<String>,
+
+    #[clap(long, conflicts_with_all = &["jsonArray", "extract"])]
+    /// Describe oplog entries by operation kind, namespace and time, with the operations of
+    /// applyOps commands expanded after them; JSON output types only
+    oplog: bool,
+
+    #[clap(long = "oplogNs", name = "oplogNs", multiple_occurrences = true)]
+    /// Output only oplog entries that touch a namespace matching this pattern, e.g. "mydb.orders" or
+    /// "mydb.*"; may be repeated
+    oplog_ns: Vec<glob::Pattern>,
+
+    #[clap(long = "oplogStart", name = "oplogStart")]
+    /// Output only oplog entries at or after this time: seconds,increment or an ISO-8601 date or time
+    oplog_start: Option<crate::oplog::OplogTime>,
+
+    #[clap(long = "oplogEnd", name = "oplogEnd")]
+    /// Output only oplog entries before this time: seconds,increment or an ISO-8601 date or time
+    oplog_end: Option<crate::oplog::OplogTime>,
+
+    #[clap(long = "keepMissing", name = "keepMissing", requires = "extract")]
+    /// Output null for documents without the --extract path instead of skipping them
+    keep_missing: bool,
+
+    #[clap(long = "jsonArray", name = "jsonArray")]
+    /// Output a single JSON array instead of one document per line; JSON output types only
+    json_array: bool,
+
+    #[clap(long = "asciiOutput", name = "asciiOutput")]
+    /// Escape every non-ASCII character in JSON output as \uXXXX, for consumers that don't handle
+    /// UTF-8; JSON output types only
+    ascii_output: bool,
+
+    #[clap(long, value_delimiter = ',')]
+    /// Comma-separated list of dot-separated field paths to output; required for csv, optional for table
+    fields: Vec<String>,
+
+    #[clap(long = "lintRules", name = "lintRules", arg_enum, value_delimiter = ',')]
+    /// Comma-separated list of rules for --type=lint to check: dottedKeys, longKeys, longArrays,
+    /// deepDocuments and mixedTypes; default is all of them
+    lint_rules: Vec<LintRule>,
+
+    #[clap(long = "lintMaxKeyBytes", name = "lintMaxKeyBytes", default_value_t = 128)]
+    /// Keys longer than this break the longKeys rule
+    lint_max_key_bytes: usize,
+
+    #[clap(long = "lintMaxArrayLength", name = "lintMaxArrayLength", default_value_t = 1000)]
+    /// Arrays with more elements than this break the longArrays rule
+    lint_max_array_length: usize,
+
+    #[clap(long = "lintMaxDepth", name = "lintMaxDepth", default_value_t = 20)]
+    /// Documents nested more than this many levels deep break the deepDocuments rule
+    lint_max_depth: usize,
+
+    #[clap(long = "sortBy", name = "sortBy")]
+    /// Output documents sorted by the value at this dot-separated path, e.g. "ts" or "ts:desc",
+    /// spilling to temporary files when there are too many to sort in memory. Documents are sorted
+    /// as they're output, after --fields and --excludeFields
+    sort_by: Option<crate::sort::SortKey>,
+
+    #[clap(long = "maxMemory", name = "maxMemory")]
+    /// Hold no more than about this many bytes of documents in memory at once, e.g. 512MB: --sortBy
+    /// and --checkDuplicateIds spill to temporary files sooner, --threads converts fewer ahead of
+    /// writing, and --tail and --sampleCount fail rather than hold more
+    max_memory: Option<crate::units::ByteSize>,
+
+    #[clap(long, default_value_t = 1)]
+    /// Convert documents on this many threads, alongside the ones reading and writing them, still
+    /// outputting them in order; json, csv, yaml, hex, debug and bson output only
+    threads: usize,
+
+    #[clap(long)]
+    /// Report how long the run took, how many documents and bytes a second it read and wrote, and
+    /// how long it spent reading, converting and writing documents, summed across threads
+    timing: bool,
+
+    #[clap(long, multiple_occurrences = true)]
+    /// Move the field at a dot-separated path to another, e.g. "name.first=firstName", before
+    /// --fields, --excludeFields and --redact see the document; may be repeated
+    rename: Vec<crate::transform::Rename>,
+
+    #[clap(long, multiple_occurrences = true)]
+    /// Remove the field at a dot-separated path, after --rename; may be repeated
+    drop: Vec<String>,
+
+    #[clap(long = "excludeFields", name = "excludeFields", value_delimiter = ',')]
+    /// Comma-separated list of dot-separated field paths to strip from each document
+    exclude_fields: Vec<String>,
+
+    #[clap(long = "pageSize", name = "pageSize")]
+    /// Number of rows per page of table output; default fits the terminal
+    page_size: Option<usize>,
+
+    #[clap(long = "typeNames", name = "typeNames")]
+    /// Print type names instead of numeric type codes in debug output
+    type_names: bool,
+
+    #[clap(long = "showValues", name = "showValues")]
+    /// Print a truncated rendering of each element's value in debug output
+    show_values: bool,
+
+    #[clap(long = "sortKeys", name = "sortKeys")]
+    /// Sort the keys of JSON and YAML objects lexicographically
+    sort_keys: bool,
+
+    #[clap(long, arg_enum)]
+    /// Render binary UUIDs (subtypes 3 and 4) as {"$uuid": ...} (extJson) or as a plain string
+    uuid: Option<UuidFormat>,
+
+    #[clap(long = "uuidRepresentation", name = "uuidRepresentation", arg_enum, default_value_t = UuidRepresentation::Python)]
+    /// Byte order of legacy (subtype 3) UUIDs when using --uuid
+    uuid_representation: UuidRepresentation,
+
+    #[clap(long = "binaryDir", name = "binaryDir")]
+    /// Write binary values of at least --binaryThreshold bytes to files in this directory, named by
+    /// the SHA-256 of their bytes, and output {"$binaryRef": path, "length": N, "subType": ...} in
+    /// their place
+    binary_dir: Option<PathBuf>,
+
+    #[clap(long = "binaryThreshold", name = "binaryThreshold", requires = "binaryDir", default_value = "1MB")]
+    /// The size from which --binaryDir writes binary values to files, e.g. 64KB
+    binary_threshold: crate::units::ByteSize,
+
+    #[clap(long = "dateFormat", name = "dateFormat")]
+    /// Render $date values as iso8601-local, iso8601-utc, epoch-millis, or a strftime pattern (in UTC)
+    date_format: Option<crate::datetime::DateFormat>,
+
+    #[clap(long = "decimalFormat", name = "decimalFormat", arg_enum, default_value_t = DecimalFormat::ExtJson)]
+    /// Render Decimal128 values as {"$numberDecimal": ...} (extJson), a JSON number, or a string
+    decimal_format: DecimalFormat,
+
+    #[clap(long = "parquetSchema", name = "parquetSchema")]
+    /// Path to a Parquet message type to write instead of inferring one from the first row group
+    parquet_schema: Option<String>,
+
+    #[clap(long, default_value = "tab")]
+    /// Indentation for prettyJson output: "tab" or a number of spaces
+    indent: crate::Indent,
+
+    #[clap(long, arg_enum, default_value_t = ColorChoice::Auto)]
+    /// Colorize JSON output: auto (only when writing to a terminal), always, never
+    color: ColorChoice,
+
+    #[clap(long = "outFile", name = "outFile")]
+    /// Path to output file, or S3 object, to dump JSON to; default is stdout. A template such as
+    /// '{dir}/{stem}.json' ({dir}, {name} and {stem} of each input) writes each input to its own file
+    out_file: Option<String>,
+
+    #[clap(long = "outputBufferSize", name = "outputBufferSize", default_value = "64KB")]
+    /// How much output to hold before writing it, e.g. 1MB; larger buffers make fewer, larger writes
+    output_buffer_size: crate::units::ByteSize,
+
+    #[clap(long = "outDir", name = "outDir")]
+    /// Directory to write one output file per collection to when dumping a directory; default is
+    /// next to each .bson file
+    out_dir: Option<PathBuf>,
+
+    #[clap(
+        long = "includeMetadata",
+        name = "includeMetadata",
+        conflicts_with_all = &["jsonArray", "count", "fieldStats"]
+    )]
+    /// When writing each collection to its own file, start it with the collection's options, indexes
+    /// and UUID from its .metadata.json; JSON output types only
+    include_metadata: bool,
+}
+
+fn read_redactions(path: &str) -> Result<crate::redact::Rules, String> {
+    let rules = std::fs::read_to_string(path).map_err(|err| format!("Failed to read {}. {}", path, err))?;
+    rules.parse().map_err(|err: crate::redact::ParseRulesError| err.to_string())
+}
+
+impl Cli {
+    /// How much to log, from -v and -q.
+    pub fn log_level_filter(&self) -> log::LevelFilter {
+        self.verbose.log_level_filter()
+    }
+
+    // Documents are validated unless --noobjcheck is given; --objcheck is accepted for
+    // compatibility with the Go tool.
+    fn objcheck(&self) -> bool {
+        !self.noobjcheck
+    }
+
+    fn output_buffer_size(&self) -> usize {
+        usize::try_from(self.output_buffer_size.0).unwrap_or(usize::MAX)
+    }
+
+    fn output_compression(&self) -> Option<Compression> {
+        if self.gzip_output {
+            Some(Compression::Gzip)
+        } else {
+            self.compress_output.map(Compression::from)
+        }
+    }
+}
+
+/// What kind of failure stopped a run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The arguments are invalid or contradict each other.
+    Usage,
+    /// An input or output couldn't be opened, read or written.
+    Io,
+    /// An input is truncated, or holds a document that isn't valid BSON or can't be converted.
+    Corrupt,
+    /// A document is over the size or nesting limits, or the documents to hold are over
+    /// --maxMemory.
+    Size,
+}
+
+impl ErrorKind {
+    /// The exit status, so that scripts can tell a bad invocation from a missing file or a corrupt
+    /// one.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 1,
+            ErrorKind::Io => 2,
+            ErrorKind::Corrupt => 3,
+            ErrorKind::Size => 4,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BsondumpError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// How many documents had been output when a document failed, if the run got that far.
+    pub found: Option<u32>,
+}
+
+impl BsondumpError {
+    fn new(kind: ErrorKind, message: String) -> Self {
+        BsondumpError { kind, message, found: None }
+    }
+
+    // A failure after `found` documents had been output.
+    fn document(found: u32, kind: ErrorKind, message: String) -> Self {
+        BsondumpError { kind, message, found: Some(found) }
+    }
+}
+
+impl std::fmt::Display for BsondumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for BsondumpError {}
+
+// A truncated or undecompressable input is corrupt rather than unreadable.
+fn io_kind(err: &std::io::Error) -> ErrorKind {
+    match err.kind() {
+        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::InvalidData => ErrorKind::Corrupt,
+        _ => ErrorKind::Io,
+    }
+}
+
+fn document_kind(err: &crate::docbytes::Error) -> ErrorKind {
+    match err {
+        crate::docbytes::Error::IOError(err) => io_kind(err),
+        crate::docbytes::Error::TruncatedError(..) | crate::docbytes::Error::UnterminatedError(_) => {
+            ErrorKind::Corrupt
+        }
+        _ => ErrorKind::Size,
+    }
+}
+
+fn archive_kind(err: &crate::archive::Error) -> ErrorKind {
+    match err {
+        crate::archive::Error::IOError(err) => io_kind(err),
+        _ => ErrorKind::Corrupt,
+    }
+}
+
+// Fail with an I/O error after `found` documents.
+fn io_error(found: u32) -> impl Fn(std::io::Error) -> BsondumpError {
+    move |err| BsondumpError::document(found, io_kind(&err), format!("{}", err))
+}
+
+// Where output is written: a file, stdout or an S3 object, which is only there once it's finished.
+trait OutputFile: Write + Send {
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+impl<W: Write + Send> OutputFile for BufWriter<W> {
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(feature = "remote")]
+impl OutputFile for crate::remote::S3Writer {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        crate::remote::S3Writer::finish(*self)
+    }
+}
+
+fn create_output(path: &Path, buffer_size: usize) -> std::io::Result<Box<dyn OutputFile>> {
+    let context = |err: std::io::Error, what: String| std::io::Error::new(err.kind(), format!("{}. {}", what, err));
+    #[cfg(feature = "remote")]
+    if let Some(url) = path.to_str().filter(|path| crate::remote::is_s3(path)) {
+        return match crate::remote::S3Writer::create(url) {
+            Err(err) => Err(context(err, format!("Failed to create {} for writing", url))),
+            Ok(writer) => Ok(Box::new(writer)),
+        };
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| context(err, format!("Failed to create directory {}", parent.display())))?;
+    }
+    match File::create(path) {
+        Err(err) => Err(context(err, format!("Failed to create {} for writing", path.display()))),
+        Ok(file) => Ok(Box::new(BufWriter::with_capacity(buffer_size, file))),
+    }
+}
+
+fn create_output_or_fail(path: &Path, buffer_size: usize) -> Result<Box<dyn OutputFile>, BsondumpError> {
+    create_output(path, buffer_size).map_err(|err| BsondumpError::new(ErrorKind::Io, format!("{}", err)))
+}
+
+#[derive(Copy, Clone)]
+struct JsonFormat<'a> {
+    pretty: bool,
+    color: bool,
+    indent: crate::Indent,
+    extract: Option<&'a str>,
+    keep_missing: bool,
+    oplog: bool,
+    ascii: bool,
+}
+
+// Convert a document to extended JSON. A document that doesn't convert is an error unless
+// --noobjcheck is given, and otherwise skipped.
+fn to_extjson_value(
+    raw_doc_buf: &bson::RawDocumentBuf,
+    options: &crate::ExtJsonOptions,
+    num_found: u32,
+    fail_on_error: bool,
+) -> Result<Option<serde_json::Value>, BsondumpError> {
+    match crate::to_extjson_value(raw_doc_buf, options) {
+        Err(err) if fail_on_error => Err(BsondumpError::document(
+            num_found,
+            ErrorKind::Corrupt,
+            format!("Failed to convert to extended json: {}", err),
+        )),
+        Err(_) => Ok(None),
+        Ok(value) => Ok(Some(value)),
+    }
+}
+
+// Returns false for a document without the --extract path, which isn't output.
+fn print_json<W: Write>(
+    writer: &mut W,
+    raw_doc_buf: &bson::RawDocumentBuf,
+    num_found: u32,
+    options: &crate::ExtJsonOptions,
+    format: &JsonFormat,
+    fail_on_error: bool,
+    // Written before and after the document.
+    (prefix, terminator): (&str, &str),
+) -> Result<bool, BsondumpError> {
+    // Plain JSON is written straight from the document's bytes, when they convert the same way.
+    let plain = options.uuid_format.is_none()
+        && options.date_format.is_none()
+        && options.decimal_format == crate::decimal::DecimalFormat::ExtJson
+        && options.binary_dir.is_none()
+        && !options.sort_keys
+        && format.extract.is_none()
+        && !(format.pretty || format.color || format.oplog);
+    let mut json = Vec::new();
+    if plain && crate::extjson::write_document(&mut json, raw_doc_buf, options.relaxed).unwrap_or(false) {
+        write_str(writer, num_found, prefix)?;
+        if format.ascii {
+            // The document's strings were checked to be UTF-8 as they were written.
+            let json = std::str::from_utf8(&json).expect("writing valid UTF-8");
+            write_str(writer, num_found, &crate::escape_non_ascii(json))?;
+        } else {
+            writer.write_all(&json).map_err(io_error(num_found))?;
+        }
+        return write_str(writer, num_found, terminator).map(|()| true);
+    }
+    let value = match to_extjson_value(raw_doc_buf, options, num_found, fail_on_error)? {
+        None => return Ok(true),
+        Some(value) => value,
+    };
+    let value = match format.extract {
+        None => value,
+        Some(path) => match crate::path::lookup(&value, path) {
+            Some(extracted) => extracted.clone(),
+            None if format.keep_missing => serde_json::Value::Null,
+            None => return Ok(false),
+        },
+    };
+    write_str(writer, num_found, prefix)?;
+
+    let values = if format.oplog { crate::oplog::expand(value) } else { vec![value] };
+    for value in values {
+        print_json_value(writer, &value, num_found, format, fail_on_error, terminator)?;
+    }
+    Ok(true)
+}
+
+fn print_json_value<W: Write>(
+    writer: &mut W,
+    value: &serde_json::Value,
+    num_found: u32,
+    format: &JsonFormat,
+    fail_on_error: bool,
+    terminator: &str,
+) -> Result<(), BsondumpError> {
+    if !format.pretty && !format.color {
+        if format.ascii {
+            let json = crate::escape_non_ascii(&value.to_string()).into_owned();
+            return write!(writer, "{}{}", json, terminator).map_err(io_error(num_found));
+        }
+        return write!(writer, "{}{}", value, terminator).map_err(io_error(num_found));
+    }
+
+    let indent = format.indent.to_bytes();
+    let result = match (format.pretty, format.color) {
+        (true, true) => crate::color::to_colored_string(value, PrettyFormatter::with_indent(&indent)),
+        (false, true) => crate::color::to_colored_string(value, CompactFormatter),
+        _ => crate::to_pretty_string(value, format.indent),
+    };
+    let value = match result {
+        Err(err) if fail_on_error => return Err(io_error(num_found)(err)),
+        Err(_) => return Ok(()),
+        Ok(value) if format.ascii => crate::escape_non_ascii(&value).into_owned(),
+        Ok(value) => value,
+    };
+
+    write!(writer, "{}{}", value, terminator).map_err(io_error(num_found))?;
+    writer.flush().map_err(io_error(num_found))
+}
+
+fn write_str<W: Write>(writer: &mut W, num_found: u32, s: &str) -> Result<(), BsondumpError> {
+    write!(writer, "{}", s).map_err(io_error(num_found))
+}
+
+fn print_csv_record<W: Write>(writer: &mut W, record: &[String], num_found: u32) -> Result<(), BsondumpError> {
+    let mut csv_writer = csv::Writer::from_writer(Vec::new());
+    if let Err(err) = csv_writer.write_record(record) {
+        return Err(BsondumpError::document(num_found, ErrorKind::Io, format!("{}", err)));
+    }
+    match csv_writer.into_inner() {
+        Err(err) => Err(BsondumpError::document(num_found, ErrorKind::Io, format!("{}", err))),
+        Ok(line) => writer.write_all(&line).map_err(io_error(num_found)),
+    }
+}
+
+fn print_field_stats<W: Write>(
+    writer: &mut W,
+    field_stats: &[serde_json::Value],
+    json_array: bool,
+    ascii: bool,
+) -> Result<(), std::io::Error> {
+    let escape = |json: String| if ascii { crate::escape_non_ascii(&json).into_owned() } else { json };
+    if json_array {
+        return writeln!(writer, "{}", escape(serde_json::Value::from(field_stats).to_string()));
+    }
+    for field_stat in field_stats {
+        writeln!(writer, "{}", escape(field_stat.to_string()))?;
+    }
+    Ok(())
+}
+
+// Rows per page of table output when it isn't written to a terminal.
+const DEFAULT_PAGE_SIZE: usize = 100;
+// Each page of table output has a header, a rule, and a blank line.
+const PAGE_OVERHEAD: usize = 3;
+
+fn print_table_page<W: Write>(
+    writer: &mut W,
+    fields: &[String],
+    page: &mut Vec<serde_json::Value>,
+    num_found: u32,
+) -> Result<(), BsondumpError> {
+    if page.is_empty() {
+        return Ok(());
+    }
+    let columns = if fields.is_empty() { crate::table::discover_columns(page) } else { fields.to_vec() };
+    crate::table::write_table(writer, &columns, page).map_err(io_error(num_found))?;
+    write_str(writer, num_found, "\n")?;
+    page.clear();
+    Ok(())
+}
+
+fn detect_compression<R: BufRead>(reader: &mut R, name: &str) -> Result<Option<Compression>, BsondumpError> {
+    crate::compression::detect(reader)
+        .map_err(|err| BsondumpError::new(ErrorKind::Io, format!("Failed to read {}. {}", name, err)))
+}
+
+fn decompress<'r, R: BufRead + 'r>(
+    reader: R,
+    compression: Compression,
+    name: &str,
+) -> Result<Box<dyn BufRead + 'r>, BsondumpError> {
+    crate::compression::decompress(reader, compression)
+        .map_err(|err| BsondumpError::new(io_kind(&err), format!("Failed to decompress {}. {}", name, err)))
+}
+
+// An opened input: its reader, how many bytes of it are left to skip once it's decompressed, and
+// its size if it's a file read uncompressed.
+type Opened = (Box<dyn BufRead>, u64, Option<u64>);
+
+// A file being read, or stdin or a caller's reader when path is None.
+struct Input<'r> {
+    path: Option<String>,
+    reader: Box<dyn BufRead + 'r>,
+    documents: u64,
+    // Where the next document starts.
+    position: crate::docbytes::Position,
+    // The size of a file that's read uncompressed.
+    size: Option<u64>,
+    // With --mmap, an uncompressed file's mapping, which its documents are read out of instead of
+    // reader.
+    mapping: Option<crate::mmap::Mapping>,
+}
+
+impl<'r> Input<'r> {
+    // Open a file, or stdin, decompressing it if it's compressed. --skipBytes counts decompressed
+    // bytes, so only uncompressed files can seek straight to the offset. A followed file waits for
+    // more to be appended at its end instead of ending.
+    fn open(
+        path: Option<&str>,
+        skip_bytes: u64,
+        compression: Option<Compression>,
+        follow: bool,
+        io_uring: bool,
+        mmap: bool,
+    ) -> Result<Input<'static>, BsondumpError> {
+        let name = path.unwrap_or("stdin");
+        let (mut reader, unskipped, size): Opened = match path {
+            None => (Input::open_stream(buffered(stdin()), name, compression)?, skip_bytes, None),
+            Some(url) if crate::http::is_url(url) => match crate::http::HttpReader::open(url) {
+                Err(err) => {
+                    let message = format!("Failed to open {url} for reading. {err}", url = url, err = err);
+                    return Err(BsondumpError::new(ErrorKind::Io, message));
+                }
+                Ok(body) => (Input::open_stream(buffered(body), name, compression)?, skip_bytes, None),
+            },
+            #[cfg(feature = "remote")]
+            Some(url) if crate::remote::is_s3(url) => match crate::remote::S3Reader::open(url) {
+                Err(err) => {
+                    let message = format!("Failed to open {url} for reading. {err}", url = url, err = err);
+                    return Err(BsondumpError::new(ErrorKind::Io, message));
+                }
+                Ok(object) => Input::open_file(buffered(object), url, skip_bytes, compression)?,
+            },
+            Some(path) => match File::open(path) {
+                Err(err) => {
+                    let message = format!("Failed to open {path} for reading. {err}", path = path, err = err);
+                    return Err(BsondumpError::new(ErrorKind::Io, message));
+                }
+                Ok(file) if follow => {
+                    let file = crate::follow::Follow::new(file, crate::follow::POLL_INTERVAL);
+                    let (reader, unskipped, _) = Input::open_file(buffered(file), path, skip_bytes, compression)?;
+                    (reader, unskipped, None)
+                }
+                Ok(file) if io_uring => Input::open_uring(file, path, skip_bytes, compression)?,
+                Ok(file) if mmap => match Input::map(&file, path, compression)? {
+                    None => Input::open_file(buffered(file), path, skip_bytes, compression)?,
+                    Some(mapping) => {
+                        let position = crate::docbytes::Position { index: 0, offset: skip_bytes };
+                        let size = Some(mapping.len() as u64);
+                        let reader = Box::new(std::io::empty());
+                        let path = Some(path.to_string());
+                        return Ok(Input { path, reader, documents: 0, position, size, mapping: Some(mapping) });
+                    }
+                },
+                Ok(file) => Input::open_file(buffered(file), path, skip_bytes, compression)?,
+            },
+        };
+        if unskipped > 0 {
+            if let Err(err) = std::io::copy(&mut (&mut reader).take(unskipped), &mut std::io::sink()) {
+                let message = format!("Failed to skip {} bytes of {}. {}", unskipped, name, err);
+                return Err(BsondumpError::new(io_kind(&err), message));
+            }
+        }
+        let position = crate::docbytes::Position { index: 0, offset: skip_bytes };
+        Ok(Input { path: path.map(String::from), reader, documents: 0, position, size, mapping: None })
+    }
+
+    // Map a regular file that isn't compressed. Anything else, or a file that can't be mapped, is
+    // read through a reader instead.
+    fn map(
+        file: &File,
+        path: &str,
+        compression: Option<Compression>,
+    ) -> Result<Option<crate::mmap::Mapping>, BsondumpError> {
+        let regular = file.metadata().is_ok_and(|metadata| metadata.is_file());
+        if !regular || compression.or_else(|| crate::compression::from_extension(Path::new(path))).is_some() {
+            return Ok(None);
+        }
+        // --mmap's help warns that the files mustn't change while they're read.
+        let mapping = match unsafe { crate::mmap::Mapping::open(file) } {
+            Err(err) => {
+                warn!("Failed to map {}, so reading it normally. {}", path, err);
+                return Ok(None);
+            }
+            Ok(mapping) => mapping,
+        };
+        match detect_compression(&mut &mapping[..], path)? {
+            None => Ok(Some(mapping)),
+            Some(_) => Ok(None),
+        }
+    }
+
+    // Stdin, a download or a caller's reader, which can't seek.
+    fn open_stream<R: Read + 'r>(
+        mut reader: BufReader<R>,
+        name: &str,
+        compression: Option<Compression>,
+    ) -> Result<Box<dyn BufRead + 'r>, BsondumpError> {
+        let compression = match compression {
+            None => detect_compression(&mut reader, name)?,
+            compression => compression,
+        };
+        match compression {
+            None => Ok(Box::new(reader)),
+            Some(compression) => decompress(reader, compression, name),
+        }
+    }
+
+    fn open_file<R: Read + Seek + 'static>(
+        mut reader: BufReader<R>,
+        path: &str,
+        skip_bytes: u64,
+        compression: Option<Compression>,
+    ) -> Result<Opened, BsondumpError> {
+        let compression = match compression.or_else(|| crate::compression::from_extension(Path::new(path))) {
+            None => detect_compression(&mut reader, path)?,
+            compression => compression,
+        };
+        match compression {
+            None => {
+                let size = reader.seek(SeekFrom::End(0)).ok();
+                if let Err(err) = reader.seek(SeekFrom::Start(skip_bytes)) {
+                    let message = format!("Failed to seek {} to byte {}. {}", path, skip_bytes, err);
+                    return Err(BsondumpError::new(ErrorKind::Io, message));
+                }
+                Ok((Box::new(reader), 0, size))
+            }
+            Some(compression) => Ok((decompress(reader, compression, path)?, skip_bytes, None)),
+        }
+    }
+
+    // Read a file through io_uring, or normally where it can't be set up.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn open_uring(
+        file: File,
+        path: &str,
+        skip_bytes: u64,
+        compression: Option<Compression>,
+    ) -> Result<Opened, BsondumpError> {
+        match file.try_clone().and_then(crate::uring::UringReader::new) {
+            Err(err) => {
+                warn!("Failed to set up io_uring for {}, so reading it normally. {}", path, err);
+                Input::open_file(buffered(file), path, skip_bytes, compression)
+            }
+            Ok(reader) => Input::open_file(buffered(reader), path, skip_bytes, compression),
+        }
+    }
+
+    // Where io_uring isn't built in, files are read normally; run refuses --ioUring before any
+    // is opened.
+    #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+    fn open_uring(
+        file: File,
+        path: &str,
+        skip_bytes: u64,
+        compression: Option<Compression>,
+    ) -> Result<Opened, BsondumpError> {
+        Input::open_file(buffered(file), path, skip_bytes, compression)
+    }
+
+    // Prefix an error with the file it came from.
+    fn context<E: std::fmt::Display>(&self, err: E) -> String {
+        match &self.path {
+            None => format!("{}", err),
+            Some(path) => format!("{}: {}", path, err),
+        }
+    }
+
+    // Prefix an error with the file it came from and which document in it caused it.
+    fn document_context<E: std::fmt::Display>(&self, position: crate::docbytes::Position, err: E) -> String {
+        self.context(format!("{}: {}", position, err))
+    }
+}
+
+// The input files, opened one at a time and read in order as one stream of documents.
+struct Inputs<'r> {
+    pending: VecDeque<String>,
+    current: Option<Input<'r>>,
+    compression: Option<Compression>,
+    // Log how many documents each file held when there's more than one.
+    summarize: bool,
+    // Follow the last file once it's reached.
+    follow: bool,
+    io_uring: bool,
+    mmap: bool,
+    max_bson_size: u32,
+    // The inputs opened so far, in order.
+    names: Vec<String>,
+    progress: Option<crate::progress::Progress>,
+    // The bytes and documents of the inputs read before the current one.
+    read: u64,
+    read_documents: u64,
+}
+
+impl<'r> Inputs<'r> {
+    // --skipBytes applies to the first file.
+    fn new(
+        paths: &[String],
+        skip_bytes: u64,
+        compression: Option<Compression>,
+        follow: bool,
+        io_uring: bool,
+        mmap: bool,
+        max_bson_size: u32,
+    ) -> Result<Inputs<'static>, BsondumpError> {
+        let mut pending: VecDeque<String> = paths.iter().cloned().collect();
+        let summarize = pending.len() > 1;
+        let path = pending.pop_front();
+        let follow_first = follow && pending.is_empty();
+        let current = Input::open(path.as_deref(), skip_bytes, compression, follow_first, io_uring, mmap)?;
+        let names = vec![path.unwrap_or_else(|| "stdin".to_string())];
+        Ok(Inputs {
+            pending,
+            current: Some(current),
+            compression,
+            summarize,
+            follow,
+            io_uring,
+            mmap,
+            max_bson_size,
+            names,
+            progress: None,
+            read: 0,
+            read_documents: 0,
+        })
+    }
+
+    // Read the documents of the namespaces in `include` out of a mongodump archive.
+    fn archive(
+        path: &str,
+        include: &[glob::Pattern],
+        compression: Option<Compression>,
+        max_bson_size: u32,
+    ) -> Result<Inputs<'static>, BsondumpError> {
+        let mut input = Input::open(Some(path), 0, compression, false, false, false)?;
+        match crate::archive::ArchiveReader::new(input.reader, include.to_vec()) {
+            Err(err) => {
+                let message = format!("Failed to read archive {}. {}", path, err);
+                return Err(BsondumpError::new(archive_kind(&err), message));
+            }
+            Ok(archive) => {
+                if !archive.namespaces().iter().any(|namespace| archive.is_selected(namespace)) {
+                    warn!("No namespaces in {} match --nsInclude", path);
+                }
+                // Offsets in errors are then into the selected documents rather than the archive.
+                input.reader = Box::new(buffered(archive));
+            }
+        }
+        Ok(Inputs {
+            pending: VecDeque::new(),
+            current: Some(input),
+            compression,
+            summarize: false,
+            follow: false,
+            io_uring: false,
+            mmap: false,
+            max_bson_size,
+            names: vec![path.to_string()],
+            progress: None,
+            read: 0,
+            read_documents: 0,
+        })
+    }
+
+    // Read the documents of a caller's reader, decompressing it if it's compressed.
+    fn reader<R: Read + 'r>(reader: R, max_bson_size: u32) -> Result<Inputs<'r>, BsondumpError> {
+        let reader = Input::open_stream(buffered(reader), "the input", None)?;
+        let position = crate::docbytes::Position { index: 0, offset: 0 };
+        Ok(Inputs {
+            pending: VecDeque::new(),
+            current: Some(Input { path: None, reader, documents: 0, position, size: None, mapping: None }),
+            compression: None,
+            summarize: false,
+            follow: false,
+            io_uring: false,
+            mmap: false,
+            max_bson_size,
+            names: vec!["the input".to_string()],
+            progress: None,
+            read: 0,
+            read_documents: 0,
+        })
+    }
+
+    // Show how far through `paths` reading is on stderr, if they're files that are read
+    // uncompressed, so that how many bytes are left is known.
+    fn show_progress(&mut self, paths: &[String]) {
+        let mut total = 0;
+        for path in paths {
+            match std::fs::metadata(path) {
+                Ok(metadata) if metadata.is_file() => total += metadata.len(),
+                _ => return,
+            }
+        }
+        if self.current.as_ref().is_some_and(|input| input.size.is_some()) {
+            self.progress = Some(crate::progress::Progress::new(total));
+        }
+    }
+
+    // How many bytes of documents have been read.
+    fn bytes_read(&self) -> u64 {
+        self.read + self.current.as_ref().map_or(0, |input| input.position.offset)
+    }
+
+    fn update_progress(&mut self) {
+        let read = self.bytes_read();
+        if let (Some(progress), Some(input)) = (self.progress.as_mut(), self.current.as_ref()) {
+            progress.update(read, self.read_documents + input.position.index);
+        }
+    }
+
+    fn advance(&mut self) -> Result<(), BsondumpError> {
+        if let Some(Input { path: Some(path), documents, .. }) = &self.current {
+            if self.summarize {
+                info!("{}: {} documents", path, documents);
+            }
+        }
+        if let Some(input) = self.current.take() {
+            self.read += input.size.unwrap_or(input.position.offset);
+            self.read_documents += input.position.index;
+        }
+        if let Some(path) = self.pending.pop_front() {
+            let follow = self.follow && self.pending.is_empty();
+            let input = Input::open(Some(&path), 0, self.compression, follow, self.io_uring, self.mmap)?;
+            if input.size.is_none() {
+                self.progress = None;
+            }
+            self.current = Some(input);
+            self.names.push(path);
+        }
+        Ok(())
+    }
+
+    fn skip_documents(&mut self, count: u64) -> Result<(), BsondumpError> {
+        let mut remaining = count;
+        let max_size = self.max_bson_size;
+        while let Some(input) = self.current.as_mut() {
+            let result = match &input.mapping {
+                Some(mapping) => {
+                    let mut documents = crate::docbytes::slice_source_at(mapping, input.position).max_size(max_size);
+                    let mut skipping = documents.by_ref().take(remaining as usize);
+                    let result = skipping.try_fold(0, |skipped, document| document.map(|_| skipped + 1));
+                    input.position = documents.position();
+                    result
+                }
+                None => {
+                    let mut source = crate::docbytes::source_at(&mut input.reader, input.position).max_size(max_size);
+                    let result = source.skip_documents(remaining);
+                    input.position = source.position();
+                    result
+                }
+            };
+            match result {
+                Err(err) => {
+                    let message = input.document_context(input.position, &err);
+                    return Err(BsondumpError::document(0, document_kind(&err), message));
+                }
+                Ok(skipped) => remaining -= skipped,
+            }
+            if remaining == 0 {
+                return Ok(());
+            }
+            self.advance()?;
+        }
+        Ok(())
+    }
+}
+
+type ValidationReport = crate::validate::Report<Box<dyn OutputFile>>;
+
+// Which documents, and which parts of them, are passed on for output.
+struct Selection<'a> {
+    grep: Option<crate::grep::Grep>,
+    oplog_ns: Option<crate::oplog::NamespaceFilter>,
+    oplog_range: Option<crate::oplog::TimeRange>,
+    objcheck: bool,
+    // What --objcheck makes of string values that aren't valid UTF-8.
+    invalid_utf8: crate::utf8::InvalidUtf8,
+    max_depth: usize,
+    report: Option<&'a mut ValidationReport>,
+    filter: Option<&'a crate::filter::Filter>,
+    transform: &'a crate::transform::Transform,
+    projection: &'a crate::projection::Projection,
+    redactor: Option<crate::redact::Redactor>,
+    sample: Option<f64>,
+    rng: StdRng,
+    continue_on_error: bool,
+    skipped: u32,
+    allow_partial: bool,
+    ids: Option<crate::duplicates::IdSet>,
+    // Counts of deprecated elements, when looking for them.
+    deprecated: Option<crate::deprecated::DeprecatedCounts>,
+    fail_deprecated: bool,
+    #[cfg(feature = "client")]
+    verifier: Option<crate::client::Verifier>,
+    // How many documents --verifyAgainst found missing from the collection, or different there.
+    missing: u32,
+    different: u32,
+    timing: Option<&'a crate::timing::Timing>,
+}
+
+impl Selection<'_> {
+    // With --continueOnError, log and count a document that can't be output instead of failing.
+    fn skip(&mut self, what: &str, err: BsondumpError) -> Result<(), BsondumpError> {
+        skip(self.continue_on_error, what, err)?;
+        self.skipped += 1;
+        Ok(())
+    }
+}
+
+fn skip(continue_on_error: bool, what: &str, err: BsondumpError) -> Result<(), BsondumpError> {
+    if !continue_on_error || err.kind == ErrorKind::Io {
+        return Err(err);
+    }
+    warn!("Skipping {}. {}", what, err);
+    Ok(())
+}
+
+// Read documents until one passes --grep, the --oplog* filters, the filter, and the --sample coin
+// flip, and project it.
+fn next_selected(
+    inputs: &mut Inputs<'_>,
+    selection: &mut Selection,
+    num_found: u32,
+) -> Result<Option<bson::RawDocumentBuf>, BsondumpError> {
+    let timing = selection.timing;
+    crate::timing::time(timing, crate::timing::Stage::Reading, || select_next(inputs, selection, num_found))
+}
+
+fn select_next(
+    inputs: &mut Inputs<'_>,
+    selection: &mut Selection,
+    num_found: u32,
+) -> Result<Option<bson::RawDocumentBuf>, BsondumpError> {
+    loop {
+        inputs.update_progress();
+        let input = match inputs.current.as_mut() {
+            None => return Ok(None),
+            Some(input) => input,
+        };
+        let position = input.position;
+        // A mapped input's documents are borrowed from its mapping rather than read into a buffer.
+        let next = match &input.mapping {
+            Some(mapping) => {
+                let mut documents = crate::docbytes::slice_source_at(mapping, position).max_size(inputs.max_bson_size);
+                let next = documents.next().map(|result| result.map(|document| Cow::Borrowed(document.as_bytes())));
+                input.position = documents.position();
+                if let Some(Err(_)) = next {
+                    // There's no telling where the next document starts, so the mapping ends here
+                    // as a stream would.
+                    input.position.offset = mapping.len() as u64;
+                }
+                next
+            }
+            None => {
+                let mut source = crate::docbytes::source_at(&mut input.reader, position).max_size(inputs.max_bson_size);
+                let next = source.next_buffered().map(|result| result.map(|read| Cow::Owned(read.bytes)));
+                input.position = source.position();
+                next
+            }
+        };
+        let result = match next {
+            None => {
+                inputs.advance()?;
+                continue;
+            }
+            Some(result) => result,
+        };
+        let bytes: Cow<[u8]> = match result {
+            Err(crate::docbytes::Error::TruncatedError(bytes, size)) if selection.allow_partial => {
+                let recovered = crate::docbytes::recover_elements(&bytes);
+                let count = recovered.iter().count();
+                let err = crate::docbytes::Error::TruncatedError(bytes, size);
+                warn!("{}; outputting the {} elements read in full", input.document_context(position, &err), count);
+                Cow::Owned(recovered.into_bytes())
+            }
+            Err(err) => {
+                let report = match selection.report.as_mut() {
+                    None => {
+                        // There's no telling where the next document starts.
+                        let message = input.document_context(position, &err);
+                        let err = BsondumpError::document(num_found, document_kind(&err), message);
+                        selection.skip("the rest of the input", err)?;
+                        inputs.advance()?;
+                        continue;
+                    }
+                    Some(report) => report,
+                };
+                let kind = match err {
+                    crate::docbytes::Error::IOError(_) | crate::docbytes::Error::TruncatedError(..) => {
+                        crate::validate::ErrorKind::Read
+                    }
+                    _ => crate::validate::ErrorKind::Size,
+                };
+                // There's no telling where the next document starts.
+                let result = report.invalid(input.path.as_deref(), position, kind, "", &err.to_string());
+                report_result(result, num_found)?;
+                inputs.advance()?;
+                continue;
+            }
+            Ok(bytes) => bytes,
+        };
+        input.documents += 1;
+        let file = input.path.as_deref();
+        // Fail on a bad document, unless it's being reported with --validateReport.
+        let fail = |kind: ErrorKind, err: &dyn std::fmt::Display| {
+            BsondumpError::document(num_found, kind, input.document_context(position, err))
+        };
+
+        if selection.objcheck {
+            if let Err(err) = crate::validate::validate(&bytes, selection.invalid_utf8) {
+                let report = match selection.report.as_mut() {
+                    None => {
+                        selection.skip("the document", fail(ErrorKind::Corrupt, &err))?;
+                        continue;
+                    }
+                    Some(report) => report,
+                };
+                let kind = crate::validate::ErrorKind::Structure;
+                report_result(report.invalid(file, position, kind, &err.path, &err.reason), num_found)?;
+                continue;
+            }
+        }
+
+        let raw_doc_buf = match bson::RawDocumentBuf::from_bytes(bytes.into_owned()) {
+            Err(err) => {
+                let report = match selection.report.as_mut() {
+                    None => {
+                        selection.skip("the document", fail(ErrorKind::Corrupt, &err))?;
+                        continue;
+                    }
+                    Some(report) => report,
+                };
+                let kind = crate::validate::ErrorKind::Conversion;
+                report_result(report.invalid(file, position, kind, "", &err.to_string()), num_found)?;
+                continue;
+            }
+            Ok(raw_doc_buf) => raw_doc_buf,
+        };
+        if let Err(err) = crate::validate::check_depth(&raw_doc_buf, selection.max_depth) {
+            let report = match selection.report.as_mut() {
+                None => {
+                    selection.skip("the document", fail(ErrorKind::Size, &err))?;
+                    continue;
+                }
+                Some(report) => report,
+            };
+            let kind = crate::validate::ErrorKind::Depth;
+            report_result(report.invalid(file, position, kind, &err.path, &err.reason), num_found)?;
+            continue;
+        }
+        if selection.deprecated.is_some() {
+            let found = match crate::deprecated::find(&raw_doc_buf) {
+                Err(err) => {
+                    selection.skip("the document", fail(ErrorKind::Corrupt, &err))?;
+                    continue;
+                }
+                Ok(found) => found,
+            };
+            if let Some(element) = found.first().filter(|_| selection.fail_deprecated) {
+                selection.skip("the document", fail(ErrorKind::Corrupt, element))?;
+                continue;
+            }
+            if let Some(counts) = selection.deprecated.as_mut() {
+                for element in &found {
+                    warn!("{}", input.document_context(position, element));
+                    counts.add(element);
+                }
+            }
+        }
+        if let Some(report) = selection.report.as_mut() {
+            report_result(report.valid(file, position), num_found)?;
+        }
+        if let Some(ids) = selection.ids.as_mut() {
+            let input = inputs.names.len() as u32 - 1;
+            let location = crate::duplicates::Location { input, index: position.index };
+            ids.add(&raw_doc_buf, location).map_err(|err| {
+                let message = format!("Failed to spill _ids to a temporary file. {}", err);
+                BsondumpError::document(num_found, ErrorKind::Io, message)
+            })?;
+        }
+        #[cfg(feature = "client")]
+        if let Some(verifier) = selection.verifier.as_mut() {
+            let input = inputs.names.len() as u32 - 1;
+            let location = crate::duplicates::Location { input, index: position.index };
+            let mismatches = verifier.add(&raw_doc_buf, location).map_err(verify_error(num_found))?;
+            report_mismatches(selection, mismatches, &inputs.names);
+        }
+
+        if let Some(grep) = &selection.grep {
+            match grep.matches(&raw_doc_buf) {
+                Err(err) => {
+                    selection.skip("the document", fail(ErrorKind::Corrupt, &err))?;
+                    continue;
+                }
+                Ok(false) => continue,
+                Ok(true) => (),
+            }
+        }
+
+        if let Some(oplog_range) = &selection.oplog_range {
+            if !oplog_range.matches(&raw_doc_buf) {
+                continue;
+            }
+        }
+
+        if let Some(oplog_ns) = &selection.oplog_ns {
+            match oplog_ns.matches(&raw_doc_buf) {
+                Err(err) => {
+                    selection.skip("the document", fail(ErrorKind::Corrupt, &err))?;
+                    continue;
+                }
+                Ok(false) => continue,
+                Ok(true) => (),
+            }
+        }
+
+        if let Some(filter) = selection.filter {
+            match raw_doc_buf.to_document() {
+                Err(err) => {
+                    selection.skip("the document", fail(ErrorKind::Corrupt, &err))?;
+                    continue;
+                }
+                Ok(document) if !filter.matches(&document) => continue,
+                Ok(_) => (),
+            }
+        }
+
+        if let Some(fraction) = selection.sample {
+            if !selection.rng.gen_bool(fraction) {
+                continue;
+            }
+        }
+
+        let transformed = match selection.transform.is_empty() {
+            true => Ok(raw_doc_buf),
+            false => selection.transform.apply(&raw_doc_buf),
+        };
+        let projected = match selection.projection.is_empty() {
+            true => transformed,
+            false => transformed.and_then(|transformed| selection.projection.apply(&transformed)),
+        };
+        let redacted = match selection.redactor.as_mut() {
+            None => projected,
+            Some(redactor) => projected.and_then(|projected| redactor.apply(&projected)),
+        };
+        match redacted {
+            Err(err) => selection.skip("the document", fail(ErrorKind::Corrupt, &err))?,
+            Ok(redacted) => return Ok(Some(redacted)),
+        }
+    }
+}
+
+fn report_result(result: std::io::Result<()>, num_found: u32) -> Result<(), BsondumpError> {
+    result.map_err(|err| {
+        let message = format!("Failed to write the validation report. {}", err);
+        BsondumpError::document(num_found, ErrorKind::Io, message)
+    })
+}
+
+// An output that's told where the header and each document end, so that it can start a new file
+// between documents.
+trait DocumentWrite: Write + Send {
+    fn end_header(&mut self) {}
+
+    fn end_document(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DocumentWrite for std::io::Sink {}
+
+impl<W: Write + Send> DocumentWrite for BufWriter<W> {}
+
+// An output whose writes are timed and counted for --timing.
+struct Timed<'a> {
+    writer: &'a mut dyn DocumentWrite,
+    timing: &'a crate::timing::Timing,
+}
+
+impl Write for Timed<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let count = crate::timing::time(Some(self.timing), crate::timing::Stage::Writing, || self.writer.write(buf))?;
+        self.timing.add_written(count as u64);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        crate::timing::time(Some(self.timing), crate::timing::Stage::Writing, || self.writer.flush())
+    }
+}
+
+impl DocumentWrite for Timed<'_> {
+    fn end_header(&mut self) {
+        self.writer.end_header()
+    }
+
+    fn end_document(&mut self) -> std::io::Result<()> {
+        self.writer.end_document()
+    }
+}
+
+// One output file or stdout, compressed if asked to.
+enum Sink {
+    Plain(Box<dyn OutputFile>),
+    Compressed(crate::compression::Encoder<Box<dyn OutputFile>>),
+}
+
+impl Sink {
+    fn new(writer: Box<dyn OutputFile>, compression: Option<Compression>) -> std::io::Result<Sink> {
+        match compression {
+            None => Ok(Sink::Plain(writer)),
+            Some(compression) => match crate::compression::Encoder::new(writer, compression) {
+                Err(err) => {
+                    let message = format!("Failed to start compressing output. {}", err);
+                    Err(std::io::Error::new(err.kind(), message))
+                }
+                Ok(encoder) => Ok(Sink::Compressed(encoder)),
+            },
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Sink::Plain(writer) => writer,
+            Sink::Compressed(encoder) => encoder,
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.finish(),
+            Sink::Compressed(encoder) => encoder.finish()?.finish(),
+        }
+    }
+}
+
+// Where and when to start the next file of --splitEvery or --splitSize output.
+struct Split {
+    path: PathBuf,
+    every: Option<u64>,
+    size: Option<u64>,
+    index: u32,
+    documents: u64,
+    bytes: u64,
+    // Everything written before the first document, e.g. the csv header, is repeated in each file.
+    header: Vec<u8>,
+    in_header: bool,
+    due: bool,
+    buffer_size: usize,
+}
+
+type Checksums = crate::checksum::Checksums<Box<dyn OutputFile>>;
+
+struct Output {
+    sink: Option<Sink>,
+    compression: Option<Compression>,
+    split: Option<Split>,
+    checksums: Option<Checksums>,
+}
+
+impl Output {
+    fn new(
+        writer: Box<dyn OutputFile>,
+        compression: Option<Compression>,
+        split: Option<Split>,
+        checksums: Option<Checksums>,
+    ) -> std::io::Result<Output> {
+        Ok(Output { sink: Some(Sink::new(writer, compression)?), compression, split, checksums })
+    }
+
+    fn sink(&mut self) -> &mut Sink {
+        self.sink.as_mut().unwrap() // Only taken while starting the next file
+    }
+
+    // Start the next file once the current one is full and there's more to write.
+    fn start_next_file(&mut self) -> std::io::Result<()> {
+        let split = match self.split.as_mut() {
+            Some(split) if split.due => split,
+            _ => return Ok(()),
+        };
+        split.index += 1;
+        split.documents = 0;
+        split.bytes = split.header.len() as u64;
+        split.due = false;
+        let path = crate::split::split_path(&split.path, split.index);
+        let header = split.header.clone();
+        self.sink.take().unwrap().finish()?;
+        self.sink = Some(Sink::new(create_output(&path, split.buffer_size)?, self.compression)?);
+        self.sink().writer().write_all(&header)
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        if let Some(checksums) = self.checksums.take() {
+            checksums.finish()?.finish()?;
+        }
+        self.sink.take().unwrap().finish()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.start_next_file()?;
+        let count = self.sink().writer().write(buf)?;
+        if let Some(split) = self.split.as_mut() {
+            split.bytes += count as u64;
+            if split.in_header {
+                split.header.extend_from_slice(&buf[..count]);
+            }
+        }
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.update(&buf[..count]);
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.sink().writer().flush()
+    }
+}
+
+impl DocumentWrite for Output {
+    fn end_header(&mut self) {
+        if let Some(split) = self.split.as_mut() {
+            split.in_header = false;
+        }
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.end_header();
+        }
+    }
+
+    fn end_document(&mut self) -> std::io::Result<()> {
+        if let Some(split) = self.split.as_mut() {
+            split.documents += 1;
+            split.due = split.every.is_some_and(|every| split.documents >= every)
+                || split.size.is_some_and(|size| split.bytes >= size);
+        }
+        match self.checksums.as_mut() {
+            None => Ok(()),
+            Some(checksums) => checksums
+                .end_document()
+                .map_err(|err| std::io::Error::new(err.kind(), format!("Failed to write checksums. {}", err))),
+        }
+    }
+}
+
+// Write every selected document from `inputs` to the file at `path`, or stdout, compressed and
+// split across files if asked to, returning how many were output and skipped.
+fn dump(
+    cli: &Cli,
+    inputs: Inputs<'_>,
+    path: Option<&Path>,
+    parquet_schema: Option<arrow_schema::SchemaRef>,
+    to_terminal: bool,
+    metadata: Option<serde_json::Value>,
+    report: Option<&mut ValidationReport>,
+) -> Result<Summary, BsondumpError> {
+    let splitting = cli.split_every.is_some() || cli.split_size.is_some();
+    // Rust's stdout writes bytes as they are on every platform, without the newline translation of
+    // a C runtime's text mode, so binary output needs no special handling.
+    let buffer_size = cli.output_buffer_size();
+    let writer = match path {
+        None => Box::new(BufWriter::with_capacity(buffer_size, stdout())),
+        Some(path) if splitting => create_output_or_fail(&crate::split::split_path(path, 1), buffer_size)?,
+        Some(path) => create_output_or_fail(path, buffer_size)?,
+    };
+    let split = match path {
+        Some(path) if splitting => Some(Split {
+            path: path.to_path_buf(),
+            every: cli.split_every,
+            size: cli.split_size.map(|size| size.0),
+            index: 1,
+            documents: 0,
+            bytes: 0,
+            header: Vec::new(),
+            in_header: true,
+            due: false,
+            buffer_size,
+        }),
+        _ => None,
+    };
+    let checksums = match cli.checksum.zip(path) {
+        None => None,
+        Some((algorithm, path)) => {
+            let algorithm = crate::checksum::Algorithm::from(algorithm);
+            let mut sidecar = path.as_os_str().to_owned();
+            sidecar.push(format!(".{}", algorithm.name()));
+            Some(crate::checksum::Checksums::new(algorithm, create_output_or_fail(Path::new(&sidecar), buffer_size)?))
+        }
+    };
+    let mut output = Output::new(writer, cli.output_compression(), split, checksums).map_err(io_error(0))?;
+    let summary = dump_documents(cli, inputs, &mut output, parquet_schema, to_terminal, metadata, report)?;
+    output.finish().map_err(io_error(summary.found))?;
+    Ok(summary)
+}
+
+// How each document is written for the output types that write them one at a time, so that it
+// can be done on any thread.
+struct Render<'a> {
+    output_type: OutputType,
+    canonical: &'a crate::ExtJsonOptions,
+    relaxed: &'a crate::ExtJsonOptions,
+    json_format: JsonFormat<'a>,
+    debug_options: crate::DebugOptions,
+    fields: &'a [String],
+    terminator: &'a str,
+    fail_on_error: bool,
+}
+
+impl Render<'_> {
+    // The other output types add documents to a page, a report or a Parquet file.
+    fn is_per_document(&self) -> bool {
+        matches!(
+            self.output_type,
+            OutputType::Json
+                | OutputType::PrettyJson
+                | OutputType::RelaxedJson
+                | OutputType::Csv
+                | OutputType::Yaml
+                | OutputType::Bson
+                | OutputType::Hex
+                | OutputType::Debug
+        )
+    }
+
+    // Returns false for a document --extract skips, which isn't output or counted.
+    fn document<W: Write>(
+        &self,
+        writer: &mut W,
+        raw_doc_buf: &bson::RawDocumentBuf,
+        num_found: u32,
+        prefix: &str,
+    ) -> Result<bool, BsondumpError> {
+        let corrupt =
+            |err: &dyn std::fmt::Display| BsondumpError::document(num_found, ErrorKind::Corrupt, err.to_string());
+        let fail_on_error = self.fail_on_error;
+        match self.output_type {
+            OutputType::Json | OutputType::PrettyJson | OutputType::RelaxedJson => {
+                let options = if self.output_type == OutputType::RelaxedJson { self.relaxed } else { self.canonical };
+                let format = JsonFormat { pretty: self.output_type == OutputType::PrettyJson, ..self.json_format };
+                let ending = (prefix, self.terminator);
+                return print_json(writer, raw_doc_buf, num_found, options, &format, fail_on_error, ending);
+            }
+            OutputType::Csv => {
+                if let Some(value) = to_extjson_value(raw_doc_buf, self.relaxed, num_found, fail_on_error)? {
+                    print_csv_record(writer, &crate::to_csv_record(&value, self.fields), num_found)?;
+                }
+            }
+            OutputType::Yaml => {
+                if let Some(value) = to_extjson_value(raw_doc_buf, self.relaxed, num_found, fail_on_error)? {
+                    match crate::to_yaml_string(&value) {
+                        Err(err) if fail_on_error => return Err(corrupt(&err)),
+                        Err(_) => (),
+                        Ok(yaml) => write_str(writer, num_found, &format!("---\n{}", yaml))?,
+                    }
+                }
+            }
+            OutputType::Bson => writer.write_all(raw_doc_buf.as_bytes()).map_err(io_error(num_found))?,
+            OutputType::Hex => {
+                let value = crate::hexdump::hex_dump(raw_doc_buf).map_err(|err| corrupt(&err))?;
+                writeln!(writer, "{}", value).map_err(io_error(num_found))?;
+                writer.flush().map_err(io_error(num_found))?;
+            }
+            OutputType::Debug => {
+                crate::debug_to_writer_with_options(writer, raw_doc_buf, &self.debug_options)
+                    .map_err(|err| corrupt(&err))?;
+                writeln!(writer).map_err(io_error(num_found))?;
+                writer.flush().map_err(io_error(num_found))?;
+            }
+            _ => unreachable!("Only per-document output types are rendered"),
+        }
+        Ok(true)
+    }
+}
+
+// Reserve a document that --tail or --sampleCount holds until the input ends.
+fn hold(
+    budget: Option<&crate::memory::Budget>,
+    document: &bson::RawDocumentBuf,
+    flag: &str,
+) -> Result<(), BsondumpError> {
+    if crate::memory::try_reserve(budget, document.as_bytes().len() as u64) {
+        return Ok(());
+    }
+    let limit = crate::progress::format_size(budget.map_or(0, crate::memory::Budget::limit));
+    let message = format!("The documents {} holds are over --maxMemory {}", flag, limit);
+    Err(BsondumpError::new(ErrorKind::Size, message))
+}
+
+// Release a document that was held, as it's passed on.
+fn unhold(
+    budget: Option<&crate::memory::Budget>,
+    document: Option<bson::RawDocumentBuf>,
+) -> Option<bson::RawDocumentBuf> {
+    if let Some(document) = &document {
+        crate::memory::release(budget, document.as_bytes().len() as u64);
+    }
+    document
+}
+
+// How many documents are handed between the stages of the pipeline at a time, so that passing them
+// costs little next to converting them.
+const PIPELINE_BATCH: usize = 256;
+
+// Write documents in a pipeline, so that reading, converting and writing them overlap: this
+// thread reads and selects them, which has to be done in order, a pool of threads renders them,
+// and another thread writes them in order. Every document but the first is rendered with `prefix`,
+// the separator --jsonArray puts before it. Returns how many documents were output.
+fn pipeline(
+    cli: &Cli,
+    render: &Render,
+    prefix: &str,
+    writer: &mut dyn DocumentWrite,
+    selection: &mut Selection,
+    budget: Option<&crate::memory::Budget>,
+    mut next_document: impl FnMut(&mut Selection, u32) -> Result<Option<bson::RawDocumentBuf>, BsondumpError>,
+) -> Result<u32, BsondumpError> {
+    let timing = selection.timing;
+    std::thread::scope(|scope| {
+        let (mut jobs, results) = crate::parallel::pool(scope, cli.threads, move |batch: Vec<bson::RawDocumentBuf>| {
+            let render = |raw_doc_buf: bson::RawDocumentBuf| {
+                let mut rendered = Vec::new();
+                render.document(&mut rendered, &raw_doc_buf, 0, prefix).map(|output| output.then_some(rendered))
+            };
+            crate::timing::time(timing, crate::timing::Stage::Converting, || {
+                batch.into_iter().map(render).collect::<Vec<_>>()
+            })
+        });
+        // How many documents of each batch were written, and how many weren't.
+        let (written, acknowledgements) = std::sync::mpsc::channel();
+        let writing = scope.spawn(move || -> Result<(u32, u32), BsondumpError> {
+            let (mut num_found, mut skipped) = (0, 0);
+            for batch in results {
+                let (found_before, mut unwritten) = (num_found, 0);
+                for rendered in batch {
+                    let rendered = match rendered {
+                        Err(err) => {
+                            let err = BsondumpError { found: Some(num_found), ..err };
+                            skip(cli.continue_on_error, "the document", err)?;
+                            skipped += 1;
+                            unwritten += 1;
+                            continue;
+                        }
+                        Ok(None) => {
+                            unwritten += 1;
+                            continue;
+                        }
+                        Ok(Some(rendered)) => rendered,
+                    };
+                    let first = num_found == 0 && rendered.starts_with(prefix.as_bytes());
+                    let start = if first { prefix.len() } else { 0 };
+                    writer.write_all(&rendered[start..]).map_err(io_error(num_found))?;
+                    // Show each document as soon as it's appended rather than when the output buffer fills.
+                    if cli.follow {
+                        writer.flush().map_err(io_error(num_found))?;
+                    }
+                    writer.end_document().map_err(io_error(num_found))?;
+                    num_found += 1;
+                }
+                let _ = written.send((u64::from(num_found - found_before), unwritten));
+            }
+            Ok((num_found, skipped))
+        });
+
+        // A followed file's documents are passed on one at a time, so that each one is output as
+        // soon as it's appended.
+        let batch_size = if cli.follow { 1 } else { PIPELINE_BATCH };
+        // With --maxMemory, a batch is also sent once it holds its share, so that a few can be
+        // converted at once.
+        let batch_bytes = budget.map_or(u64::MAX, |budget| budget.limit() / (2 * (cli.threads as u64 + 1)));
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut batch_held = 0;
+        let mut sent = Sent { budget, ..Sent::default() };
+        let read = loop {
+            while let Ok(acknowledgement) = acknowledgements.try_recv() {
+                sent.acknowledge(acknowledgement);
+            }
+            let queued = sent.documents + batch.len() as u64;
+            if cli.limit.is_some_and(|limit| queued - sent.failed >= u64::from(limit)) {
+                if !batch.is_empty() {
+                    sent.reserve(&batch, &acknowledgements);
+                    batch_held = 0;
+                    if !jobs.send(std::mem::take(&mut batch)) {
+                        break Ok(());
+                    }
+                    continue;
+                }
+                if sent.acknowledged == sent.documents {
+                    break Ok(());
+                }
+                match acknowledgements.recv() {
+                    // The writer stopped.
+                    Err(_) => break Ok(()),
+                    Ok(acknowledgement) => sent.acknowledge(acknowledgement),
+                }
+                continue;
+            }
+            match next_document(selection, (sent.acknowledged - sent.failed) as u32) {
+                Err(err) => break Err(err),
+                Ok(None) => break Ok(()),
+                Ok(Some(raw_doc_buf)) => {
+                    batch_held += raw_doc_buf.as_bytes().len() as u64;
+                    batch.push(raw_doc_buf);
+                }
+            }
+            if batch.len() >= batch_size || batch_held >= batch_bytes {
+                sent.reserve(&batch, &acknowledgements);
+                batch_held = 0;
+                if !jobs.send(std::mem::replace(&mut batch, Vec::with_capacity(batch_size))) {
+                    // The writer stopped.
+                    break Ok(());
+                }
+            }
+        };
+        // The documents read before the input ended or failed are still output.
+        if !batch.is_empty() {
+            jobs.send(batch);
+        }
+        drop(jobs);
+        let (num_found, skipped) = writing.join().unwrap()?;
+        selection.skipped += skipped;
+        read.map_err(|err| BsondumpError { found: Some(num_found), ..err })?;
+        Ok(num_found)
+    })
+}
+
+// The batches of documents the pipeline has sent to be converted and written, counted as the
+// writer acknowledges them. Documents that are skipped, or that --extract outputs nothing for,
+// don't count toward --limit, so more are read in their place.
+#[derive(Default)]
+struct Sent<'b> {
+    documents: u64,
+    acknowledged: u64,
+    failed: u64,
+    budget: Option<&'b crate::memory::Budget>,
+    // What each batch not yet acknowledged holds of the budget, in order.
+    reserved: VecDeque<u64>,
+}
+
+impl Sent<'_> {
+    // Reserve what a batch about to be sent will hold, the documents and about as much again for
+    // what they're converted to, waiting for the batches sent before to be written until it fits,
+    // or until none are left.
+    fn reserve(&mut self, batch: &[bson::RawDocumentBuf], acknowledgements: &std::sync::mpsc::Receiver<(u64, u64)>) {
+        let bytes = 2 * batch.iter().map(|document| document.as_bytes().len() as u64).sum::<u64>();
+        self.documents += batch.len() as u64;
+        if let Some(budget) = self.budget {
+            while !budget.try_reserve(bytes) {
+                let acknowledgement = if self.reserved.is_empty() { None } else { acknowledgements.recv().ok() };
+                match acknowledgement {
+                    // The writer stopped, or there's nothing left to wait for.
+                    None => {
+                        budget.reserve(bytes);
+                        break;
+                    }
+                    Some(acknowledgement) => self.acknowledge(acknowledgement),
+                }
+            }
+        }
+        self.reserved.push_back(bytes);
+    }
+
+    // The writer wrote the next batch's documents, or didn't.
+    fn acknowledge(&mut self, (found, unwritten): (u64, u64)) {
+        self.acknowledged += found + unwritten;
+        self.failed += unwritten;
+        if let Some(bytes) = self.reserved.pop_front() {
+            crate::memory::release(self.budget, bytes);
+        }
+    }
+}
+
+fn dump_documents(
+    cli: &Cli,
+    mut inputs: Inputs<'_>,
+    output: &mut dyn DocumentWrite,
+    parquet_schema: Option<arrow_schema::SchemaRef>,
+    to_terminal: bool,
+    metadata: Option<serde_json::Value>,
+    report: Option<&mut ValidationReport>,
+) -> Result<Summary, BsondumpError> {
+    let mut sink = std::io::sink();
+    let timing = cli.timing.then(crate::timing::Timing::default);
+    let budget = cli.max_memory.map(|size| std::sync::Arc::new(crate::memory::Budget::new(size.0)));
+    let mut timed;
+    let mut writer: &mut dyn DocumentWrite = match &timing {
+        None => output,
+        Some(timing) => {
+            timed = Timed { writer: output, timing };
+            &mut timed
+        }
+    };
+    let terminator = if cli.json_array { "" } else { "\n" };
+    let separator = if cli.output_type == OutputType::PrettyJson { ",\n" } else { "," };
+
+    let color = match cli.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => to_terminal && std::env::var_os("NO_COLOR").is_none(),
+    };
+    let canonical = crate::ExtJsonOptions {
+        relaxed: false,
+        sort_keys: cli.sort_keys,
+        uuid_format: cli.uuid.map(crate::binary::UuidFormat::from),
+        uuid_representation: cli.uuid_representation.into(),
+        date_format: cli.date_format.clone(),
+        decimal_format: cli.decimal_format.into(),
+        invalid_utf8: match (cli.strict_utf8, cli.binary_fallback) {
+            (true, _) => crate::utf8::InvalidUtf8::Fail,
+            (_, true) => crate::utf8::InvalidUtf8::Binary,
+            _ => crate::utf8::InvalidUtf8::Replace,
+        },
+        binary_dir: cli.binary_dir.clone().map(|path| crate::binary::BinaryDir {
+            path,
+            threshold: usize::try_from(cli.binary_threshold.0).unwrap_or(usize::MAX),
+        }),
+    };
+    let relaxed = crate::ExtJsonOptions { relaxed: true, ..canonical.clone() };
+    let json_format = JsonFormat {
+        pretty: false,
+        color,
+        indent: cli.indent,
+        extract: cli.extract.as_deref(),
+        keep_missing: cli.keep_missing,
+        oplog: cli.oplog,
+        ascii: cli.ascii_output,
+    };
+    let page_size = cli.page_size.unwrap_or_else(|| match terminal_size::terminal_size() {
+        Some((_, terminal_size::Height(height))) if to_terminal => {
+            (height as usize).saturating_sub(PAGE_OVERHEAD).max(1)
+        }
+        _ => DEFAULT_PAGE_SIZE,
+    });
+    let mut page: Vec<serde_json::Value> = Vec::new();
+    let mut schema = crate::schema::Schema::new();
+    let mut stats = crate::stats::SizeStats::new();
+    let lint_rules: Vec<crate::lint::Rule> = match &cli.lint_rules[..] {
+        [] => crate::lint::Rule::ALL.to_vec(),
+        rules => rules.iter().map(|&rule| rule.into()).collect(),
+    };
+    let lint_limits = crate::lint::Limits {
+        key_bytes: cli.lint_max_key_bytes,
+        array_length: cli.lint_max_array_length,
+        depth: cli.lint_max_depth,
+    };
+    let mut linter = crate::lint::Linter::new(&lint_rules, lint_limits);
+    let debug_options = crate::DebugOptions { type_names: cli.type_names, show_values: cli.show_values };
+    // csv and table output select their columns with --fields rather than projecting the document.
+    let include = if matches!(cli.output_type, OutputType::Csv | OutputType::Table) { &[] } else { &cli.fields[..] };
+    let projection = crate::projection::Projection::new(include, &cli.exclude_fields);
+    let transform = crate::transform::Transform::new(&cli.rename, &cli.drop);
+    let mut parquet_writer = None;
+    if cli.output_type == OutputType::Parquet && !cli.count {
+        let output = std::mem::replace(&mut writer, &mut sink);
+        parquet_writer = Some(crate::parquet::ParquetWriter::new(output, parquet_schema));
+    }
+
+    if let Some(metadata) = metadata {
+        let format = JsonFormat { pretty: cli.output_type == OutputType::PrettyJson, ..json_format };
+        print_json_value(&mut writer, &metadata, 0, &format, true, "\n")?;
+    }
+    if cli.json_array && !cli.field_stats {
+        write_str(&mut writer, 0, "[")?;
+    }
+    if cli.output_type == OutputType::Csv && !cli.count && !cli.field_stats {
+        print_csv_record(&mut writer, &cli.fields, 0)?;
+    }
+    writer.end_header();
+
+    if let Some(skip) = cli.skip {
+        inputs.skip_documents(skip)?;
+    }
+
+    let mut selection = Selection {
+        grep: cli.grep.clone().map(|regex| crate::grep::Grep::new(regex, cli.grep_field.clone())),
+        oplog_ns: match cli.oplog_ns.as_slice() {
+            [] => None,
+            patterns => Some(crate::oplog::NamespaceFilter::new(patterns.to_vec())),
+        },
+        oplog_range: match (cli.oplog_start, cli.oplog_end) {
+            (None, None) => None,
+            (start, end) => Some(crate::oplog::TimeRange { start, end }),
+        },
+        objcheck: cli.objcheck(),
+        invalid_utf8: canonical.invalid_utf8,
+        max_depth: cli.max_depth,
+        report,
+        filter: cli.filter.as_ref(),
+        transform: &transform,
+        projection: &projection,
+        redactor: cli.redact.as_ref().map(|rules| {
+            let key = cli.redact_key.as_deref().unwrap_or_default();
+            crate::redact::Redactor::with_seed(rules, key.as_bytes(), cli.seed)
+        }),
+        sample: cli.sample,
+        rng: match cli.seed {
+            None => StdRng::from_entropy(),
+            Some(seed) => StdRng::seed_from_u64(seed),
+        },
+        continue_on_error: cli.continue_on_error,
+        skipped: 0,
+        allow_partial: cli.allow_partial,
+        ids: cli.check_duplicate_ids.then(|| match &budget {
+            None => crate::duplicates::IdSet::new(),
+            Some(budget) => crate::duplicates::IdSet::new().budget(budget.clone()),
+        }),
+        deprecated: if cli.warn_deprecated || cli.fail_deprecated {
+            Some(crate::deprecated::DeprecatedCounts::new())
+        } else {
+            None
+        },
+        fail_deprecated: cli.fail_deprecated,
+        #[cfg(feature = "client")]
+        verifier: match (&cli.verify_against, &cli.verify_namespace) {
+            (Some(uri), Some(namespace)) => Some(
+                crate::client::Verifier::connect(uri, namespace, cli.verify_documents).map_err(verify_error(0))?,
+            ),
+            _ => None,
+        },
+        missing: 0,
+        different: 0,
+        timing: timing.as_ref(),
+    };
+    // --sampleCount and --tail have to see every document before they know which ones to output.
+    let mut buffered = None;
+    if let Some(sample_count) = cli.sample_count {
+        let mut reservoir = crate::sample::Reservoir::new(sample_count, selection.rng.clone());
+        while let Some(raw_doc_buf) = next_selected(&mut inputs, &mut selection, 0)? {
+            hold(budget.as_deref(), &raw_doc_buf, "--sampleCount")?;
+            if let Some(dropped) = reservoir.add(raw_doc_buf) {
+                crate::memory::release(budget.as_deref(), dropped.as_bytes().len() as u64);
+            }
+        }
+        buffered = Some(VecDeque::from(reservoir.into_items()));
+    }
+    if let Some(tail) = cli.tail {
+        let mut last: VecDeque<bson::RawDocumentBuf> = VecDeque::with_capacity(tail);
+        while let Some(raw_doc_buf) = next_selected(&mut inputs, &mut selection, 0)? {
+            if last.len() == tail {
+                if let Some(dropped) = last.pop_front() {
+                    crate::memory::release(budget.as_deref(), dropped.as_bytes().len() as u64);
+                }
+            }
+            if tail > 0 {
+                hold(budget.as_deref(), &raw_doc_buf, "--tail")?;
+                last.push_back(raw_doc_buf);
+            }
+        }
+        buffered = Some(last);
+    }
+    let mut sorted = None;
+    if let Some(key) = &cli.sort_by {
+        let spill_error = |err: std::io::Error| {
+            let message = format!("Failed to spill documents to a temporary file. {}", err);
+            BsondumpError::new(ErrorKind::Io, message)
+        };
+        let mut sorter = match &budget {
+            None => crate::sort::Sorter::new(key.clone()),
+            Some(budget) => crate::sort::Sorter::new(key.clone()).budget(budget.clone()),
+        };
+        loop {
+            let next = match buffered.as_mut() {
+                Some(buffered) => unhold(budget.as_deref(), buffered.pop_front()),
+                None => next_selected(&mut inputs, &mut selection, 0)?,
+            };
+            match next {
+                None => break,
+                Some(raw_doc_buf) => sorter.add(raw_doc_buf).map_err(spill_error)?,
+            }
+        }
+        sorted = Some(sorter.finish().map_err(spill_error)?);
+    }
+
+    // Conversion failures are otherwise skipped with --noobjcheck.
+    let fail_on_error = cli.objcheck() || cli.continue_on_error || cli.strict_utf8;
+    let render = Render {
+        output_type: cli.output_type,
+        canonical: &canonical,
+        relaxed: &relaxed,
+        json_format,
+        debug_options,
+        fields: &cli.fields,
+        terminator,
+        fail_on_error,
+    };
+    let mut next_document = |selection: &mut Selection, num_found| match (sorted.as_mut(), buffered.as_mut()) {
+        (Some(sorted), _) => sorted.next_document().map_err(io_error(num_found)),
+        (None, Some(buffered)) => Ok(unhold(budget.as_deref(), buffered.pop_front())),
+        (None, None) => next_selected(&mut inputs, selection, num_found),
+    };
+    let mut num_found = 0;
+    let pipelined = render.is_per_document() && !cli.count && !cli.field_stats;
+    if pipelined {
+        let prefix = if cli.json_array { separator } else { "" };
+        num_found = pipeline(cli, &render, prefix, writer, &mut selection, budget.as_deref(), &mut next_document)?;
+    }
+    while !pipelined && cli.limit.map_or(true, |limit| num_found < limit) {
+        let raw_doc_buf = match next_document(&mut selection, num_found)? {
+            None => break,
+            Some(raw_doc_buf) => raw_doc_buf,
+        };
+        let corrupt =
+            |err: &dyn std::fmt::Display| BsondumpError::document(num_found, ErrorKind::Corrupt, err.to_string());
+
+        if cli.field_stats {
+            match schema.add_document(&raw_doc_buf) {
+                Err(err) => selection.skip("the document", corrupt(&err))?,
+                Ok(()) => num_found += 1,
+            }
+            continue;
+        }
+
+        if cli.count {
+            if fail_on_error {
+                if let Err(err) = to_extjson_value(&raw_doc_buf, &canonical, num_found, true) {
+                    selection.skip("the document", err)?;
+                    continue;
+                }
+            }
+            // Only the documents --extract would output are counted.
+            if cli.extract.is_some() && !cli.keep_missing {
+                match render.document(&mut std::io::sink(), &raw_doc_buf, num_found, "") {
+                    Err(err) => {
+                        selection.skip("the document", err)?;
+                        continue;
+                    }
+                    Ok(false) => continue,
+                    Ok(true) => (),
+                }
+            }
+            num_found += 1;
+            continue;
+        }
+
+        // Written just before the document, once it's converted, so that a skipped document leaves
+        // no separator behind.
+        let prefix = if cli.json_array && num_found > 0 { separator } else { "" };
+        let write_document = || -> Result<bool, BsondumpError> {
+            match cli.output_type {
+                _ if render.is_per_document() => return render.document(&mut writer, &raw_doc_buf, num_found, prefix),
+                OutputType::Table => {
+                    if let Some(value) = to_extjson_value(&raw_doc_buf, &relaxed, num_found, fail_on_error)? {
+                        page.push(value);
+                        if page.len() >= page_size {
+                            print_table_page(&mut writer, &cli.fields, &mut page, num_found)?;
+                        }
+                    }
+                }
+                OutputType::Schema => schema.add_document(&raw_doc_buf).map_err(|err| corrupt(&err))?,
+                OutputType::Stats => stats.add_document(&raw_doc_buf).map_err(|err| corrupt(&err))?,
+                OutputType::Lint => {
+                    for violation in linter.lint(&raw_doc_buf).map_err(|err| corrupt(&err))? {
+                        let value = violation.to_json(Some(num_found as u64));
+                        print_json_value(&mut writer, &value, num_found, &json_format, true, "\n")?;
+                    }
+                }
+                OutputType::Parquet => {
+                    if let Some(value) = to_extjson_value(&raw_doc_buf, &relaxed, num_found, fail_on_error)? {
+                        parquet_writer.as_mut().unwrap().add_document(&value).map_err(|err| corrupt(&err))?;
+                    }
+                }
+                _ => unreachable!("Per-document output types are rendered"),
+            };
+            Ok(true)
+        };
+        match crate::timing::time_converting(selection.timing, write_document) {
+            Err(err) => {
+                selection.skip("the document", err)?;
+                continue;
+            }
+            Ok(false) => continue,
+            Ok(true) => (),
+        }
+        // Show each document as soon as it's appended rather than when the output buffer fills.
+        if cli.follow {
+            writer.flush().map_err(io_error(num_found))?;
+        }
+        writer.end_document().map_err(io_error(num_found))?;
+
+        num_found += 1;
+    }
+
+    if cli.json_array && !cli.field_stats {
+        write_str(&mut writer, num_found, "]\n")?;
+    }
+    print_table_page(&mut writer, &cli.fields, &mut page, num_found)?;
+    if let Some(parquet_writer) = parquet_writer {
+        match parquet_writer.finish() {
+            Err(err) => return Err(BsondumpError::document(num_found, ErrorKind::Io, format!("{}", err))),
+            Ok(output) => writer = output,
+        }
+    }
+    let report = match cli.output_type {
+        _ if cli.count => writeln!(writer, "{}", num_found),
+        OutputType::Table if cli.field_stats => {
+            let columns = ["field", "present", "null", "missing"].map(String::from);
+            crate::table::write_table(&mut writer, &columns, &schema.field_stats())
+        }
+        _ if cli.field_stats => {
+            print_field_stats(&mut writer, &schema.field_stats(), cli.json_array, cli.ascii_output)
+        }
+        OutputType::Schema => schema.write_report(&mut writer),
+        OutputType::Stats => stats.write_report(&mut writer),
+        _ => Ok(()),
+    };
+    report.map_err(io_error(num_found))?;
+    if cli.output_type == OutputType::Lint && !cli.count {
+        for violation in linter.finish() {
+            print_json_value(&mut writer, &violation.to_json(None), num_found, &json_format, true, "\n")?;
+        }
+    }
+    writer.flush().map_err(io_error(num_found))?;
+    if let Some(counts) = &selection.deprecated {
+        for (field, type_name, count) in counts.counts() {
+            warn!("{} deprecated {} elements at {}", count, type_name, field);
+        }
+    }
+    let duplicates = match selection.ids.take() {
+        None => 0,
+        Some(ids) => report_duplicates(ids, &inputs.names).map_err(io_error(num_found))?,
+    };
+    #[cfg(feature = "client")]
+    if let Some(verifier) = selection.verifier.as_mut() {
+        let mismatches = verifier.finish().map_err(verify_error(num_found))?;
+        report_mismatches(&mut selection, mismatches, &inputs.names);
+    }
+    if let Some(timing) = &timing {
+        timing.add_read(inputs.bytes_read());
+    }
+    Ok(Summary {
+        found: num_found,
+        skipped: selection.skipped,
+        duplicates,
+        missing: selection.missing,
+        different: selection.different,
+        files: None,
+        timing: timing.clone(),
+    })
+}
+
+#[cfg(feature = "client")]
+fn verify_error(num_found: u32) -> impl Fn(mongodb::error::Error) -> BsondumpError {
+    move |err| {
+        let message = format!("Failed to verify against the cluster. {}", err);
+        BsondumpError::document(num_found, ErrorKind::Io, message)
+    }
+}
+
+// Log and count each document that --verifyAgainst found missing from the collection or different
+// there.
+#[cfg(feature = "client")]
+fn report_mismatches(
+    selection: &mut Selection,
+    mismatches: Vec<(crate::duplicates::Location, crate::client::Mismatch)>,
+    names: &[String],
+) {
+    let namespace = selection.verifier.as_ref().map_or("", |verifier| verifier.namespace());
+    for (location, mismatch) in mismatches {
+        let name = &names[location.input as usize];
+        match mismatch {
+            crate::client::Mismatch::Missing => {
+                warn!("{}: document #{} is missing from {}", name, location.index, namespace);
+                selection.missing += 1;
+            }
+            crate::client::Mismatch::Different => {
+                warn!("{}: document #{} is different in {}", name, location.index, namespace);
+                selection.different += 1;
+            }
+        }
+    }
+}
+
+// Log each group of documents that share an _id, returning how many documents have the _id of an
+// earlier one.
+fn report_duplicates(ids: crate::duplicates::IdSet, names: &[String]) -> std::io::Result<u32> {
+    let mut duplicates = 0;
+    for group in ids.duplicates()? {
+        let documents: Vec<String> = group
+            .iter()
+            .map(|location| format!("{}: document #{}", names[location.input as usize], location.index))
+            .collect();
+        warn!("Duplicate _id in {}", documents.join(", "));
+        duplicates += group.len() as u32 - 1;
+    }
+    Ok(duplicates)
+}
+
+// Invalid documents fail the run once the report has been written.
+fn finish_report(cli: &Cli, report: Option<ValidationReport>) -> Result<(), BsondumpError> {
+    let path = cli.validate_report.as_deref().unwrap_or_default();
+    let finish = |report: ValidationReport| -> std::io::Result<u64> {
+        let (invalid, writer) = report.finish()?;
+        writer.finish().map(|()| invalid)
+    };
+    match report.map(finish) {
+        None | Some(Ok(0)) => Ok(()),
+        Some(Ok(invalid)) => {
+            Err(BsondumpError::new(ErrorKind::Corrupt, format!("{} invalid documents; see {}", invalid, path)))
+        }
+        Some(Err(err)) => {
+            Err(BsondumpError::new(ErrorKind::Io, format!("Failed to write {path}. {err}", path = path, err = err)))
+        }
+    }
+}
+
+/// What a successful run did.
+#[derive(Debug)]
+pub struct Summary {
+    /// How many documents were output.
+    pub found: u32,
+    /// How many documents --continueOnError skipped.
+    pub skipped: u32,
+    /// How many documents --checkDuplicateIds found with the `_id` of an earlier document.
+    pub duplicates: u32,
+    /// How many documents --verifyAgainst found missing from the collection.
+    pub missing: u32,
+    /// How many documents --verifyDocuments found different in the collection.
+    pub different: u32,
+    /// How many inputs were each dumped to their own file, when they were.
+    pub files: Option<usize>,
+    /// Where the time went, with --timing.
+    pub timing: Option<crate::timing::Timing>,
+}
+
+/// Converts a stream of BSON documents the way the command line does, for programs that embed
+/// bsondump, starting from the command line's defaults:
+///
+/// ```no_run
+/// use bsondump::{Bsondump, OutputType};
+///
+/// let input = std::fs::File::open("dump/test/users.bson")?;
+/// let summary = Bsondump::new().output(OutputType::PrettyJson).objcheck(true).run(input, std::io::stdout())?;
+/// println!("{} documents", summary.found);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Bsondump {
+    cli: Cli,
+}
+
+impl Default for Bsondump {
+    fn default() -> Self {
+        Bsondump::new()
+    }
+}
+
+impl Bsondump {
+    pub fn new() -> Self {
+        Bsondump { cli: Cli::parse_from(["bsondump"]) }
+    }
+
+    /// The format to output documents in; JSON by default.
+    pub fn output(mut self, output_type: OutputType) -> Self {
+        self.cli.output_type = output_type;
+        self
+    }
+
+    /// Whether to validate each document, which is the default. Without it, documents that fail
+    /// to convert are skipped.
+    pub fn objcheck(mut self, objcheck: bool) -> Self {
+        self.cli.noobjcheck = !objcheck;
+        self
+    }
+
+    /// Skip documents that can't be output instead of failing, counting them in the summary.
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.cli.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// The fields to output, or the columns of csv and table output.
+    pub fn fields(mut self, fields: &[String]) -> Self {
+        self.cli.fields = fields.to_vec();
+        self
+    }
+
+    /// Convert the documents read from `reader`, which may be compressed, and write them to
+    /// `writer`.
+    pub fn run<R: Read, W: Write + Send>(&self, reader: R, writer: W) -> Result<Summary, BsondumpError> {
+        validate(&self.cli)?;
+        let inputs = Inputs::reader(reader, self.cli.max_bson_size.0 as u32)?;
+        let mut output = BufWriter::with_capacity(self.cli.output_buffer_size(), writer);
+        dump_documents(&self.cli, inputs, &mut output, None, false, None, None)
+    }
+}
+
+// Check the options that are wrong whatever is read, for run and Bsondump::run alike.
+fn validate(cli: &Cli) -> Result<(), BsondumpError> {
+    let usage = |message: &str| Err(BsondumpError::new(ErrorKind::Usage, message.to_string()));
+
+    let is_json = matches!(cli.output_type, OutputType::Json | OutputType::PrettyJson | OutputType::RelaxedJson);
+    if cli.json_array && !is_json {
+        return usage("--jsonArray can only be used with JSON output types");
+    }
+    if cli.output_type == OutputType::Csv && cli.fields.is_empty() {
+        return usage("--fields is required when using --type=csv");
+    }
+    if cli.include_metadata && !is_json {
+        return usage("--includeMetadata can only be used with JSON output types");
+    }
+    if cli.oplog && !is_json {
+        return usage("--oplog can only be used with JSON output types");
+    }
+    if cli.extract.is_some() && !is_json {
+        return usage("--extract can only be used with JSON output types");
+    }
+    if cli.ascii_output && !is_json {
+        return usage("--asciiOutput can only be used with JSON output types");
+    }
+    if cli.field_stats && !is_json && cli.output_type != OutputType::Table {
+        return usage("--fieldStats can only be used with JSON output types or --type=table");
+    }
+    if cli.redact.as_ref().is_some_and(|rules| rules.uses(crate::redact::Strategy::Hash)) && cli.redact_key.is_none() {
+        return usage("--redact's hash strategy needs --redactKey");
+    }
+    if cli.max_depth == 0 {
+        return usage("--maxDepth must be at least 1");
+    }
+    if cli.threads == 0 {
+        return usage("--threads must be at least 1");
+    }
+    // Sizes are int32s.
+    let max_bson_size = match u32::try_from(cli.max_bson_size.0) {
+        Ok(size) if (5..=i32::MAX as u32).contains(&size) => size,
+        _ => return usage("--maxBsonSize must be at least 5 bytes and less than 2GB"),
+    };
+    // Every document is in memory at least while it's read.
+    if cli.max_memory.is_some_and(|size| size.0 < u64::from(max_bson_size)) {
+        return usage("--maxMemory must be at least --maxBsonSize");
+    }
+    if cli.sample.is_some_and(|fraction| !(0.0..=1.0).contains(&fraction)) {
+        return usage("--sample must be between 0 and 1");
+    }
+    let splitting = cli.split_every.is_some() || cli.split_size.is_some();
+    let single_file = matches!(
+        cli.output_type,
+        OutputType::Parquet | OutputType::Schema | OutputType::Stats | OutputType::Table
+    );
+    if splitting && single_file {
+        return usage("--splitEvery and --splitSize can't be used with --type=parquet, schema, stats or table");
+    }
+    if cli.split_every == Some(0) {
+        return usage("--splitEvery must be at least 1");
+    }
+    if cli.parquet_schema.is_some() && cli.output_type != OutputType::Parquet {
+        return usage("--parquetSchema can only be used with --type=parquet");
+    }
+    if cli.checksum.is_some() && single_file {
+        return usage("--checksum can't be used with --type=parquet, schema, stats or table");
+    }
+    Ok(())
+}
+
+/// Dump the inputs that `cli` names the way it asks to.
+pub fn run(mut cli: Cli) -> Result<Summary, BsondumpError> {
+    cli.files.extend(cli.bson_file.take());
+    let usage = |message: &str| Err(BsondumpError::new(ErrorKind::Usage, message.to_string()));
+
+    validate(&cli)?;
+    // validate has checked that it's an int32.
+    let max_bson_size = cli.max_bson_size.0 as u32;
+    let parquet_schema = match cli.parquet_schema.as_deref() {
+        None => None,
+        Some(path) => {
+            let message_type = std::fs::read_to_string(path).map_err(|err| {
+                BsondumpError::new(ErrorKind::Io, format!("Failed to read {path}. {err}", path = path, err = err))
+            })?;
+            match crate::parquet::parse_schema(&message_type) {
+                Err(err) => return usage(&format!("Invalid Parquet schema in {path}. {err}", path = path, err = err)),
+                Ok(schema) => Some(schema),
+            }
+        }
+    };
+
+    // Without the S3 client, an s3:// --outFile would otherwise be written to a local s3: directory.
+    let s3_path = cli.files.iter().chain(&cli.out_file).find(|path| path.starts_with("s3://"));
+    if let Some(path) = s3_path.filter(|_| cfg!(not(feature = "remote"))) {
+        return usage(&format!("{} is an S3 object, which needs bsondump to be built with the remote feature", path));
+    }
+    let https_url = cli.files.iter().find(|path| path.starts_with("https://"));
+    if let Some(url) = https_url.filter(|_| cfg!(not(feature = "remote"))) {
+        return usage(&format!("{} is an https URL, which needs bsondump to be built with the remote feature", url));
+    }
+
+    let mut files: Vec<String> = Vec::new();
+    for arg in &cli.files {
+        if crate::http::is_url(arg) || arg.starts_with("s3://") || !crate::dumpdir::is_glob(arg) {
+            files.push(arg.clone());
+            continue;
+        }
+        match crate::dumpdir::expand_glob(arg) {
+            Err(err) => return usage(&format!("Failed to expand {}. {}", arg, err)),
+            Ok(matches) if matches.is_empty() => {
+                return Err(BsondumpError::new(ErrorKind::Io, format!("No files match {}", arg)));
+            }
+            Ok(matches) => files.extend(matches.iter().map(|path| path.to_string_lossy().into_owned())),
+        }
+    }
+
+    // Without --gzip or --compression, each input's compression is detected from its extension or
+    // its first bytes.
+    let compression = if cli.gzip { Some(Compression::Gzip) } else { cli.compression.map(Compression::from) };
+
+    let directory_mode = files.iter().any(|path| Path::new(path).is_dir());
+    let template = cli.out_file.as_deref().filter(|path| crate::dumpdir::is_template(path));
+    // Each input is dumped to its own output file.
+    let per_file = directory_mode || template.is_some();
+    if directory_mode && cli.out_file.is_some() && template.is_none() {
+        return usage(
+            "--outFile can't be used with a directory input; use --outDir or a template like {dir}/{stem}.json",
+        );
+    }
+    if cli.include_metadata && !per_file {
+        return usage("--includeMetadata can only be used when writing each input to its own file");
+    }
+    if template.is_some() && (cli.out_dir.is_some() || cli.archive.is_some() || files.is_empty()) {
+        return usage("An --outFile template needs input files, and can't be used with --outDir or --archive");
+    }
+    if cli.out_dir.is_some() && !directory_mode {
+        return usage("--outDir can only be used with a directory input");
+    }
+    // Waiting for someone to type BSON is never what was meant.
+    if files.is_empty() && cli.archive.is_none() && !cli.force_stdin && stdin().is_terminal() {
+        eprintln!("{}", Cli::command().render_usage());
+        return usage("No input file given and stdin is a terminal; pass a file, pipe one in, or use --forceStdin");
+    }
+    let binary_output = matches!(cli.output_type, OutputType::Bson | OutputType::Parquet)
+        || cli.output_compression().is_some();
+    if binary_output && cli.out_file.is_none() && !directory_mode && !cli.force && stdout().is_terminal() {
+        return usage("Refusing to write binary output to a terminal; use --outFile, redirect stdout, or use --force");
+    }
+    // The digests are written beside the output file.
+    if cli.checksum.is_some() && cli.out_file.is_none() && !per_file {
+        return usage("--checksum needs --outFile or a directory input");
+    }
+    if cli.follow && (files.is_empty() || per_file) {
+        return usage("--follow can only be used with BSON file inputs");
+    }
+    // Sorting waits for the end of input, which a followed file never reaches.
+    if cli.follow && cli.sort_by.is_some() {
+        return usage("--follow can't be used with --sortBy");
+    }
+    if cfg!(not(feature = "client")) && cli.verify_against.is_some() {
+        return usage("--verifyAgainst needs bsondump to be built with the client feature");
+    }
+    if cfg!(not(all(target_os = "linux", feature = "io-uring"))) && cli.io_uring {
+        return usage("--ioUring needs bsondump to be built for Linux with the io-uring feature");
+    }
+    if cli.verify_namespace.as_ref().is_some_and(|namespace| !namespace.contains('.')) {
+        return usage("--verifyNamespace must be db.collection");
+    }
+    if let Some(dir) = &cli.binary_dir {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            let message = format!("Failed to create directory {}. {}", dir.display(), err);
+            return Err(BsondumpError::new(ErrorKind::Io, message));
+        }
+    }
+    let mut report = match cli.validate_report.as_deref() {
+        None => None,
+        Some(path) => {
+            let report =
+                create_output(Path::new(path), cli.output_buffer_size()).and_then(crate::validate::Report::new);
+            Some(report.map_err(|err| {
+                BsondumpError::new(ErrorKind::Io, format!("Failed to write {path}. {err}", path = path, err = err))
+            })?)
+        }
+    };
+    let mut jobs: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for path in files.iter().map(Path::new).filter(|_| per_file) {
+        let (root, files) = match path.is_dir() {
+            false => (path.parent().unwrap_or(path), vec![path.to_path_buf()]),
+            true => match crate::dumpdir::bson_files(path) {
+                Err(err) => {
+                    let message = format!("Failed to read directory {}. {}", path.display(), err);
+                    return Err(BsondumpError::new(ErrorKind::Io, message));
+                }
+                Ok(files) => (path, files),
+            },
+        };
+        for input in files {
+            let extension = match cli.output_compression() {
+                None => cli.output_type.extension().to_string(),
+                Some(compression) => format!("{}.{}", cli.output_type.extension(), compression.extension()),
+            };
+            let output = match template {
+                Some(template) => crate::dumpdir::render_template(template, &input),
+                None => crate::dumpdir::output_path(root, &input, cli.out_dir.as_deref(), &extension),
+            };
+            if output == input {
+                return usage(&format!("Output for {} would overwrite it; use --outDir", input.display()));
+            }
+            jobs.push((input, output));
+        }
+    }
+
+    if per_file {
+        let mut total = Summary {
+            found: 0,
+            skipped: 0,
+            duplicates: 0,
+            missing: 0,
+            different: 0,
+            files: Some(jobs.len()),
+            timing: cli.timing.then(crate::timing::Timing::default),
+        };
+        for (input, output) in &jobs {
+            let paths = [input.to_string_lossy().into_owned()];
+            let mut inputs =
+                Inputs::new(&paths, cli.skip_bytes, compression, false, cli.io_uring, cli.mmap, max_bson_size)?;
+            if show_progress(&cli, false) {
+                inputs.show_progress(&paths);
+            }
+            let metadata = match crate::dumpdir::metadata_path(input).filter(|_| cli.include_metadata) {
+                None => None,
+                Some(path) => match crate::dumpdir::read_metadata(&path) {
+                    Err(err) => {
+                        let message = format!("Failed to read {}. {}", path.display(), err);
+                        return Err(BsondumpError::new(ErrorKind::Io, message));
+                    }
+                    Ok(metadata) => Some(metadata),
+                },
+            };
+            let summary = dump(&cli, inputs, Some(output), parquet_schema.clone(), false, metadata, report.as_mut())?;
+            info!("{}: {} objects found", input.display(), summary.found);
+            total.found += summary.found;
+            total.skipped += summary.skipped;
+            total.duplicates += summary.duplicates;
+            total.missing += summary.missing;
+            total.different += summary.different;
+            if let (Some(total), Some(timing)) = (&total.timing, &summary.timing) {
+                total.merge(timing);
+            }
+        }
+        finish_report(&cli, report)?;
+        return check_summary(&cli, total);
+    }
+
+    let to_terminal = cli.out_file.is_none() && stdout().is_terminal();
+    let inputs = match cli.archive.as_deref() {
+        None => {
+            let mut inputs =
+                Inputs::new(&files, cli.skip_bytes, compression, cli.follow, cli.io_uring, cli.mmap, max_bson_size)?;
+            if !cli.follow && show_progress(&cli, to_terminal) {
+                inputs.show_progress(&files);
+            }
+            inputs
+        }
+        Some(path) => Inputs::archive(path, &cli.ns_include, compression, max_bson_size)?,
+    };
+    let out_file = cli.out_file.as_deref().map(Path::new);
+    let summary = dump(&cli, inputs, out_file, parquet_schema, to_terminal, None, report.as_mut())?;
+    finish_report(&cli, report).map_err(|err| BsondumpError { found: Some(summary.found), ..err })?;
+    check_summary(&cli, summary)
+}
+
+// Progress is shown on stderr when it's a terminal, unless it's quieted with -q, or the output is
+// going to the same terminal.
+fn show_progress(cli: &Cli, to_terminal: bool) -> bool {
+    !to_terminal && std::io::stderr().is_terminal() && cli.log_level_filter() >= log::LevelFilter::Info
+}
+
+// Duplicate _ids fail the run once everything has been output, as they'd fail a restore, as do
+// documents that --verifyAgainst didn't find.
+fn check_summary(cli: &Cli, summary: Summary) -> Result<Summary, BsondumpError> {
+    let namespace = cli.verify_namespace.as_deref().unwrap_or_default();
+    let message = if summary.duplicates > 0 {
+        format!("{} documents have the same _id as an earlier document", summary.duplicates)
+    } else if summary.missing > 0 || summary.different > 0 {
+        format!("{} documents are missing from {} and {} are different", summary.missing, namespace, summary.different)
+    } else {
+        return Ok(summary);
+    };
+    Err(BsondumpError::document(summary.found, ErrorKind::Corrupt, message))
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use bson::{doc, Bson, RawDocument, RawDocumentBuf};
+use mongodb::{
+    options::FindOptions,
+    sync::{Client, Collection},
+};
+
+use crate::duplicates::Location;
+
+// How many _ids are looked up in one query.
+const BATCH_SIZE: usize = 1000;
+
+/// What's wrong with a document, compared to the collection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// No document in the collection has its `_id`.
+    Missing,
+    /// The document in the collection with its `_id` has different bytes.
+    Different,
+}
+
+/// Checks that the documents of a dump are in a collection, by `_id`, and optionally that the
+/// documents there are the same, by comparing digests of their bytes. Since the bytes are compared,
+/// documents with their fields in a different order are different.
+pub struct Verifier {
+    collection: Collection<RawDocumentBuf>,
+    namespace: String,
+    compare_documents: bool,
+    // The documents waiting to be looked up.
+    pending: Vec<Pending>,
+}
+
+struct Pending {
+    // The raw bytes of the _id element, to match it with the one in the collection.
+    key: Vec<u8>,
+    id: Bson,
+    // The digest of the document, when comparing them.
+    digest: Option<[u8; 32]>,
+    location: Location,
+}
+
+impl Verifier {
+    /// A verifier for the collection at `namespace`, `db.collection`, on the cluster at `uri`.
+    pub fn connect(uri: &str, namespace: &str, compare_documents: bool) -> Result<Self, mongodb::error::Error> {
+        let (database, collection) = namespace.split_once('.').unwrap_or((namespace, ""));
+        let client = Client::with_uri_str(uri)?;
+        Ok(Verifier {
+            collection: client.database(database).collection(collection),
+            namespace: namespace.to_string(),
+            compare_documents,
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Add the document at `location`, returning the documents that don't match the collection
+    /// once a batch of them has been looked up. Documents without an `_id` are ignored.
+    pub fn add(
+        &mut self,
+        document: &RawDocument,
+        location: Location,
+    ) -> Result<Vec<(Location, Mismatch)>, mongodb::error::Error> {
+        let id = match document.get("_id") {
+            Ok(Some(id)) => id,
+            _ => return Ok(Vec::new()),
+        };
+        let mut element = RawDocumentBuf::new();
+        element.append_ref("", id);
+        let id = match Bson::try_from(id.to_raw_bson()) {
+            Err(err) => return Err(mongodb::error::Error::custom(err)),
+            Ok(id) => id,
+        };
+        let digest = self.compare_documents.then(|| crate::checksum::sha256(document.as_bytes()));
+        self.pending.push(Pending { key: element.into_bytes(), id, digest, location });
+        if self.pending.len() < BATCH_SIZE {
+            return Ok(Vec::new());
+        }
+        self.flush()
+    }
+
+    /// Look up the documents still waiting, returning the ones that don't match the collection.
+    pub fn finish(&mut self) -> Result<Vec<(Location, Mismatch)>, mongodb::error::Error> {
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<Vec<(Location, Mismatch)>, mongodb::error::Error> {
+        if self.pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids: Vec<Bson> = self.pending.iter().map(|pending| pending.id.clone()).collect();
+        // Without comparing documents, only the _ids are needed.
+        let projection = if self.compare_documents { None } else { Some(doc! { "_id": 1 }) };
+        let options = FindOptions::builder().projection(projection).build();
+        let mut found = HashMap::new();
+        for remote in self.collection.find(doc! { "_id": { "$in": ids } }, options)? {
+            let remote = remote?;
+            let id = match remote.get("_id") {
+                Ok(Some(id)) => id,
+                _ => continue,
+            };
+            let mut element = RawDocumentBuf::new();
+            element.append_ref("", id);
+            let digest = self.compare_documents.then(|| crate::checksum::sha256(remote.as_bytes()));
+            found.insert(element.into_bytes(), digest);
+        }
+        let mismatches = self
+            .pending
+            .drain(..)
+            .filter_map(|pending| match found.get(&pending.key) {
+                None => Some((pending.location, Mismatch::Missing)),
+                Some(remote) if *remote != pending.digest => Some((pending.location, Mismatch::Different)),
+                Some(_) => None,
+            })
+            .collect();
+        Ok(mismatches)
+    }
+}
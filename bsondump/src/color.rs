@@ -0,0 +1,148 @@
+use std::io::{self, Write};
+
+use serde::ser::Serialize;
+use serde_json::ser::{CharEscape, Formatter, Serializer};
+
+const RESET: &[u8] = b"\x1b[0m";
+const KEY: &[u8] = b"\x1b[34m";
+// Keys such as $oid or $numberLong that wrap a value in extended JSON.
+const TYPE_WRAPPER: &[u8] = b"\x1b[35m";
+const STRING: &[u8] = b"\x1b[32m";
+const NUMBER: &[u8] = b"\x1b[33m";
+const LITERAL: &[u8] = b"\x1b[36m";
+
+/// A formatter that wraps another formatter and highlights JSON tokens with ANSI escape codes.
+pub struct ColorFormatter<F> {
+    inner: F,
+    in_key: bool,
+    // The color of a key depends on its first character, so its opening quote is written lazily.
+    pending_key_quote: bool,
+}
+
+impl<F: Formatter> ColorFormatter<F> {
+    pub fn new(inner: F) -> Self {
+        ColorFormatter { inner, in_key: false, pending_key_quote: false }
+    }
+
+    fn write_pending_key_quote<W: ?Sized + Write>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()> {
+        if self.pending_key_quote {
+            self.pending_key_quote = false;
+            writer.write_all(if fragment.starts_with('$') { TYPE_WRAPPER } else { KEY })?;
+            writer.write_all(b"\"")?;
+        }
+        Ok(())
+    }
+
+    fn colored<W, T>(writer: &mut W, color: &[u8], write: T) -> io::Result<()>
+    where
+        W: ?Sized + Write,
+        T: FnOnce(&mut W) -> io::Result<()>,
+    {
+        writer.write_all(color)?;
+        write(writer)?;
+        writer.write_all(RESET)
+    }
+}
+
+impl<F: Formatter> Formatter for ColorFormatter<F> {
+    fn write_null<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        Self::colored(writer, LITERAL, |writer| self.inner.write_null(writer))
+    }
+
+    fn write_bool<W: ?Sized + Write>(&mut self, writer: &mut W, value: bool) -> io::Result<()> {
+        Self::colored(writer, LITERAL, |writer| self.inner.write_bool(writer, value))
+    }
+
+    fn write_i64<W: ?Sized + Write>(&mut self, writer: &mut W, value: i64) -> io::Result<()> {
+        Self::colored(writer, NUMBER, |writer| self.inner.write_i64(writer, value))
+    }
+
+    fn write_u64<W: ?Sized + Write>(&mut self, writer: &mut W, value: u64) -> io::Result<()> {
+        Self::colored(writer, NUMBER, |writer| self.inner.write_u64(writer, value))
+    }
+
+    fn write_f64<W: ?Sized + Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        Self::colored(writer, NUMBER, |writer| self.inner.write_f64(writer, value))
+    }
+
+    fn write_number_str<W: ?Sized + Write>(&mut self, writer: &mut W, value: &str) -> io::Result<()> {
+        Self::colored(writer, NUMBER, |writer| self.inner.write_number_str(writer, value))
+    }
+
+    fn begin_string<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.in_key {
+            self.pending_key_quote = true;
+            return Ok(());
+        }
+        writer.write_all(STRING)?;
+        self.inner.begin_string(writer)
+    }
+
+    fn end_string<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.write_pending_key_quote(writer, "")?;
+        self.inner.end_string(writer)?;
+        writer.write_all(RESET)
+    }
+
+    fn write_string_fragment<W: ?Sized + Write>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()> {
+        self.write_pending_key_quote(writer, fragment)?;
+        self.inner.write_string_fragment(writer, fragment)
+    }
+
+    fn write_char_escape<W: ?Sized + Write>(&mut self, writer: &mut W, char_escape: CharEscape) -> io::Result<()> {
+        self.write_pending_key_quote(writer, "")?;
+        self.inner.write_char_escape(writer, char_escape)
+    }
+
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        self.in_key = true;
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.in_key = false;
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_value(writer)
+    }
+}
+
+pub fn to_colored_string<F: Formatter>(
+    value: &serde_json::value::Value,
+    formatter: F,
+) -> std::result::Result<String, std::io::Error> {
+    let mut colored_json: Vec<u8> = Vec::new();
+    let mut ser = Serializer::with_formatter(&mut colored_json, ColorFormatter::new(formatter));
+    value.serialize(&mut ser)?;
+    Ok(String::from_utf8_lossy(&colored_json).to_string())
+}
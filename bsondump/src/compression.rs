@@ -0,0 +1,121 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder};
+
+/// How an input stream is compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    /// The snappy framing format, as written by e.g. `snzip`
+    Snappy,
+}
+
+// The gzip magic number followed by the deflate compression method. A BSON document only starts
+// with these bytes if it is over half a megabyte.
+const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+// The stream identifier chunk that every snappy framed stream starts with.
+const SNAPPY_MAGIC: [u8; 10] = [0xff, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'];
+
+impl Compression {
+    /// The extension conventionally appended to a file compressed this way.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Snappy => "sz",
+        }
+    }
+}
+
+/// The compression implied by a file's extension, if any.
+pub fn from_extension(path: &Path) -> Option<Compression> {
+    match path.extension()?.to_str()? {
+        "gz" => Some(Compression::Gzip),
+        "zst" => Some(Compression::Zstd),
+        "sz" | "snappy" => Some(Compression::Snappy),
+        _ => None,
+    }
+}
+
+/// Detect the compression of `reader` from its first bytes, without consuming them.
+pub fn detect<R: BufRead>(reader: &mut R) -> io::Result<Option<Compression>> {
+    let head = reader.fill_buf()?;
+    let compression = [
+        (&GZIP_MAGIC[..], Compression::Gzip),
+        (&ZSTD_MAGIC[..], Compression::Zstd),
+        (&SNAPPY_MAGIC[..], Compression::Snappy),
+    ]
+    .into_iter()
+    .find(|(magic, _)| head.starts_with(magic))
+    .map(|(_, compression)| compression);
+    Ok(compression)
+}
+
+/// `reader` with a buffer large enough that documents are mostly read straight out of it.
+pub fn buffered<R: Read>(reader: R) -> BufReader<R> {
+    BufReader::with_capacity(crate::docbytes::READ_BUFFER_SIZE, reader)
+}
+
+/// Wrap `reader` so that reading from it yields the decompressed stream.
+pub fn decompress<'r, R: BufRead + 'r>(reader: R, compression: Compression) -> io::Result<Box<dyn BufRead + 'r>> {
+    Ok(match compression {
+        Compression::Gzip => Box::new(buffered(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(buffered(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Snappy => Box::new(buffered(snap::read::FrameDecoder::new(reader))),
+    })
+}
+
+enum EncoderKind<W: Write> {
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Snappy(Box<snap::write::FrameEncoder<W>>),
+}
+
+/// Compresses everything written to it into the underlying writer. The compressed stream is only
+/// complete once `finish` is called.
+pub struct Encoder<W: Write> {
+    kind: EncoderKind<W>,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        let kind = match compression {
+            Compression::Gzip => EncoderKind::Gzip(GzEncoder::new(writer, flate2::Compression::default())),
+            Compression::Zstd => EncoderKind::Zstd(zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?),
+            Compression::Snappy => EncoderKind::Snappy(Box::new(snap::write::FrameEncoder::new(writer))),
+        };
+        Ok(Encoder { kind })
+    }
+
+    /// Write the end of the compressed stream and return the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.kind {
+            EncoderKind::Gzip(encoder) => encoder.finish(),
+            EncoderKind::Zstd(encoder) => encoder.finish(),
+            EncoderKind::Snappy(encoder) => encoder.into_inner().map_err(|err| err.into_error()),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.kind {
+            EncoderKind::Gzip(encoder) => encoder.write(buf),
+            EncoderKind::Zstd(encoder) => encoder.write(buf),
+            EncoderKind::Snappy(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.kind {
+            EncoderKind::Gzip(encoder) => encoder.flush(),
+            EncoderKind::Zstd(encoder) => encoder.flush(),
+            EncoderKind::Snappy(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, SecondsFormat, TimeZone, Utc,
+};
+use serde_json::{json, Value};
+
+/// How `$date` values are rendered in extended JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DateFormat {
+    /// ISO-8601 in the local time zone, e.g. `2014-11-14T20:03:03.000+01:00`
+    Iso8601Local,
+    /// ISO-8601 in UTC, e.g. `2014-11-14T19:03:03.000Z`
+    Iso8601Utc,
+    /// Milliseconds since the Unix epoch as a JSON number
+    EpochMillis,
+    /// A strftime-style pattern, formatted in UTC
+    Custom(String),
+}
+
+#[derive(Debug)]
+pub struct ParseDateFormatError(String);
+
+impl std::fmt::Display for ParseDateFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid date format: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDateFormatError {}
+
+impl std::str::FromStr for DateFormat {
+    type Err = ParseDateFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iso8601-local" => Ok(DateFormat::Iso8601Local),
+            "iso8601-utc" => Ok(DateFormat::Iso8601Utc),
+            "epoch-millis" => Ok(DateFormat::EpochMillis),
+            pattern if pattern.contains('%') => {
+                // chrono panics when formatting with an invalid pattern, so reject it up front.
+                if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+                    return Err(ParseDateFormatError(pattern.to_string()));
+                }
+                Ok(DateFormat::Custom(pattern.to_string()))
+            }
+            other => Err(ParseDateFormatError(other.to_string())),
+        }
+    }
+}
+
+// Returns the milliseconds since the epoch held by a canonical or relaxed `$date` wrapper.
+fn extract_millis(value: &Value) -> Option<i64> {
+    match value.as_object().filter(|map| map.len() == 1)?.get("$date")? {
+        Value::Object(long) => long.get("$numberLong")?.as_str()?.parse().ok(),
+        Value::String(iso) => DateTime::parse_from_rfc3339(iso).ok().map(|datetime| datetime.timestamp_millis()),
+        _ => None,
+    }
+}
+
+fn format_millis(millis: i64, format: &DateFormat) -> Option<Value> {
+    let utc = Utc.timestamp_millis_opt(millis).single()?;
+    let formatted = match format {
+        DateFormat::Iso8601Local => utc.with_timezone(&Local).to_rfc3339_opts(SecondsFormat::Millis, false),
+        DateFormat::Iso8601Utc => utc.to_rfc3339_opts(SecondsFormat::Millis, true),
+        DateFormat::EpochMillis => return Some(json!(millis)),
+        DateFormat::Custom(pattern) => utc.format(pattern).to_string(),
+    };
+    Some(Value::String(formatted))
+}
+
+/// Recursively re-render the `$date` values in an extended JSON value.
+pub fn render_dates(value: &mut Value, format: &DateFormat) {
+    if let Some(formatted) = extract_millis(value).and_then(|millis| format_millis(millis, format)) {
+        *value = json!({ "$date": formatted });
+        return;
+    }
+    match value {
+        Value::Object(map) => map.values_mut().for_each(|entry| render_dates(entry, format)),
+        Value::Array(array) => array.iter_mut().for_each(|entry| render_dates(entry, format)),
+        _ => (),
+    }
+}
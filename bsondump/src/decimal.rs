@@ -0,0 +1,60 @@
+use log::warn;
+use serde_json::Value;
+
+/// How Decimal128 values are rendered in extended JSON.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DecimalFormat {
+    /// `{"$numberDecimal": "1.10"}`
+    #[default]
+    ExtJson,
+    /// `1.1`, which may lose precision
+    Number,
+    /// `"1.10"`
+    String,
+}
+
+// An f64 round-trips any decimal with at most this many significant digits.
+const F64_SIGNIFICANT_DIGITS: usize = 15;
+
+fn significant_digits(decimal: &str) -> usize {
+    let mantissa = decimal.split(['E', 'e']).next().unwrap_or_default();
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    digits.trim_start_matches('0').trim_end_matches('0').len()
+}
+
+fn to_number(decimal: &str) -> Value {
+    match decimal.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+        None => {
+            warn!("Decimal128 value {} cannot be represented as a JSON number", decimal);
+            Value::String(decimal.to_string())
+        }
+        Some(number) => {
+            if significant_digits(decimal) > F64_SIGNIFICANT_DIGITS {
+                warn!("Decimal128 value {} loses precision as a JSON number", decimal);
+            }
+            Value::Number(number)
+        }
+    }
+}
+
+/// Recursively re-render the `$numberDecimal` values in an extended JSON value.
+pub fn render_decimals(value: &mut Value, format: DecimalFormat) {
+    let decimal = value
+        .as_object()
+        .filter(|map| map.len() == 1)
+        .and_then(|map| map.get("$numberDecimal"))
+        .and_then(Value::as_str);
+    if let Some(decimal) = decimal {
+        match format {
+            DecimalFormat::ExtJson => (),
+            DecimalFormat::Number => *value = to_number(decimal),
+            DecimalFormat::String => *value = Value::String(decimal.to_string()),
+        }
+        return;
+    }
+    match value {
+        Value::Object(map) => map.values_mut().for_each(|entry| render_decimals(entry, format)),
+        Value::Array(array) => array.iter_mut().for_each(|entry| render_decimals(entry, format)),
+        _ => (),
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use bson::{spec::ElementType, RawBsonRef, RawDocument};
+
+/// Element types that are deprecated in the BSON spec, and that newer drivers may not read.
+pub const DEPRECATED_TYPES: [ElementType; 3] = [ElementType::Symbol, ElementType::Undefined, ElementType::DbPointer];
+
+/// An element of a deprecated type, and the dot-separated path to it. `field` is the path with
+/// array indices as `[]`, as in --type=schema, so that it's the same for every element of an array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeprecatedElement {
+    pub path: String,
+    pub field: String,
+    pub element_type: ElementType,
+}
+
+impl std::fmt::Display for DeprecatedElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is a deprecated {}", self.path, crate::type_name(self.element_type))
+    }
+}
+
+/// Find the elements of deprecated types in a document, including in embedded documents and
+/// arrays, in the order they appear.
+pub fn find(document: &RawDocument) -> Result<Vec<DeprecatedElement>, bson::raw::Error> {
+    let mut found = Vec::new();
+    // Each level's elements, its path and field, and whether it's an array.
+    let mut stack = vec![(document.iter_elements(), String::new(), String::new(), false)];
+    while let Some((elements, path, field, array)) = stack.last_mut() {
+        let element = match elements.next() {
+            None => {
+                stack.pop();
+                continue;
+            }
+            Some(element) => element?,
+        };
+        let key = if *array { "[]" } else { element.key() };
+        let (element_path, element_field) = match path.as_str() {
+            "" => (element.key().to_string(), key.to_string()),
+            path => (format!("{}.{}", path, element.key()), format!("{}.{}", field, key)),
+        };
+        let element_type = element.element_type();
+        if DEPRECATED_TYPES.contains(&element_type) {
+            found.push(DeprecatedElement { path: element_path, field: element_field, element_type });
+            continue;
+        }
+        let (embedded, array) = match element_type {
+            ElementType::EmbeddedDocument | ElementType::Array => match element.value()? {
+                RawBsonRef::Document(embedded) => (embedded, false),
+                RawBsonRef::Array(embedded) => (RawDocument::from_bytes(embedded.as_bytes())?, true),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        stack.push((embedded.iter_elements(), element_path, element_field, array));
+    }
+    Ok(found)
+}
+
+/// How many elements of each deprecated type were found at each path across a stream of
+/// documents.
+#[derive(Debug, Default)]
+pub struct DeprecatedCounts {
+    counts: BTreeMap<(String, &'static str), u64>,
+}
+
+impl DeprecatedCounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, element: &DeprecatedElement) {
+        let key = (element.field.clone(), crate::type_name(element.element_type));
+        *self.counts.entry(key).or_default() += 1;
+    }
+
+    /// The counts by field, then type name.
+    pub fn counts(&self) -> impl Iterator<Item = (&str, &'static str, u64)> {
+        self.counts.iter().map(|((path, type_name), count)| (path.as_str(), *type_name, *count))
+    }
+}
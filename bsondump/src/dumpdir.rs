@@ -0,0 +1,124 @@
+use std::{
+    error::Error,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Find every `.bson` file under `dir`, including the per-database subdirectories that mongodump
+/// creates, sorted by path so that output is deterministic.
+pub fn bson_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_bson_files(dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_bson_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_bson_files(&path, files)?;
+        } else if is_bson_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// mongodump --gzip writes .bson.gz files; other tools write .bson.zst or .bson.sz.
+fn is_bson_file(path: &Path) -> bool {
+    let path = strip_compression_extension(path);
+    path.extension().is_some_and(|extension| extension == "bson")
+}
+
+fn strip_compression_extension(path: &Path) -> PathBuf {
+    match crate::compression::from_extension(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Whether `arg` should be expanded as a glob pattern rather than used as a path. Patterns are
+/// expanded here, not only by the shell, so that they also work on Windows.
+pub fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?', '[']) && !Path::new(arg).exists()
+}
+
+/// The files matching a glob pattern such as `dump/**/*.bson`, sorted by path.
+pub fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The `.metadata.json` file that mongodump writes next to a collection's `.bson` file, if there
+/// is one.
+pub fn metadata_path(input: &Path) -> Option<PathBuf> {
+    let input = strip_compression_extension(input);
+    let stem = input.file_stem()?.to_string_lossy();
+    ["metadata.json", "metadata.json.gz"]
+        .iter()
+        .map(|extension| input.with_file_name(format!("{}.{}", stem, extension)))
+        .find(|path| path.is_file())
+}
+
+/// The collection options, indexes and UUID from a `.metadata.json` file, as a
+/// `{"metadata": {...}}` object to output ahead of the collection's documents.
+pub fn read_metadata(path: &Path) -> Result<serde_json::Value, Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+    let metadata: serde_json::Value = match crate::compression::from_extension(path) {
+        Some(compression) => {
+            let reader = crate::compression::decompress(io::BufReader::new(file), compression)?;
+            serde_json::from_reader(reader)?
+        }
+        None => serde_json::from_reader(io::BufReader::new(file))?,
+    };
+    let mut header = serde_json::Map::new();
+    for key in ["collectionName", "type", "uuid", "options", "indexes"] {
+        if let Some(value) = metadata.get(key) {
+            header.insert(key.to_string(), value.clone());
+        }
+    }
+    Ok(serde_json::json!({ "metadata": header }))
+}
+
+/// Whether an `--outFile` path is a template to fill in for each input, e.g. `{dir}/{stem}.json`.
+pub fn is_template(path: &str) -> bool {
+    ["{dir}", "{name}", "{stem}"].iter().any(|placeholder| path.contains(placeholder))
+}
+
+/// Fill in an output path template for `input`: `{dir}` is the directory containing it, `{name}`
+/// its file name, and `{stem}` its file name without `.bson` or a compression extension.
+pub fn render_template(template: &str, input: &Path) -> PathBuf {
+    let dir = match input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+        _ => ".".into(),
+    };
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    let uncompressed = strip_compression_extension(input);
+    let stem = match uncompressed.extension() {
+        Some(bson) if bson == "bson" => uncompressed.file_stem(),
+        _ => uncompressed.file_name(),
+    };
+    let stem = stem.unwrap_or_default().to_string_lossy();
+    PathBuf::from(template.replace("{dir}", &dir).replace("{name}", &name).replace("{stem}", &stem))
+}
+
+/// Where to write the output for `input`, found under `root`: the same relative path under
+/// `out_dir`, or next to the input when there is no `out_dir`, with `extension` in place of `.bson`
+/// and any compression extension such as `.gz`.
+pub fn output_path(root: &Path, input: &Path, out_dir: Option<&Path>, extension: &str) -> PathBuf {
+    let input = strip_compression_extension(input);
+    let input = input.as_path();
+    let relative = input.strip_prefix(root).unwrap_or(input);
+    match out_dir {
+        None => input.with_extension(extension),
+        Some(out_dir) => out_dir.join(relative).with_extension(extension),
+    }
+}
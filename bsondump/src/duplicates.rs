@@ -0,0 +1,164 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+use bson::{RawDocument, RawDocumentBuf};
+
+/// Where a document is: the index of its input, and its index among that input's documents.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub input: u32,
+    pub index: u64,
+}
+
+// How many ids are held in memory before they're sorted and spilled to a temporary file: 4M ids
+// take 128MB.
+const SPILL_AT: usize = 1 << 22;
+
+// The size of an id on disk: the hash, the input and the index, little-endian.
+const RECORD_SIZE: usize = 16 + 4 + 8;
+
+// The size of an id in memory.
+const ID_SIZE: u64 = std::mem::size_of::<(u128, Location)>() as u64;
+
+/// The `_id`s of the documents of a dump, to find the documents that share one. Each id is kept
+/// as a hash of its raw bytes, including its type, so `1` and `NumberLong(1)` are different ids.
+/// Once there are too many to hold in memory, they're sorted and spilled to temporary files, which
+/// are merged at the end.
+pub struct IdSet {
+    ids: Vec<(u128, Location)>,
+    runs: Vec<File>,
+    spill_at: usize,
+    budget: Option<Arc<crate::memory::Budget>>,
+}
+
+impl Default for IdSet {
+    fn default() -> Self {
+        IdSet::new()
+    }
+}
+
+impl IdSet {
+    pub fn new() -> Self {
+        IdSet::with_spill_at(SPILL_AT)
+    }
+
+    /// An id set that spills to disk once it holds `spill_at` ids.
+    pub fn with_spill_at(spill_at: usize) -> Self {
+        IdSet { ids: Vec::new(), runs: Vec::new(), spill_at: spill_at.max(1), budget: None }
+    }
+
+    /// Also spill once the ids held can't be reserved from `budget`.
+    pub fn budget(mut self, budget: Arc<crate::memory::Budget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Add the `_id` of the document at `location`. Documents without one are ignored.
+    pub fn add(&mut self, document: &RawDocument, location: Location) -> io::Result<()> {
+        let id = match document.get("_id") {
+            Ok(Some(id)) => id,
+            _ => return Ok(()),
+        };
+        let mut element = RawDocumentBuf::new();
+        element.append_ref("", id);
+        let digest = crate::checksum::sha256(element.as_bytes());
+        let hash = u128::from_le_bytes(digest[..16].try_into().unwrap());
+        if !crate::memory::try_reserve(self.budget.as_deref(), ID_SIZE) {
+            if !self.ids.is_empty() {
+                self.spill()?;
+            }
+            if let Some(budget) = &self.budget {
+                budget.reserve(ID_SIZE);
+            }
+        }
+        self.ids.push((hash, location));
+        if self.ids.len() >= self.spill_at {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.ids.sort_unstable();
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        crate::memory::release(self.budget.as_deref(), self.ids.len() as u64 * ID_SIZE);
+        for (hash, location) in self.ids.drain(..) {
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&location.input.to_le_bytes())?;
+            writer.write_all(&location.index.to_le_bytes())?;
+        }
+        let mut file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    /// The groups of documents that share an `_id`, each in the order they were added, ordered by
+    /// their first document.
+    pub fn duplicates(mut self) -> io::Result<Vec<Vec<Location>>> {
+        self.ids.sort_unstable();
+        crate::memory::release(self.budget.as_deref(), self.ids.len() as u64 * ID_SIZE);
+        let mut runs: Vec<Run> = Vec::new();
+        runs.push(Run::Memory(std::mem::take(&mut self.ids).into_iter()));
+        runs.extend(self.runs.into_iter().map(|file| Run::File(BufReader::new(file))));
+
+        // Merge the sorted runs, grouping equal hashes as they come out.
+        let mut heap = BinaryHeap::new();
+        for (run, ids) in runs.iter_mut().enumerate() {
+            if let Some((hash, location)) = ids.next_id()? {
+                heap.push(Reverse((hash, location, run)));
+            }
+        }
+        let mut groups = Vec::new();
+        let mut group: Vec<Location> = Vec::new();
+        let mut group_hash = None;
+        while let Some(Reverse((hash, location, run))) = heap.pop() {
+            if group_hash != Some(hash) {
+                if group.len() > 1 {
+                    groups.push(std::mem::take(&mut group));
+                }
+                group.clear();
+                group_hash = Some(hash);
+            }
+            group.push(location);
+            if let Some((hash, location)) = runs[run].next_id()? {
+                heap.push(Reverse((hash, location, run)));
+            }
+        }
+        if group.len() > 1 {
+            groups.push(group);
+        }
+        groups.sort();
+        Ok(groups)
+    }
+}
+
+// A sorted run of ids, in memory or spilled.
+enum Run {
+    Memory(std::vec::IntoIter<(u128, Location)>),
+    File(BufReader<File>),
+}
+
+impl Run {
+    fn next_id(&mut self) -> io::Result<Option<(u128, Location)>> {
+        let reader = match self {
+            Run::Memory(ids) => return Ok(ids.next()),
+            Run::File(reader) => reader,
+        };
+        let mut record = [0u8; RECORD_SIZE];
+        match reader.read_exact(&mut record) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+            Ok(()) => (),
+        }
+        let hash = u128::from_le_bytes(record[..16].try_into().unwrap());
+        let input = u32::from_le_bytes(record[16..20].try_into().unwrap());
+        let index = u64::from_le_bytes(record[20..].try_into().unwrap());
+        Ok(Some((hash, Location { input, index })))
+    }
+}
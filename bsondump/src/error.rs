@@ -0,0 +1,62 @@
+/// Why reading, parsing or converting a document failed.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing failed.
+    Io(std::io::Error),
+    /// The document's bytes aren't valid BSON.
+    Parse(bson::raw::Error),
+    /// The document is valid BSON but couldn't be converted, e.g. to extended JSON.
+    Convert(bson::ser::Error),
+    /// The document's size is too small, too large or negative, the input ended before it did, or
+    /// it isn't terminated.
+    Size(crate::docbytes::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::Parse(err) => err.fmt(f),
+            Error::Convert(err) => err.fmt(f),
+            Error::Size(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::Convert(err) => Some(err),
+            Error::Size(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<bson::raw::Error> for Error {
+    fn from(err: bson::raw::Error) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl From<bson::ser::Error> for Error {
+    fn from(err: bson::ser::Error) -> Self {
+        Error::Convert(err)
+    }
+}
+
+impl From<crate::docbytes::Error> for Error {
+    fn from(err: crate::docbytes::Error) -> Self {
+        match err {
+            crate::docbytes::Error::IOError(err) => Error::Io(err),
+            err => Error::Size(err),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::Write;
+
+use bson::{RawBsonRef, RawDocument};
+
+const ONES: u64 = 0x0101_0101_0101_0101;
+const HIGHS: u64 = 0x8080_8080_8080_8080;
+
+// Dates from 1970 up to the end of year 9999 are written as RFC 3339 strings in relaxed mode.
+const MAX_RFC3339_MILLIS: i64 = 253_402_300_800_000;
+
+/// Append `document` to `buf` as compact extended JSON, straight from its raw bytes rather than
+/// through a `Bson` tree and a `serde_json::Value`. The output is the same as writing the value
+/// `to_canonical_extjson_value` or `to_relaxed_extjson_value` returns.
+///
+/// Returns false, leaving `buf` as it was, for a document that converting treats differently from
+/// its bytes, so that it has to be converted instead: one with a key repeated, of which
+/// converting keeps the last value, or a key starting with `$`, which converting reads as
+/// extended JSON, or holding JavaScript code with scope or a DBPointer.
+pub fn write_document(buf: &mut Vec<u8>, document: &RawDocument, relaxed: bool) -> Result<bool, bson::raw::Error> {
+    let len = buf.len();
+    let written = Writer { buf, relaxed }.document(document)?;
+    if !written {
+        buf.truncate(len);
+    }
+    Ok(written)
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+// Whether any of the eight bytes of `word` is a control character, a quote or a backslash, testing
+// them all at once.
+fn needs_escape(word: u64) -> bool {
+    let has_zero = |word: u64| word.wrapping_sub(ONES) & !word & HIGHS != 0;
+    let has_control = word.wrapping_sub(ONES * 0x20) & !word & HIGHS != 0;
+    has_control || has_zero(word ^ (ONES * b'"' as u64)) || has_zero(word ^ (ONES * b'\\' as u64))
+}
+
+struct Writer<'a> {
+    buf: &'a mut Vec<u8>,
+    relaxed: bool,
+}
+
+impl Writer<'_> {
+    fn document(&mut self, document: &RawDocument) -> Result<bool, bson::raw::Error> {
+        let mut keys = Vec::new();
+        self.buf.push(b'{');
+        for element in document {
+            let (key, value) = element?;
+            if key.starts_with('$') {
+                return Ok(false);
+            }
+            if !keys.is_empty() {
+                self.buf.push(b',');
+            }
+            keys.push(key);
+            self.string(key);
+            self.buf.push(b':');
+            if !self.value(value)? {
+                return Ok(false);
+            }
+        }
+        self.buf.push(b'}');
+        keys.sort_unstable();
+        Ok(keys.windows(2).all(|pair| pair[0] != pair[1]))
+    }
+
+    fn value(&mut self, value: RawBsonRef) -> Result<bool, bson::raw::Error> {
+        match value {
+            RawBsonRef::Double(double) => self.double(double),
+            RawBsonRef::String(string) => self.string(string),
+            RawBsonRef::Document(embedded) => return self.document(embedded),
+            RawBsonRef::Array(array) => {
+                self.buf.push(b'[');
+                for (index, element) in array.into_iter().enumerate() {
+                    if index > 0 {
+                        self.buf.push(b',');
+                    }
+                    if !self.value(element?)? {
+                        return Ok(false);
+                    }
+                }
+                self.buf.push(b']');
+            }
+            RawBsonRef::Boolean(boolean) => self.raw(if boolean { "true" } else { "false" }),
+            RawBsonRef::Null => self.raw("null"),
+            RawBsonRef::RegularExpression(regex) => {
+                let mut options: Vec<char> = regex.options.chars().collect();
+                options.sort_unstable();
+                let options: String = options.into_iter().collect();
+                self.raw(r#"{"$regularExpression":{"pattern":"#);
+                self.string(regex.pattern);
+                self.raw(r#","options":"#);
+                self.string(&options);
+                self.raw("}}");
+            }
+            RawBsonRef::JavaScriptCode(code) => self.wrapped("$code", code),
+            RawBsonRef::Int32(int) if self.relaxed => self.raw(itoa::Buffer::new().format(int)),
+            RawBsonRef::Int32(int) => self.wrapped("$numberInt", itoa::Buffer::new().format(int)),
+            RawBsonRef::Int64(int) if self.relaxed => self.raw(itoa::Buffer::new().format(int)),
+            RawBsonRef::Int64(int) => self.wrapped("$numberLong", itoa::Buffer::new().format(int)),
+            RawBsonRef::Timestamp(timestamp) => {
+                self.raw(r#"{"$timestamp":{"t":"#);
+                self.raw(itoa::Buffer::new().format(timestamp.time));
+                self.raw(r#","i":"#);
+                self.raw(itoa::Buffer::new().format(timestamp.increment));
+                self.raw("}}");
+            }
+            RawBsonRef::Binary(binary) => {
+                self.raw(r#"{"$binary":{"base64":"#);
+                self.string(&base64::encode(binary.bytes));
+                let _ = write!(self.buf, r#","subType":"{:02x}"}}}}"#, u8::from(binary.subtype));
+            }
+            RawBsonRef::ObjectId(oid) => self.wrapped("$oid", &oid.to_hex()),
+            RawBsonRef::DateTime(datetime) => {
+                let millis = datetime.timestamp_millis();
+                match datetime.try_to_rfc3339_string() {
+                    Ok(date) if self.relaxed && (0..MAX_RFC3339_MILLIS).contains(&millis) => {
+                        self.wrapped("$date", &date)
+                    }
+                    _ => {
+                        self.raw(r#"{"$date":"#);
+                        self.wrapped("$numberLong", itoa::Buffer::new().format(millis));
+                        self.buf.push(b'}');
+                    }
+                }
+            }
+            RawBsonRef::Symbol(symbol) => self.wrapped("$symbol", symbol),
+            RawBsonRef::Decimal128(decimal) => self.wrapped("$numberDecimal", &decimal.to_string()),
+            RawBsonRef::Undefined => self.raw(r#"{"$undefined":true}"#),
+            RawBsonRef::MaxKey => self.raw(r#"{"$maxKey":1}"#),
+            RawBsonRef::MinKey => self.raw(r#"{"$minKey":1}"#),
+            RawBsonRef::JavaScriptCodeWithScope(_) | RawBsonRef::DbPointer(_) => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Canonical mode writes finite doubles as strings, other than subnormal ones, as converting
+    // does.
+    fn double(&mut self, double: f64) {
+        if double.is_nan() {
+            self.wrapped("$numberDouble", if double.is_sign_negative() { "-NaN" } else { "NaN" });
+        } else if double.is_infinite() {
+            self.wrapped("$numberDouble", if double.is_sign_negative() { "-Infinity" } else { "Infinity" });
+        } else if self.relaxed || !(double.is_normal() || double == 0.0) {
+            self.raw(ryu::Buffer::new().format_finite(double));
+        } else if double == 0.0 {
+            self.wrapped("$numberDouble", if double.is_sign_negative() { "-0.0" } else { "0.0" });
+        } else if double.fract() == 0.0 {
+            self.wrapped("$numberDouble", &format!("{}.0", double));
+        } else {
+            self.wrapped("$numberDouble", &double.to_string());
+        }
+    }
+
+    // Escape a string the way serde_json does. The bytes that need escaping are rare, so they're
+    // looked for eight at a time, and the runs between them copied whole.
+    fn string(&mut self, string: &str) {
+        let bytes = string.as_bytes();
+        self.buf.push(b'"');
+        // Where the run that hasn't been copied yet starts.
+        let mut start = 0;
+        let mut index = 0;
+        while index < bytes.len() {
+            if let Some(word) = bytes.get(index..index + 8) {
+                if !needs_escape(u64::from_le_bytes(word.try_into().unwrap())) {
+                    index += 8;
+                    continue;
+                }
+            }
+            let escaped: &[u8] = match bytes[index] {
+                b'"' => b"\\\"",
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                0x08 => b"\\b",
+                0x0c => b"\\f",
+                byte @ 0..=0x1f => &[b'\\', b'u', b'0', b'0', HEX[(byte >> 4) as usize], HEX[(byte & 0xf) as usize]],
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            self.buf.extend_from_slice(&bytes[start..index]);
+            self.buf.extend_from_slice(escaped);
+            index += 1;
+            start = index;
+        }
+        self.buf.extend_from_slice(&bytes[start..]);
+        self.buf.push(b'"');
+    }
+
+    fn raw(&mut self, json: &str) {
+        self.buf.extend_from_slice(json.as_bytes());
+    }
+
+    // A string in a single-key object, e.g. {"$oid":"..."}.
+    fn wrapped(&mut self, key: &str, string: &str) {
+        self.buf.push(b'{');
+        self.string(key);
+        self.buf.push(b':');
+        self.string(string);
+        self.buf.push(b'}');
+    }
+}
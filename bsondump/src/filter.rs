@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+
+use bson::{Bson, Document};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// A single query operator applied to the values at a field path.
+#[derive(Clone, Debug)]
+enum Condition {
+    Eq(Bson),
+    Ne(Bson),
+    Gt(Bson),
+    Gte(Bson),
+    Lt(Bson),
+    Lte(Bson),
+    In(Vec<Bson>),
+    Nin(Vec<Bson>),
+    Exists(bool),
+    Regex(Regex),
+}
+
+/// A client-side match expression supporting a subset of the MongoDB query language: implicit
+/// equality and the `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`, `$nin`, `$exists`, and
+/// `$regex` operators on dot-separated field paths. Operands may use extended JSON, e.g.
+/// `{"_id": {"$gt": {"$oid": "546651f74bf6e4cb017c5312"}}}`.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    clauses: Vec<(String, Vec<Condition>)>,
+}
+
+#[derive(Debug)]
+pub struct ParseFilterError(String);
+
+impl std::fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid filter: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFilterError {}
+
+fn to_bson(value: &Value) -> Result<Bson, ParseFilterError> {
+    Bson::try_from(value.clone()).map_err(|err| ParseFilterError(err.to_string()))
+}
+
+fn to_bson_list(operator: &str, value: &Value) -> Result<Vec<Bson>, ParseFilterError> {
+    match value {
+        Value::Array(values) => values.iter().map(to_bson).collect(),
+        _ => Err(ParseFilterError(format!("{} needs an array", operator))),
+    }
+}
+
+fn to_regex(pattern: &str, options: &str) -> Result<Regex, ParseFilterError> {
+    let pattern = match options {
+        "" => pattern.to_string(),
+        _ => format!("(?{}){}", options, pattern),
+    };
+    Regex::new(&pattern).map_err(|err| ParseFilterError(err.to_string()))
+}
+
+// An object is an operator expression when its keys are operators, e.g. {"$gt": 5}, rather than
+// an extended JSON value such as {"$oid": ...}.
+fn is_operator_expression(map: &Map<String, Value>) -> bool {
+    let starts_with_dollar = map.keys().next().is_some_and(|key| key.starts_with('$'));
+    starts_with_dollar && matches!(Bson::try_from(Value::Object(map.clone())), Ok(Bson::Document(_)) | Err(_))
+}
+
+fn parse_conditions(map: &Map<String, Value>) -> Result<Vec<Condition>, ParseFilterError> {
+    let mut conditions = Vec::new();
+    for (operator, operand) in map {
+        let condition = match operator.as_str() {
+            "$eq" => Condition::Eq(to_bson(operand)?),
+            "$ne" => Condition::Ne(to_bson(operand)?),
+            "$gt" => Condition::Gt(to_bson(operand)?),
+            "$gte" => Condition::Gte(to_bson(operand)?),
+            "$lt" => Condition::Lt(to_bson(operand)?),
+            "$lte" => Condition::Lte(to_bson(operand)?),
+            "$in" => Condition::In(to_bson_list(operator, operand)?),
+            "$nin" => Condition::Nin(to_bson_list(operator, operand)?),
+            "$exists" => match operand {
+                Value::Bool(exists) => Condition::Exists(*exists),
+                _ => return Err(ParseFilterError("$exists needs a boolean".to_string())),
+            },
+            "$regex" => {
+                let options = match map.get("$options") {
+                    None => "",
+                    Some(Value::String(options)) => options,
+                    Some(_) => return Err(ParseFilterError("$options needs a string".to_string())),
+                };
+                match operand {
+                    Value::String(pattern) => Condition::Regex(to_regex(pattern, options)?),
+                    _ => return Err(ParseFilterError("$regex needs a string".to_string())),
+                }
+            }
+            "$options" if map.contains_key("$regex") => continue,
+            other => return Err(ParseFilterError(format!("unsupported operator {}", other))),
+        };
+        conditions.push(condition);
+    }
+    Ok(conditions)
+}
+
+impl std::str::FromStr for Filter {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let map = match serde_json::from_str(s) {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => return Err(ParseFilterError("expected a JSON object".to_string())),
+            Err(err) => return Err(ParseFilterError(err.to_string())),
+        };
+        let mut clauses = Vec::new();
+        for (path, value) in &map {
+            if path.starts_with('$') {
+                return Err(ParseFilterError(format!("unsupported operator {}", path)));
+            }
+            let conditions = match value {
+                Value::Object(operators) if is_operator_expression(operators) => parse_conditions(operators)?,
+                _ => match to_bson(value)? {
+                    Bson::RegularExpression(regex) => vec![Condition::Regex(to_regex(&regex.pattern, &regex.options)?)],
+                    other => vec![Condition::Eq(other)],
+                },
+            };
+            clauses.push((path.clone(), conditions));
+        }
+        Ok(Filter { clauses })
+    }
+}
+
+// Collect the values at `path`, descending into arrays of embedded documents the way MongoDB does.
+fn collect_values<'v>(value: &'v Bson, path: &[&str], values: &mut Vec<&'v Bson>) {
+    let (key, rest) = match path.split_first() {
+        None => return values.push(value),
+        Some(split) => split,
+    };
+    match value {
+        Bson::Document(document) => collect_document_values(document, key, rest, values),
+        Bson::Array(array) => match key.parse::<usize>() {
+            Ok(index) => {
+                if let Some(child) = array.get(index) {
+                    collect_values(child, rest, values);
+                }
+            }
+            Err(_) => array.iter().for_each(|element| {
+                if let Bson::Document(_) = element {
+                    collect_values(element, path, values);
+                }
+            }),
+        },
+        _ => (),
+    }
+}
+
+fn collect_document_values<'v>(document: &'v Document, key: &str, rest: &[&str], values: &mut Vec<&'v Bson>) {
+    if let Some(child) = document.get(key) {
+        collect_values(child, rest, values);
+    }
+}
+
+fn as_f64(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(n) => Some(*n as f64),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        Bson::Decimal128(n) => n.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+// Only values of comparable types are ordered; comparing e.g. a string with a number never matches.
+fn compare(a: &Bson, b: &Bson) -> Option<Ordering> {
+    match (a, b) {
+        (Bson::Int64(a), Bson::Int64(b)) => Some(a.cmp(b)),
+        (Bson::String(a), Bson::String(b)) => Some(a.cmp(b)),
+        (Bson::Boolean(a), Bson::Boolean(b)) => Some(a.cmp(b)),
+        (Bson::DateTime(a), Bson::DateTime(b)) => Some(a.cmp(b)),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => Some(a.bytes().cmp(&b.bytes())),
+        (Bson::Timestamp(a), Bson::Timestamp(b)) => Some((a.time, a.increment).cmp(&(b.time, b.increment))),
+        (Bson::Null, Bson::Null) => Some(Ordering::Equal),
+        _ => as_f64(a)?.partial_cmp(&as_f64(b)?),
+    }
+}
+
+fn equals(value: &Bson, operand: &Bson) -> bool {
+    value == operand || compare(value, operand) == Some(Ordering::Equal)
+}
+
+// A condition matches an array value if it matches the array itself or any of its elements.
+fn any_value(values: &[&Bson], predicate: impl Fn(&Bson) -> bool) -> bool {
+    values.iter().any(|value| predicate(value) || matches!(value, Bson::Array(array) if array.iter().any(&predicate)))
+}
+
+fn matches_eq(values: &[&Bson], operand: &Bson) -> bool {
+    // Like MongoDB, equality with null also matches a missing field.
+    (values.is_empty() && *operand == Bson::Null) || any_value(values, |value| equals(value, operand))
+}
+
+fn matches_in(values: &[&Bson], operands: &[Bson]) -> bool {
+    operands.iter().any(|operand| matches_eq(values, operand))
+}
+
+impl Condition {
+    fn matches(&self, values: &[&Bson]) -> bool {
+        let ordered = |operand: &Bson, expected: &[Ordering]| {
+            any_value(values, |value| compare(value, operand).is_some_and(|ordering| expected.contains(&ordering)))
+        };
+        match self {
+            Condition::Eq(operand) => matches_eq(values, operand),
+            Condition::Ne(operand) => !matches_eq(values, operand),
+            Condition::Gt(operand) => ordered(operand, &[Ordering::Greater]),
+            Condition::Gte(operand) => ordered(operand, &[Ordering::Greater, Ordering::Equal]),
+            Condition::Lt(operand) => ordered(operand, &[Ordering::Less]),
+            Condition::Lte(operand) => ordered(operand, &[Ordering::Less, Ordering::Equal]),
+            Condition::In(operands) => matches_in(values, operands),
+            Condition::Nin(operands) => !matches_in(values, operands),
+            Condition::Exists(exists) => values.is_empty() != *exists,
+            Condition::Regex(regex) => any_value(values, |value| match value {
+                Bson::String(string) | Bson::Symbol(string) => regex.is_match(string),
+                _ => false,
+            }),
+        }
+    }
+}
+
+impl Filter {
+    /// Whether `document` satisfies every clause of the filter.
+    pub fn matches(&self, document: &Document) -> bool {
+        self.clauses.iter().all(|(path, conditions)| {
+            let path: Vec<&str> = path.split('.').collect();
+            let mut values = Vec::new();
+            collect_document_values(document, path[0], &path[1..], &mut values);
+            conditions.iter().all(|condition| condition.matches(&values))
+        })
+    }
+}
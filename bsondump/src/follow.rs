@@ -0,0 +1,40 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    thread,
+    time::Duration,
+};
+
+/// How long to wait before looking for more data at the end of a followed file.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reads a file that's still being appended to, like `tail -f`: at the end of the file it waits
+/// for more data instead of reporting the end of input. A document that's only partly written
+/// when it's reached is therefore waited for until the rest of it arrives.
+pub struct Follow<R: Read> {
+    reader: R,
+    interval: Duration,
+}
+
+impl<R: Read> Follow<R> {
+    pub fn new(reader: R, interval: Duration) -> Self {
+        Follow { reader, interval }
+    }
+}
+
+impl<R: Read> Read for Follow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let count = self.reader.read(buf)?;
+            if count > 0 || buf.is_empty() {
+                return Ok(count);
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for Follow<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.reader.seek(pos)
+    }
+}
@@ -0,0 +1,82 @@
+use bson::{RawBsonRef, RawDocument};
+use regex::Regex;
+
+/// Matches documents with a string value, anywhere in the document or under one dot-separated
+/// path, that matches a regular expression. Works on the raw document so that non-matching
+/// documents are never converted.
+pub struct Grep {
+    regex: Regex,
+    path: Option<String>,
+}
+
+impl Grep {
+    pub fn new(regex: Regex, path: Option<String>) -> Self {
+        Grep { regex, path }
+    }
+
+    pub fn matches(&self, document: &RawDocument) -> Result<bool, bson::raw::Error> {
+        match &self.path {
+            None => self.matches_document(document),
+            Some(path) => self.matches_path(RawBsonRef::Document(document), &path.split('.').collect::<Vec<_>>()),
+        }
+    }
+
+    // Array elements on the path are addressed by index or, for arrays of embedded documents, by
+    // the next key.
+    fn matches_path(&self, value: RawBsonRef, path: &[&str]) -> Result<bool, bson::raw::Error> {
+        let (key, rest) = match path.split_first() {
+            None => return self.matches_value(value),
+            Some(split) => split,
+        };
+        match value {
+            RawBsonRef::Document(document) => match document.get(key)? {
+                None => Ok(false),
+                Some(child) => self.matches_path(child, rest),
+            },
+            RawBsonRef::Array(array) => match key.parse::<usize>() {
+                Ok(index) => match array.get(index)? {
+                    None => Ok(false),
+                    Some(child) => self.matches_path(child, rest),
+                },
+                Err(_) => {
+                    for element in array {
+                        let element = element?;
+                        if let RawBsonRef::Document(_) = element {
+                            if self.matches_path(element, path)? {
+                                return Ok(true);
+                            }
+                        }
+                    }
+                    Ok(false)
+                }
+            },
+            _ => Ok(false),
+        }
+    }
+
+    fn matches_document(&self, document: &RawDocument) -> Result<bool, bson::raw::Error> {
+        for element in document {
+            let (_, value) = element?;
+            if self.matches_value(value)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn matches_value(&self, value: RawBsonRef) -> Result<bool, bson::raw::Error> {
+        match value {
+            RawBsonRef::String(string) | RawBsonRef::Symbol(string) => Ok(self.regex.is_match(string)),
+            RawBsonRef::Document(document) => self.matches_document(document),
+            RawBsonRef::Array(array) => {
+                for element in array {
+                    if self.matches_value(element?)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::Write;
+
+use bson::{RawBsonRef, RawDocument};
+
+use crate::bytes::CountBytes;
+
+const BYTES_PER_ROW: usize = 16;
+
+struct Boundary {
+    offset: usize,
+    label: String,
+}
+
+pub fn hex_dump(raw_doc: &RawDocument) -> std::result::Result<String, std::io::Error> {
+    let mut buf: Vec<u8> = Vec::new();
+    hex_dump_document(&mut buf, raw_doc)?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+fn hex_dump_document<W: Write>(writer: &mut W, raw_doc: &RawDocument) -> std::result::Result<(), std::io::Error> {
+    let mut boundaries = Vec::new();
+    // Parse errors don't stop the dump: the bytes are still printed, annotated with where parsing failed.
+    if let Err(err) = collect_document_boundaries(raw_doc, 0, "", &mut boundaries) {
+        boundaries.push(err);
+    }
+    boundaries.sort_by_key(|boundary| boundary.offset);
+
+    let bytes = raw_doc.as_bytes();
+    let mut boundaries = boundaries.iter().peekable();
+    for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        let row_offset = row * BYTES_PER_ROW;
+        write!(writer, "{:08x} ", row_offset)?;
+        for i in 0..BYTES_PER_ROW {
+            if i == BYTES_PER_ROW / 2 {
+                write!(writer, " ")?;
+            }
+            match chunk.get(i) {
+                Some(byte) => write!(writer, " {:02x}", byte)?,
+                None => write!(writer, "   ")?,
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        write!(writer, "  |{:width$}|", ascii, width = BYTES_PER_ROW)?;
+
+        let mut labels = Vec::new();
+        while let Some(boundary) = boundaries.next_if(|boundary| boundary.offset < row_offset + chunk.len()) {
+            labels.push(format!("{}@{:#x}", boundary.label, boundary.offset));
+        }
+        if !labels.is_empty() {
+            write!(writer, "  {}", labels.join(" "))?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn collect_element_boundaries(
+    name: &str,
+    bson_ref: &RawBsonRef,
+    offset: usize,
+    prefix: &str,
+    boundaries: &mut Vec<Boundary>,
+) -> std::result::Result<usize, Boundary> {
+    let path = format!("{}{}", prefix, name);
+    boundaries.push(Boundary { offset, label: path.clone() });
+    let size_of_type = 1usize;
+    let size_of_name = name.len() + 1; // null terminator
+    let value_offset = offset + size_of_type + size_of_name;
+    match bson_ref {
+        RawBsonRef::Document(embedded) => {
+            collect_document_boundaries(embedded, value_offset, &format!("{}.", path), boundaries)?
+        }
+        RawBsonRef::Array(embedded) => {
+            let embedded = RawDocument::from_bytes(embedded.as_bytes()).map_err(|err| Boundary {
+                offset: value_offset,
+                label: format!("<error: {}>", err),
+            })?;
+            collect_document_boundaries(embedded, value_offset, &format!("{}.", path), boundaries)?
+        }
+        _ => (),
+    };
+    Ok(size_of_type + size_of_name + bson_ref.count_bytes())
+}
+
+fn collect_document_boundaries(
+    raw_doc: &RawDocument,
+    offset: usize,
+    prefix: &str,
+    boundaries: &mut Vec<Boundary>,
+) -> std::result::Result<(), Boundary> {
+    // Skip the i32 document size.
+    let mut element_offset = offset + 4;
+    for element in raw_doc {
+        let (name, bson_ref) = element.map_err(|err| Boundary {
+            offset: element_offset,
+            label: format!("<error: {}>", err),
+        })?;
+        element_offset += collect_element_boundaries(name, &bson_ref, element_offset, prefix, boundaries)?;
+    }
+    Ok(())
+}
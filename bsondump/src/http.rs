@@ -0,0 +1,318 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use log::warn;
+
+const MAX_REDIRECTS: usize = 5;
+const MAX_RETRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+// A connection that stalls for this long is dropped and the download resumed on a new one.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether an input path is a URL to download rather than a file.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+struct Url {
+    // Whether it's https, so that the connection is over TLS.
+    tls: bool,
+    host: String,
+    port: u16,
+    // The path and query sent in the request line.
+    target: String,
+}
+
+fn parse_url(url: &str) -> io::Result<Url> {
+    let (tls, rest) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+        (Some(rest), _) => (false, rest),
+        (None, Some(_)) if cfg!(not(feature = "remote")) => {
+            let message = format!("{} is an https URL, which needs bsondump to be built with the remote feature", url);
+            return Err(io::Error::new(io::ErrorKind::Unsupported, message));
+        }
+        (None, Some(rest)) => (true, rest),
+        (None, None) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not an http URL: {}", url))),
+    };
+    let (authority, target) = match rest.find(['/', '?']) {
+        None => (rest, "/".to_string()),
+        Some(index) if rest[index..].starts_with('?') => (&rest[..index], format!("/{}", &rest[index..])),
+        Some(index) => (&rest[..index], rest[index..].to_string()),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => match port.parse() {
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid port in {}", url))),
+            Ok(port) => (host, port),
+        },
+        _ => (authority, if tls { 443 } else { 80 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    Ok(Url { tls, host, port, target })
+}
+
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+// Connect to the URL's host, over TLS for https.
+fn connect(url: &Url) -> io::Result<Box<dyn Stream>> {
+    let stream = TcpStream::connect((url.host.as_str(), url.port))?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    match url.tls {
+        false => Ok(Box::new(stream)),
+        true => tls(stream, &url.host),
+    }
+}
+
+#[cfg(feature = "remote")]
+fn tls(stream: TcpStream, host: &str) -> io::Result<Box<dyn Stream>> {
+    static CONFIG: std::sync::OnceLock<std::sync::Arc<rustls::ClientConfig>> = std::sync::OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        let config = rustls::ClientConfig::builder().with_safe_defaults().with_root_certificates(roots);
+        std::sync::Arc::new(config.with_no_client_auth())
+    });
+    let name = rustls::ServerName::try_from(host)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid host name: {}", host)))?;
+    let connection = rustls::ClientConnection::new(config.clone(), name).map_err(io::Error::other)?;
+    Ok(Box::new(rustls::StreamOwned::new(connection, stream)))
+}
+
+// parse_url refuses https URLs without the remote feature.
+#[cfg(not(feature = "remote"))]
+fn tls(_stream: TcpStream, _host: &str) -> io::Result<Box<dyn Stream>> {
+    unreachable!()
+}
+
+// Check that a 206 response from `url` resumes the body at `offset` rather than elsewhere.
+fn check_range(url: &str, content_range: Option<&str>, offset: u64) -> io::Result<()> {
+    let start = content_range
+        .and_then(|range| range.strip_prefix("bytes "))
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.trim().parse::<u64>().ok());
+    match start {
+        Some(start) if start == offset => Ok(()),
+        _ => {
+            let range = content_range.unwrap_or("no Content-Range");
+            let message = format!("{} sent {} when asked for bytes {}-", url, range, offset);
+            Err(io::Error::new(io::ErrorKind::InvalidData, message))
+        }
+    }
+}
+
+// How the end of a response body is found.
+enum Framing {
+    Length(u64),
+    Chunked { remaining: u64, done: bool },
+    Close,
+}
+
+struct Body {
+    reader: BufReader<Box<dyn Stream>>,
+    framing: Framing,
+}
+
+impl Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.framing {
+            Framing::Length(0) => Ok(0),
+            Framing::Length(remaining) => {
+                let max = buf.len().min(*remaining as usize);
+                let count = self.reader.read(&mut buf[..max])?;
+                if count == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                *remaining -= count as u64;
+                Ok(count)
+            }
+            Framing::Chunked { done: true, .. } => Ok(0),
+            Framing::Chunked { remaining, done } => {
+                if *remaining == 0 {
+                    let line = read_line(&mut self.reader)?;
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    *remaining = u64::from_str_radix(size, 16)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+                    if *remaining == 0 {
+                        // Skip any trailer fields.
+                        while !read_line(&mut self.reader)?.is_empty() {}
+                        *done = true;
+                        return Ok(0);
+                    }
+                }
+                let max = buf.len().min(*remaining as usize);
+                let count = self.reader.read(&mut buf[..max])?;
+                if count == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                *remaining -= count as u64;
+                if *remaining == 0 {
+                    read_line(&mut self.reader)?;
+                }
+                Ok(count)
+            }
+            Framing::Close => self.reader.read(buf),
+        }
+    }
+}
+
+// A line of the response head or chunk framing, without its line ending.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Streams the body of an http URL, or an https one with the remote feature. When the connection
+/// drops or stalls partway through, the download resumes from where it stopped with a `Range`
+/// request, so that a transient failure doesn't restart a multi-GB download.
+pub struct HttpReader {
+    url: String,
+    offset: u64,
+    body: Body,
+}
+
+impl HttpReader {
+    pub fn open(url: &str) -> io::Result<Self> {
+        let body = request(url, 0)?;
+        Ok(HttpReader { url: url.to_string(), offset: 0, body })
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        let mut attempt = 1;
+        loop {
+            warn!("Download of {} interrupted; resuming at byte {}", self.url, self.offset);
+            thread::sleep(RETRY_DELAY * attempt);
+            match request(&self.url, self.offset) {
+                Ok(body) => {
+                    self.body = body;
+                    return Ok(());
+                }
+                Err(err) if attempt < MAX_RETRIES && is_transient(&err) => attempt += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retries = 0;
+        loop {
+            match self.body.read(buf) {
+                Ok(count) => {
+                    self.offset += count as u64;
+                    return Ok(count);
+                }
+                Err(err) if retries < MAX_RETRIES && is_transient(&err) => {
+                    retries += 1;
+                    self.resume()?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+// GET `url` from byte `offset` on, following redirects.
+fn request(url: &str, offset: u64) -> io::Result<Body> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let parsed = parse_url(&url)?;
+        let mut stream = connect(&parsed)?;
+        let mut head = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: bsondump\r\nAccept-Encoding: identity\r\nConnection: close\r\n",
+            parsed.target, parsed.host
+        );
+        if offset > 0 {
+            head.push_str(&format!("Range: bytes={}-\r\n", offset));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let status_line = read_line(&mut reader)?;
+        let status: u16 = status_line.split(' ').nth(1).and_then(|status| status.parse().ok()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("invalid HTTP response: {}", status_line))
+        })?;
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+        match status {
+            301 | 302 | 303 | 307 | 308 => {
+                let location = header("location")
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "redirect without a location"))?;
+                url = match location.starts_with('/') {
+                    true => {
+                        let scheme = if parsed.tls { "https" } else { "http" };
+                        format!("{}://{}:{}{}", scheme, parsed.host, parsed.port, location)
+                    }
+                    false => location.to_string(),
+                };
+                continue;
+            }
+            200 => (),
+            206 => check_range(&url, header("content-range"), offset)?,
+            _ => return Err(io::Error::other(format!("{} returned {}", url, status_line))),
+        }
+
+        let framing = if header("transfer-encoding").is_some_and(|encoding| encoding.contains("chunked")) {
+            Framing::Chunked { remaining: 0, done: false }
+        } else {
+            match header("content-length").map(|length| length.parse::<u64>()) {
+                None => Framing::Close,
+                Some(Ok(length)) => Framing::Length(length),
+                Some(Err(_)) => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")),
+            }
+        };
+        let mut body = Body { reader, framing };
+        // A server that ignores the Range header sends the whole body again.
+        if status == 200 && offset > 0 {
+            let mut skipped = 0;
+            let mut buf = [0u8; 8192];
+            while skipped < offset {
+                let max = buf.len().min((offset - skipped) as usize);
+                match body.read(&mut buf[..max])? {
+                    0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    count => skipped += count as u64,
+                }
+            }
+        }
+        return Ok(body);
+    }
+    Err(io::Error::other(format!("too many redirects from {}", url)))
+}
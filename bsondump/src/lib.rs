@@ -1,39 +1,362 @@
 use std::io::Write;
 
-use bson::{RawArray, RawBsonRef, RawDocument, RawDocumentBuf};
+use bson::{spec::ElementType, RawBsonRef, RawDocument, RawDocumentBuf};
 use serde::ser::Serialize;
 use serde_json::{ser::PrettyFormatter, Serializer};
 
-mod bytes;
-pub mod docbytes;
+pub mod archive;
+pub mod binary;
+pub mod checksum;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod bytes;
+pub mod color;
+#[cfg(feature = "cli")]
+pub mod compression;
+pub mod datetime;
+pub mod decimal;
+pub mod deprecated;
+#[cfg(feature = "cli")]
+pub mod dumpdir;
+#[cfg(feature = "cli")]
+pub mod duplicates;
+mod error;
+pub mod extjson;
+pub mod filter;
+#[cfg(feature = "cli")]
+pub mod follow;
+pub mod grep;
+pub mod hexdump;
+#[cfg(feature = "cli")]
+pub mod http;
+pub mod lint;
+pub mod memory;
+#[cfg(feature = "cli")]
+pub mod mmap;
+pub mod oplog;
+#[cfg(feature = "cli")]
+pub mod parallel;
+#[cfg(feature = "cli")]
+pub mod parquet;
+pub mod path;
+#[cfg(feature = "cli")]
+pub mod progress;
+pub mod projection;
+pub mod redact;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod sample;
+pub mod schema;
+#[cfg(feature = "cli")]
+pub mod sort;
+#[cfg(feature = "cli")]
+pub mod split;
+pub mod stats;
+pub mod table;
+#[cfg(feature = "cli")]
+pub mod timing;
+pub mod transform;
+pub mod units;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+pub mod utf8;
+pub mod validate;
+pub use bson_stream as docbytes;
 use bytes::CountBytes;
+#[cfg(feature = "cli")]
+pub use cli::{Bsondump, OutputType};
+pub use error::Error;
 
 
 fn get_indent(indent_level: usize) -> String {
     "\t".repeat(indent_level)
 }
 
-pub fn to_pretty_string(value: &serde_json::value::Value) -> std::result::Result<String, std::io::Error> {
+/// The indentation used for each nesting level of pretty-printed JSON.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Indent {
+    #[default]
+    Tab,
+    Spaces(usize),
+}
+
+impl Indent {
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Indent::Tab => b"\t".to_vec(),
+            Indent::Spaces(count) => vec![b' '; count],
+        }
+    }
+}
+
+impl std::str::FromStr for Indent {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "tab" => Ok(Indent::Tab),
+            spaces => Ok(Indent::Spaces(spaces.parse()?)),
+        }
+    }
+}
+
+pub fn to_pretty_string(
+    value: &serde_json::value::Value,
+    indent: Indent,
+) -> std::result::Result<String, std::io::Error> {
     let mut pretty_json: Vec<u8> = Vec::new();
-    let formatter = PrettyFormatter::with_indent(b"\t");
+    let indent = indent.to_bytes();
+    let formatter = PrettyFormatter::with_indent(&indent);
     let mut ser = Serializer::with_formatter(&mut pretty_json, formatter);
     value.serialize(&mut ser)?;
     Ok(String::from_utf8_lossy(&pretty_json).to_string())
 }
 
+/// `json` with every non-ASCII character escaped as \uXXXX, as a UTF-16 surrogate pair past U+FFFF.
+/// JSON only has them in strings, where the escapes read back as the same characters.
+pub fn escape_non_ascii(json: &str) -> std::borrow::Cow<'_, str> {
+    if json.is_ascii() {
+        return std::borrow::Cow::Borrowed(json);
+    }
+    let mut escaped = String::with_capacity(json.len() + json.len() / 2);
+    for c in json.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+            continue;
+        }
+        for unit in c.encode_utf16(&mut [0; 2]) {
+            escaped.push_str(&format!("\\u{:04x}", unit));
+        }
+    }
+    std::borrow::Cow::Owned(escaped)
+}
+
+/// Recursively reorder the keys of every object in `value` lexicographically.
+pub fn sort_keys(value: &mut serde_json::value::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<(String, serde_json::Value)> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, mut entry) in entries {
+                sort_keys(&mut entry);
+                map.insert(key, entry);
+            }
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(sort_keys),
+        _ => (),
+    }
+}
+
+pub fn to_yaml_string(value: &serde_json::value::Value) -> std::result::Result<String, serde_yaml::Error> {
+    serde_yaml::to_string(value)
+}
+
 pub fn to_canonical_extjson_value(
     raw_document_buf: &RawDocumentBuf,
-) -> std::result::Result<serde_json::value::Value, bson::ser::Error> {
+) -> std::result::Result<serde_json::value::Value, Error> {
     let bson_doc: bson::Bson = bson::to_bson(&raw_document_buf)?;
     Ok(bson_doc.into_canonical_extjson())
 }
 
-pub fn debug(raw_doc: &RawDocument) -> std::result::Result<String, Box<dyn std::error::Error>> {
+pub fn to_relaxed_extjson_value(
+    raw_document_buf: &RawDocumentBuf,
+) -> std::result::Result<serde_json::value::Value, Error> {
+    let bson_doc: bson::Bson = bson::to_bson(&raw_document_buf)?;
+    Ok(bson_doc.into_relaxed_extjson())
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExtJsonOptions {
+    /// Use relaxed instead of canonical extended JSON
+    pub relaxed: bool,
+    /// Sort the keys of every object lexicographically
+    pub sort_keys: bool,
+    /// Render binary UUIDs as text in this format
+    pub uuid_format: Option<binary::UuidFormat>,
+    /// The byte order of legacy (subtype 3) UUIDs
+    pub uuid_representation: binary::LegacyUuidRepresentation,
+    /// Render `$date` values in this format
+    pub date_format: Option<datetime::DateFormat>,
+    /// Render Decimal128 values in this format
+    pub decimal_format: decimal::DecimalFormat,
+    /// What to do with string values that aren't valid UTF-8
+    pub invalid_utf8: utf8::InvalidUtf8,
+    /// Write large binary values to files in this directory instead of inlining them
+    #[cfg(feature = "cli")]
+    pub binary_dir: Option<binary::BinaryDir>,
+}
+
+pub fn to_extjson_value(
+    raw_document_buf: &RawDocumentBuf,
+    options: &ExtJsonOptions,
+) -> std::result::Result<serde_json::value::Value, Error> {
+    let bson_doc = utf8::to_bson(raw_document_buf, options.invalid_utf8)?;
+    let mut value = if options.relaxed { bson_doc.into_relaxed_extjson() } else { bson_doc.into_canonical_extjson() };
+    if let Some(uuid_format) = options.uuid_format {
+        binary::render_uuids(&mut value, uuid_format, options.uuid_representation);
+    }
+    #[cfg(feature = "cli")]
+    if let Some(binary_dir) = &options.binary_dir {
+        binary::externalize_binaries(&mut value, binary_dir)?;
+    }
+    if let Some(date_format) = &options.date_format {
+        datetime::render_dates(&mut value, date_format);
+    }
+    if options.decimal_format != decimal::DecimalFormat::ExtJson {
+        decimal::render_decimals(&mut value, options.decimal_format);
+    }
+    if options.sort_keys {
+        sort_keys(&mut value);
+    }
+    Ok(value)
+}
+
+/// Append `document` to `buf` as compact extended JSON with the default options, straight from its
+/// bytes where `extjson::write_document` can, and converting it where it can't. Leaves `buf` as it
+/// was on an error.
+pub fn write_extjson(buf: &mut Vec<u8>, document: &RawDocument, relaxed: bool) -> std::result::Result<(), Error> {
+    let len = buf.len();
+    let result = match extjson::write_document(buf, document, relaxed) {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            let options = ExtJsonOptions { relaxed, ..ExtJsonOptions::default() };
+            to_extjson_value(&document.to_raw_document_buf(), &options)
+                .and_then(|value| serde_json::to_writer(&mut *buf, &value).map_err(|err| Error::Io(err.into())))
+        }
+        Err(err) => Err(err.into()),
+    };
+    if result.is_err() {
+        buf.truncate(len);
+    }
+    result
+}
+
+/// Convert the documents in `bytes`, one after another as in a .bson file, to canonical extended
+/// JSON, one per line. Converting stops at a document that can't be read or converted, with a last
+/// line saying why, e.g. `{"$error":"document #2 at offset 0xa4: ..."}`, so that every line is
+/// JSON. It needs no files or threads, so it's there in builds for the browser too.
+pub fn convert_bytes_to_extjson(bytes: &[u8]) -> String {
+    let mut json = Vec::new();
+    let mut documents = docbytes::slice_source(bytes);
+    loop {
+        let position = documents.position();
+        let result = match documents.next() {
+            None => break,
+            Some(document) => {
+                document.map_err(Error::from).and_then(|document| write_extjson(&mut json, document, false))
+            }
+        };
+        if let Err(err) = result {
+            let message = format!("{}: {}", position, err);
+            serde_json::to_writer(&mut json, &serde_json::json!({ "$error": message })).expect("writing to a Vec");
+            json.push(b'\n');
+            break;
+        }
+        json.push(b'\n');
+    }
+    String::from_utf8(json).expect("extended JSON is UTF-8")
+}
+
+pub fn to_csv_record(value: &serde_json::value::Value, fields: &[String]) -> Vec<String> {
+    fields.iter().map(|field| table::to_cell_string(path::lookup(value, field))).collect()
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DebugOptions {
+    /// Print type names such as `objectId` instead of numeric type codes
+    pub type_names: bool,
+    /// Print a truncated rendering of each element's value
+    pub show_values: bool,
+}
+
+// Longer values are truncated when rendered by `debug` with `show_values`.
+const MAX_VALUE_PREVIEW_CHARS: usize = 40;
+
+fn truncate(value: &str) -> String {
+    if value.chars().count() <= MAX_VALUE_PREVIEW_CHARS {
+        return value.to_string();
+    }
+    let truncated: String = value.chars().take(MAX_VALUE_PREVIEW_CHARS).collect();
+    format!("{}...", truncated)
+}
+
+pub(crate) fn value_preview(bson_ref: &RawBsonRef) -> Option<String> {
+    let preview = match bson_ref {
+        RawBsonRef::Double(double) => format!("{:?}", double),
+        RawBsonRef::String(string) => format!("{:?}", truncate(string)),
+        RawBsonRef::Document(_) | RawBsonRef::Array(_) => return None,
+        RawBsonRef::Boolean(boolean) => boolean.to_string(),
+        RawBsonRef::Null => "null".to_string(),
+        RawBsonRef::RegularExpression(regex) => format!("/{}/{}", truncate(regex.pattern), regex.options),
+        RawBsonRef::JavaScriptCode(code) => format!("{:?}", truncate(code)),
+        RawBsonRef::JavaScriptCodeWithScope(cws) => format!("{:?}", truncate(cws.code)),
+        RawBsonRef::Int32(int) => int.to_string(),
+        RawBsonRef::Int64(int) => int.to_string(),
+        RawBsonRef::Timestamp(timestamp) => format!("({}, {})", timestamp.time, timestamp.increment),
+        RawBsonRef::Binary(binary) => {
+            format!("subtype {:#04x}, {} bytes", u8::from(binary.subtype), binary.bytes.len())
+        }
+        RawBsonRef::ObjectId(oid) => oid.to_hex(),
+        RawBsonRef::DateTime(datetime) => datetime.to_string(),
+        RawBsonRef::Symbol(symbol) => format!("{:?}", truncate(symbol)),
+        RawBsonRef::Decimal128(dec) => dec.to_string(),
+        RawBsonRef::Undefined => "undefined".to_string(),
+        RawBsonRef::MaxKey => "MaxKey".to_string(),
+        RawBsonRef::MinKey => "MinKey".to_string(),
+        RawBsonRef::DbPointer(_) => "DBPointer".to_string(),
+    };
+    Some(preview)
+}
+
+/// Render `raw_doc` the way the debug output type does. For large documents, `debug_to_writer`
+/// saves building the whole rendering in memory.
+pub fn debug(raw_doc: &RawDocument) -> std::result::Result<String, Error> {
+    debug_with_options(raw_doc, &DebugOptions::default())
+}
+
+pub fn debug_with_options(
+    raw_doc: &RawDocument,
+    options: &DebugOptions,
+) -> std::result::Result<String, Error> {
     let mut buf: Vec<u8> = Vec::new();
-    debug_document(&mut buf, raw_doc, 0)?;
+    debug_to_writer_with_options(&mut buf, raw_doc, options)?;
     Ok(String::from_utf8_lossy(&buf).to_string())
 }
 
+/// Write `raw_doc` the way `debug` renders it to `writer`, as it goes.
+pub fn debug_to_writer<W: Write>(writer: &mut W, raw_doc: &RawDocument) -> std::result::Result<(), Error> {
+    debug_to_writer_with_options(writer, raw_doc, &DebugOptions::default())
+}
+
+/// The alias MongoDB uses for a BSON type, e.g. in `$type` queries.
+pub fn type_name(element_type: ElementType) -> &'static str {
+    match element_type {
+        ElementType::Double => "double",
+        ElementType::String => "string",
+        ElementType::EmbeddedDocument => "object",
+        ElementType::Array => "array",
+        ElementType::Binary => "binData",
+        ElementType::Undefined => "undefined",
+        ElementType::ObjectId => "objectId",
+        ElementType::Boolean => "bool",
+        ElementType::DateTime => "date",
+        ElementType::Null => "null",
+        ElementType::RegularExpression => "regex",
+        ElementType::DbPointer => "dbPointer",
+        ElementType::JavaScriptCode => "javascript",
+        ElementType::Symbol => "symbol",
+        ElementType::JavaScriptCodeWithScope => "javascriptWithScope",
+        ElementType::Int32 => "int",
+        ElementType::Timestamp => "timestamp",
+        ElementType::Int64 => "long",
+        ElementType::Decimal128 => "decimal",
+        ElementType::MaxKey => "maxKey",
+        ElementType::MinKey => "minKey",
+    }
+}
+
 fn new_object_header<W: Write, O: CountBytes + ?Sized>(
     writer: &mut W,
     object: &O,
@@ -49,29 +372,54 @@ fn new_object_header<W: Write, O: CountBytes + ?Sized>(
     Ok(())
 }
 
-fn debug_array<W: Write>(
-    writer: &mut W,
-    array: &RawArray,
-    indent_level: usize,
-) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    new_object_header(writer, array, indent_level)?;
-    for (i, element) in array.into_iter().enumerate() {
-        let name = i.to_string();
-        let bson_ref = element?;
-        debug_item(writer, &name, &bson_ref, indent_level)?;
+// The elements of a document or array still being printed.
+enum Elements<'a> {
+    Document(bson::raw::RawIter<'a>),
+    Array(std::iter::Enumerate<bson::raw::RawArrayIter<'a>>),
+}
+
+impl<'a> Elements<'a> {
+    fn next(&mut self) -> Option<std::result::Result<(String, RawBsonRef<'a>), bson::raw::Error>> {
+        match self {
+            Elements::Document(iter) => {
+                iter.next().map(|element| element.and_then(|element| Ok((element.key().to_string(), element.value()?))))
+            }
+            Elements::Array(iter) => iter.next().map(|(i, element)| element.map(|value| (i.to_string(), value))),
+        }
     }
-    Ok(())
 }
 
-fn debug_document<W: Write>(
+/// Write `raw_doc` the way `debug_with_options` renders it to `writer`, as it goes. Embedded
+/// documents and arrays are kept on a stack rather than recursed into, so that deeply nested
+/// documents can't overflow the call stack.
+pub fn debug_to_writer_with_options<W: Write>(
     writer: &mut W,
-    raw_document: &RawDocument,
-    indent_level: usize,
-) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    new_object_header(writer, raw_document, indent_level)?;
-    for element in raw_document {
-        let (name, bson_ref) = element?;
-        debug_item(writer, name, &bson_ref, indent_level)?;
+    raw_doc: &RawDocument,
+    options: &DebugOptions,
+) -> std::result::Result<(), Error> {
+    new_object_header(writer, raw_doc, 0)?;
+    let mut stack = vec![(Elements::Document(raw_doc.iter_elements()), 0)];
+    while let Some((elements, indent_level)) = stack.last_mut() {
+        let indent_level = *indent_level;
+        let (name, bson_ref) = match elements.next() {
+            None => {
+                stack.pop();
+                continue;
+            }
+            Some(element) => element?,
+        };
+        debug_item(writer, &name, &bson_ref, indent_level, options)?;
+        match bson_ref {
+            RawBsonRef::Document(embedded) => {
+                new_object_header(writer, embedded, indent_level + 3)?;
+                stack.push((Elements::Document(embedded.iter_elements()), indent_level + 3));
+            }
+            RawBsonRef::Array(embedded) => {
+                new_object_header(writer, embedded, indent_level + 3)?;
+                stack.push((Elements::Array(embedded.into_iter().enumerate()), indent_level + 3));
+            }
+            _ => (),
+        }
     }
     Ok(())
 }
@@ -81,22 +429,27 @@ fn debug_item<W: Write>(
     name: &str,
     bson_ref: &RawBsonRef,
     indent_level: usize,
-) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    options: &DebugOptions,
+) -> std::result::Result<(), Error> {
     writeln!(writer, "{indent}{name}", indent = get_indent(indent_level + 2), name = name,)?;
     let size_of_type = 1usize;
     let size_of_name = name.len() + 1; // null terminator
     let size = size_of_type + size_of_name + bson_ref.count_bytes();
-    writeln!(
+    let element_type = if options.type_names {
+        type_name(bson_ref.element_type()).to_string()
+    } else {
+        (bson_ref.element_type() as u8).to_string()
+    };
+    write!(
         writer,
         "{indent}type: {type:>4} size: {size}",
         indent = get_indent(indent_level + 3),
-        type = bson_ref.element_type() as u8,
+        type = element_type,
         size = size
     )?;
-    match bson_ref {
-        RawBsonRef::Document(embedded) => debug_document(writer, embedded, indent_level + 3)?,
-        RawBsonRef::Array(embedded) => debug_array(writer, embedded, indent_level + 3)?,
-        _ => (),
+    match value_preview(bson_ref) {
+        Some(preview) if options.show_values => writeln!(writer, " value: {}", preview)?,
+        _ => writeln!(writer)?,
     };
     Ok(())
 }
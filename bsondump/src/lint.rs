@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bson::{spec::ElementType, RawBsonRef, RawDocument};
+use serde_json::{json, Map, Value};
+
+use crate::type_name;
+
+/// A check that `Linter` can run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rule {
+    /// Keys containing `.` or `$`, which queries can't address.
+    DottedKeys,
+    /// Keys longer than `Limits::key_bytes`.
+    LongKeys,
+    /// Arrays with more than `Limits::array_length` elements.
+    LongArrays,
+    /// Documents nested more than `Limits::depth` levels deep.
+    DeepDocuments,
+    /// Fields that hold values of more than one type across documents, not counting nulls.
+    MixedTypes,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] =
+        [Rule::DottedKeys, Rule::LongKeys, Rule::LongArrays, Rule::DeepDocuments, Rule::MixedTypes];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::DottedKeys => "dottedKeys",
+            Rule::LongKeys => "longKeys",
+            Rule::LongArrays => "longArrays",
+            Rule::DeepDocuments => "deepDocuments",
+            Rule::MixedTypes => "mixedTypes",
+        }
+    }
+}
+
+/// The thresholds of the rules that have them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub key_bytes: usize,
+    pub array_length: usize,
+    pub depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { key_bytes: 128, array_length: 1000, depth: 20 }
+    }
+}
+
+/// A rule that a document, or a field across documents, breaks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub rule: Rule,
+    /// The dot-separated path of the element. For mixed types it's the field instead, with array
+    /// indices as `[]` as in --type=schema.
+    pub path: String,
+    pub message: String,
+    /// For mixed types, how many values of each type the field held.
+    pub types: Option<BTreeMap<&'static str, u64>>,
+}
+
+impl Violation {
+    /// The violation as a JSON object, with the index of the document it was found in, if any.
+    pub fn to_json(&self, document: Option<u64>) -> Value {
+        let mut object = Map::new();
+        if let Some(document) = document {
+            object.insert("document".to_string(), json!(document));
+        }
+        object.insert("rule".to_string(), json!(self.rule.name()));
+        object.insert("path".to_string(), json!(self.path));
+        object.insert("message".to_string(), json!(self.message));
+        if let Some(types) = &self.types {
+            object.insert("types".to_string(), json!(types));
+        }
+        Value::Object(object)
+    }
+}
+
+/// Checks documents against a set of rules. Most rules apply to each document on its own; mixed
+/// types are found across all of them, so they're reported by `finish`.
+pub struct Linter {
+    rules: Vec<Rule>,
+    limits: Limits,
+    // The types each field has held, in the order the fields were first seen.
+    fields: Vec<(String, BTreeMap<&'static str, u64>)>,
+    index: HashMap<String, usize>,
+}
+
+impl Linter {
+    pub fn new(rules: &[Rule], limits: Limits) -> Self {
+        Linter { rules: rules.to_vec(), limits, fields: Vec::new(), index: HashMap::new() }
+    }
+
+    fn checks(&self, rule: Rule) -> bool {
+        self.rules.contains(&rule)
+    }
+
+    /// The violations in one document, in the order their elements appear.
+    pub fn lint(&mut self, document: &RawDocument) -> Result<Vec<Violation>, bson::raw::Error> {
+        let mut violations = Vec::new();
+        let violation =
+            |rule, path: &str, message: String| Violation { rule, path: path.to_string(), message, types: None };
+        let mut too_deep = false;
+        // Each level's elements, its path and field, its depth, and whether it's an array.
+        let mut stack = vec![(document.iter_elements(), String::new(), String::new(), 1, false)];
+        while let Some((elements, path, field, depth, array)) = stack.last_mut() {
+            let element = match elements.next() {
+                None => {
+                    stack.pop();
+                    continue;
+                }
+                Some(element) => element?,
+            };
+            let key = element.key();
+            let (element_path, element_field) = match (path.as_str(), *array) {
+                ("", _) => (key.to_string(), key.to_string()),
+                (path, false) => (format!("{}.{}", path, key), format!("{}.{}", field, key)),
+                (path, true) => (format!("{}.{}", path, key), format!("{}.[]", field)),
+            };
+            let depth = *depth;
+            if !*array {
+                if self.checks(Rule::DottedKeys) && key.contains(['.', '$']) {
+                    violations.push(violation(
+                        Rule::DottedKeys,
+                        &element_path,
+                        format!("key {:?} contains . or $", key),
+                    ));
+                }
+                if self.checks(Rule::LongKeys) && key.len() > self.limits.key_bytes {
+                    let message = format!("key is {} bytes, more than {}", key.len(), self.limits.key_bytes);
+                    violations.push(violation(Rule::LongKeys, &element_path, message));
+                }
+            }
+            let element_type = element.element_type();
+            if self.checks(Rule::MixedTypes) && element_type != ElementType::Null {
+                self.add_type(element_field.clone(), type_name(element_type));
+            }
+            let (embedded, array) = match element_type {
+                ElementType::EmbeddedDocument | ElementType::Array => match element.value()? {
+                    RawBsonRef::Document(embedded) => (embedded, false),
+                    RawBsonRef::Array(embedded) => (RawDocument::from_bytes(embedded.as_bytes())?, true),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if array && self.checks(Rule::LongArrays) {
+                let length = embedded.iter_elements().count();
+                if length > self.limits.array_length {
+                    let message = format!("array has {} elements, more than {}", length, self.limits.array_length);
+                    violations.push(violation(Rule::LongArrays, &element_path, message));
+                }
+            }
+            // Only the first element that's too deep is reported.
+            if depth >= self.limits.depth && self.checks(Rule::DeepDocuments) && !too_deep {
+                too_deep = true;
+                let message = format!("nested more than {} levels deep", self.limits.depth);
+                violations.push(violation(Rule::DeepDocuments, &element_path, message));
+            }
+            stack.push((embedded.iter_elements(), element_path, element_field, depth + 1, array));
+        }
+        Ok(violations)
+    }
+
+    fn add_type(&mut self, field: String, type_name: &'static str) {
+        let index = match self.index.get(&field) {
+            Some(&index) => index,
+            None => {
+                self.index.insert(field.clone(), self.fields.len());
+                self.fields.push((field, BTreeMap::new()));
+                self.fields.len() - 1
+            }
+        };
+        *self.fields[index].1.entry(type_name).or_insert(0) += 1;
+    }
+
+    /// The fields that held more than one type, in the order they were first seen.
+    pub fn finish(self) -> Vec<Violation> {
+        self.fields
+            .into_iter()
+            .filter(|(_, types)| types.len() > 1)
+            .map(|(path, types)| {
+                let names: Vec<&str> = types.keys().copied().collect();
+                Violation {
+                    rule: Rule::MixedTypes,
+                    path,
+                    message: format!("field holds {}", names.join(", ")),
+                    types: Some(types),
+                }
+            })
+            .collect()
+    }
+}
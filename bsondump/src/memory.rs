@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How many bytes of documents a run may hold in memory at once, for --maxMemory. Whatever holds
+/// documents, e.g. a sorter or the batches being converted on other threads, reserves them here
+/// first and releases them when it lets them go, spilling them to disk or waiting instead where it
+/// can't reserve them. It's shared by those threads.
+#[derive(Debug)]
+pub struct Budget {
+    limit: u64,
+    used: AtomicU64,
+}
+
+impl Budget {
+    pub fn new(limit: u64) -> Self {
+        Budget { limit, used: AtomicU64::new(0) }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// How many bytes are reserved.
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Reserve `bytes`, unless that would take what's reserved over the limit.
+    pub fn try_reserve(&self, bytes: u64) -> bool {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|used| *used <= self.limit)
+            })
+            .is_ok()
+    }
+
+    /// Reserve `bytes` even if that takes what's reserved over the limit, for documents that are
+    /// in memory already and can't be let go until they're passed on.
+    pub fn reserve(&self, bytes: u64) {
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn release(&self, bytes: u64) {
+        let _ = self.used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| Some(used.saturating_sub(bytes)));
+    }
+}
+
+/// Reserve `bytes` of `budget`, if there is one.
+pub fn try_reserve(budget: Option<&Budget>, bytes: u64) -> bool {
+    budget.map_or(true, |budget| budget.try_reserve(bytes))
+}
+
+/// Release `bytes` of `budget`, if there is one.
+pub fn release(budget: Option<&Budget>, bytes: u64) {
+    if let Some(budget) = budget {
+        budget.release(bytes);
+    }
+}
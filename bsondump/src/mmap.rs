@@ -0,0 +1,85 @@
+use std::{fs::File, io, ops::Deref};
+
+/// A file's contents, mapped into memory where the platform supports it and read into it
+/// otherwise, so that its documents can be read with `docbytes::slice_source` without copying
+/// each one.
+pub struct Mapping {
+    #[cfg(unix)]
+    ptr: *mut libc::c_void,
+    #[cfg(unix)]
+    len: usize,
+    #[cfg(not(unix))]
+    bytes: Vec<u8>,
+}
+
+// The mapping is read-only and owned by this value.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Map all of `file`, which must be open for reading.
+    ///
+    /// # Safety
+    ///
+    /// Changes other processes make to the file while it's mapped show through the mapping, and if
+    /// they truncate it, reading past its new end kills this process with SIGBUS. The file mustn't
+    /// be changed until the mapping is dropped.
+    #[cfg(unix)]
+    pub unsafe fn open(file: &File) -> io::Result<Mapping> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the file is too large to map"))?;
+        // Empty mappings aren't allowed.
+        if len == 0 {
+            return Ok(Mapping { ptr: std::ptr::null_mut(), len });
+        }
+        let ptr = libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0);
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // Documents are read in order, so the pages ahead can be read early and dropped once read.
+        libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        Ok(Mapping { ptr, len })
+    }
+
+    /// Read all of `file`, which must be open for reading.
+    ///
+    /// # Safety
+    ///
+    /// Always safe where files can't be mapped; it's unsafe so that it can be called the same way
+    /// everywhere.
+    #[cfg(not(unix))]
+    pub unsafe fn open(file: &File) -> io::Result<Mapping> {
+        let mut bytes = Vec::new();
+        io::Read::read_to_end(&mut &*file, &mut bytes)?;
+        Ok(Mapping { bytes })
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // The mapping is valid for `len` bytes until it's dropped.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
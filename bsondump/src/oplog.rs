@@ -0,0 +1,194 @@
+use bson::{RawBsonRef, RawDocument, Timestamp};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use glob::Pattern;
+use serde_json::{json, Map, Value};
+
+fn kind(op: &str) -> &str {
+    match op {
+        "i" => "insert",
+        "u" => "update",
+        "d" => "delete",
+        "c" => "command",
+        "n" => "noop",
+        other => other,
+    }
+}
+
+// The wall-clock time of a `ts` field, which holds seconds since the epoch and an ordinal.
+fn time(ts: &Value) -> Option<String> {
+    let seconds = ts.get("$timestamp")?.get("t")?.as_i64()?;
+    let time = Utc.timestamp_opt(seconds, 0).single()?;
+    Some(time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Describe an oplog entry, given as extended JSON, by the kind of operation, the namespace, and
+/// the wall-clock time of its `ts`, followed by the entry itself. The operations of an `applyOps`
+/// command each follow it as entries of their own. Documents that aren't oplog entries are
+/// returned unchanged.
+pub fn expand(entry: Value) -> Vec<Value> {
+    let mut expanded = Vec::new();
+    let time = entry.get("ts").and_then(time);
+    expand_into(entry, time, None, &mut expanded);
+    expanded
+}
+
+fn expand_into(mut entry: Value, time: Option<String>, index: Option<usize>, expanded: &mut Vec<Value>) {
+    let (op, ns) = match (entry.get("op").and_then(Value::as_str), entry.get("ns").and_then(Value::as_str)) {
+        (Some(op), Some(ns)) => (op.to_string(), ns.to_string()),
+        _ => {
+            expanded.push(entry);
+            return;
+        }
+    };
+    let mut description = Map::new();
+    description.insert("kind".to_string(), json!(kind(&op)));
+    description.insert("ns".to_string(), json!(ns));
+    if let Some(time) = &time {
+        description.insert("time".to_string(), json!(time));
+    }
+    if let Some(index) = index {
+        description.insert("applyOpsIndex".to_string(), json!(index));
+    }
+
+    let mut operations = Vec::new();
+    if op == "c" {
+        let command = entry.get("o").and_then(Value::as_object).and_then(|o| o.keys().next().cloned());
+        if let Some(command) = command {
+            if command == "applyOps" {
+                description.insert("kind".to_string(), json!("applyOps"));
+                let o = entry.get_mut("o").and_then(Value::as_object_mut).unwrap(); // Has the command key
+                if let Some(Value::Array(ops)) = o.remove("applyOps") {
+                    description.insert("ops".to_string(), json!(ops.len()));
+                    operations = ops;
+                }
+            } else {
+                description.insert("command".to_string(), json!(command));
+            }
+        }
+    }
+    description.insert("entry".to_string(), entry);
+    expanded.push(Value::Object(description));
+    for (index, operation) in operations.into_iter().enumerate() {
+        expand_into(operation, time.clone(), Some(index), expanded);
+    }
+}
+
+/// Matches oplog entries that touch a namespace matching any of a set of patterns such as
+/// `mydb.orders` or `mydb.*`. Works on the raw entry so that other entries are never converted.
+pub struct NamespaceFilter {
+    patterns: Vec<Pattern>,
+}
+
+impl NamespaceFilter {
+    pub fn new(patterns: Vec<Pattern>) -> Self {
+        NamespaceFilter { patterns }
+    }
+
+    /// Whether the entry's namespace matches. Commands run against `db.$cmd` touch the collection
+    /// they name, e.g. `{"create": "orders"}`, and an `applyOps` command matches when any of its
+    /// operations does.
+    pub fn matches(&self, entry: &RawDocument) -> Result<bool, bson::raw::Error> {
+        let ns = match entry.get_str("ns") {
+            Ok(ns) => ns,
+            Err(_) => return Ok(false),
+        };
+        if self.matches_namespace(ns) {
+            return Ok(true);
+        }
+        if entry.get_str("op").ok() != Some("c") {
+            return Ok(false);
+        }
+        let command = match entry.get_document("o") {
+            Ok(command) => command,
+            Err(_) => return Ok(false),
+        };
+        let (name, value) = match command.into_iter().next() {
+            None => return Ok(false),
+            Some(element) => element?,
+        };
+        match value {
+            RawBsonRef::Array(operations) if name == "applyOps" => {
+                for operation in operations {
+                    if let RawBsonRef::Document(operation) = operation? {
+                        if self.matches(operation)? {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Ok(false)
+            }
+            RawBsonRef::String(collection) => {
+                let db = ns.split_once('.').map_or(ns, |(db, _)| db);
+                Ok(self.matches_namespace(&format!("{}.{}", db, collection)))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn matches_namespace(&self, ns: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(ns))
+    }
+}
+
+/// A point in the oplog, parsed from `seconds,increment`, `seconds`, or an ISO-8601 date or time
+/// (UTC unless it has an offset).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OplogTime(pub Timestamp);
+
+#[derive(Debug)]
+pub struct ParseOplogTimeError(String);
+
+impl std::fmt::Display for ParseOplogTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid oplog time: {}; expected seconds,increment or an ISO-8601 date", self.0)
+    }
+}
+
+impl std::error::Error for ParseOplogTimeError {}
+
+impl std::str::FromStr for OplogTime {
+    type Err = ParseOplogTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseOplogTimeError(s.to_string());
+        let timestamp = |seconds: i64, increment| {
+            let time = u32::try_from(seconds).map_err(|_| error())?;
+            Ok(OplogTime(Timestamp { time, increment }))
+        };
+        let trimmed = s.trim_start_matches('<').trim_end_matches('>');
+        if let Some((seconds, increment)) = trimmed.split_once([',', ':']).filter(|_| !trimmed.contains('-')) {
+            let seconds = seconds.trim().parse().map_err(|_| error())?;
+            return timestamp(seconds, increment.trim().parse().map_err(|_| error())?);
+        }
+        if let Ok(seconds) = trimmed.parse() {
+            return timestamp(seconds, 0);
+        }
+        if let Ok(time) = DateTime::parse_from_rfc3339(trimmed) {
+            return timestamp(time.timestamp(), 0);
+        }
+        if let Ok(time) = NaiveDateTime::parse_from_str(trimmed, "%Y-%m-%dT%H:%M:%S") {
+            return timestamp(time.and_utc().timestamp(), 0);
+        }
+        match NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+            Ok(date) => timestamp(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp(), 0),
+            Err(_) => Err(error()),
+        }
+    }
+}
+
+/// Matches oplog entries whose `ts` is at or after `start` and before `end`.
+pub struct TimeRange {
+    pub start: Option<OplogTime>,
+    pub end: Option<OplogTime>,
+}
+
+impl TimeRange {
+    pub fn matches(&self, entry: &RawDocument) -> bool {
+        let ts = match entry.get_timestamp("ts") {
+            Ok(ts) => (ts.time, ts.increment),
+            Err(_) => return false,
+        };
+        let key = |time: OplogTime| (time.0.time, time.0.increment);
+        self.start.map_or(true, |start| ts >= key(start)) && self.end.map_or(true, |end| ts < key(end))
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        mpsc::{channel, sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::Scope,
+};
+
+/// Items queued for a pool of threads.
+pub struct Jobs<T> {
+    queue: SyncSender<(u64, T)>,
+    sent: u64,
+}
+
+impl<T> Jobs<T> {
+    /// Queue an item, waiting while the queue is full. Returns false once the results are no
+    /// longer being received, so that there's no point sending more.
+    pub fn send(&mut self, item: T) -> bool {
+        if self.queue.send((self.sent, item)).is_err() {
+            return false;
+        }
+        self.sent += 1;
+        true
+    }
+}
+
+/// The results of the items queued for a pool of threads, in the order the items were queued,
+/// however long each one takes. They end once the `Jobs` are dropped and every result has been
+/// received.
+pub struct Results<U> {
+    results: Receiver<(u64, U)>,
+    // Results that came back ahead of an earlier one.
+    reorder: BTreeMap<u64, U>,
+    received: u64,
+}
+
+impl<U> Iterator for Results<U> {
+    type Item = U;
+
+    fn next(&mut self) -> Option<U> {
+        while !self.reorder.contains_key(&self.received) {
+            let (index, result) = self.results.recv().ok()?;
+            self.reorder.insert(index, result);
+        }
+        let result = self.reorder.remove(&self.received);
+        self.received += 1;
+        result
+    }
+}
+
+/// Start `threads` threads, at least one, in `scope` that apply `f` to each item queued with the `Jobs`, handing
+/// back what it returns through the `Results`. The threads end once the `Jobs` are dropped, or
+/// the `Results` are.
+pub fn pool<'scope, 'env, T, U, F>(scope: &'scope Scope<'scope, 'env>, threads: usize, f: F) -> (Jobs<T>, Results<U>)
+where
+    T: Send + 'scope,
+    U: Send + 'scope,
+    F: Fn(T) -> U + Send + Sync + 'scope,
+{
+    assert!(threads > 0, "a pool needs at least one thread");
+    let (queue, jobs) = sync_channel::<(u64, T)>(threads);
+    let (done, results) = channel();
+    let jobs = Arc::new(Mutex::new(jobs));
+    let f = Arc::new(f);
+    for _ in 0..threads {
+        let (jobs, done, f) = (jobs.clone(), done.clone(), f.clone());
+        scope.spawn(move || loop {
+            // The lock is released as soon as a job is taken.
+            let job = jobs.lock().unwrap().recv();
+            let (index, item) = match job {
+                Err(_) => return,
+                Ok(job) => job,
+            };
+            if done.send((index, f(item))).is_err() {
+                return;
+            }
+        });
+    }
+    (Jobs { queue, sent: 0 }, Results { results, reorder: BTreeMap::new(), received: 0 })
+}
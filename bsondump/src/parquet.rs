@@ -0,0 +1,121 @@
+use std::{io::Write, sync::Arc};
+
+use ::parquet::{
+    arrow::{parquet_to_arrow_schema, ArrowWriter},
+    errors::ParquetError,
+    file::properties::WriterProperties,
+    schema::{parser::parse_message_type, types::SchemaDescriptor},
+};
+use arrow_json::reader::{infer_json_schema_from_iterator, ReaderBuilder};
+use arrow_schema::SchemaRef;
+use serde_json::{Map, Value};
+
+/// Number of documents buffered into each Parquet row group.
+pub const ROW_GROUP_SIZE: usize = 10_000;
+
+/// Flatten a relaxed extended JSON document into a single level of dot-separated columns.
+/// Embedded documents are flattened, arrays become lists (with any documents inside them kept as
+/// structs), and extended JSON wrappers such as {"$oid": ...} become strings.
+pub fn flatten(value: &Value) -> Value {
+    let mut columns = Map::new();
+    if let Value::Object(map) = value {
+        flatten_into(&mut columns, "", map);
+    }
+    Value::Object(columns)
+}
+
+fn flatten_into(columns: &mut Map<String, Value>, prefix: &str, map: &Map<String, Value>) {
+    for (key, value) in map {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::Object(embedded) if !is_wrapper(embedded) => flatten_into(columns, &path, embedded),
+            _ => {
+                columns.insert(path, to_column_value(value));
+            }
+        }
+    }
+}
+
+fn is_wrapper(map: &Map<String, Value>) -> bool {
+    !map.is_empty() && map.keys().all(|key| key.starts_with('$'))
+}
+
+fn to_column_value(value: &Value) -> Value {
+    match value {
+        Value::Object(map) if is_wrapper(map) => match map.values().next() {
+            Some(Value::String(string)) if map.len() == 1 => Value::String(string.clone()),
+            _ => Value::String(value.to_string()),
+        },
+        Value::Object(map) => {
+            Value::Object(map.iter().map(|(key, value)| (key.clone(), to_column_value(value))).collect())
+        }
+        Value::Array(values) => Value::Array(values.iter().map(to_column_value).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// Parse a Parquet message type, e.g. `message dump { optional binary name (STRING); }`, into the
+/// schema to write. Column names are the dot-separated paths produced by [`flatten`].
+pub fn parse_schema(message_type: &str) -> Result<SchemaRef, ParquetError> {
+    let descriptor = SchemaDescriptor::new(Arc::new(parse_message_type(message_type)?));
+    Ok(Arc::new(parquet_to_arrow_schema(&descriptor, None)?))
+}
+
+/// Writes flattened documents to a Parquet file, one row group per [`ROW_GROUP_SIZE`] documents.
+/// Unless a schema is given up front, it is inferred from the first row group; fields that only
+/// appear later are dropped.
+pub struct ParquetWriter<W: Write + Send> {
+    sink: Option<W>,
+    writer: Option<ArrowWriter<W>>,
+    schema: Option<SchemaRef>,
+    rows: Vec<Value>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(sink: W, schema: Option<SchemaRef>) -> Self {
+        ParquetWriter { sink: Some(sink), writer: None, schema, rows: Vec::new() }
+    }
+
+    pub fn add_document(&mut self, value: &Value) -> Result<(), ParquetError> {
+        self.rows.push(flatten(value));
+        if self.rows.len() >= ROW_GROUP_SIZE {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn write_row_group(&mut self) -> Result<(), ParquetError> {
+        if self.rows.is_empty() && self.writer.is_some() {
+            return Ok(());
+        }
+        let schema = match &self.schema {
+            Some(schema) => schema.clone(),
+            None => {
+                let schema = Arc::new(infer_json_schema_from_iterator(self.rows.iter().map(Ok))?);
+                self.schema = Some(schema.clone());
+                schema
+            }
+        };
+        if self.writer.is_none() {
+            let sink = self.sink.take().expect("sink is only taken once");
+            let properties = WriterProperties::builder().set_max_row_group_size(ROW_GROUP_SIZE).build();
+            self.writer = Some(ArrowWriter::try_new(sink, schema.clone(), Some(properties))?);
+        }
+        let writer = self.writer.as_mut().unwrap(); // created above
+
+        let mut decoder = ReaderBuilder::new(schema).with_coerce_primitive(true).build_decoder()?;
+        decoder.serialize(&self.rows)?;
+        if let Some(batch) = decoder.flush()? {
+            writer.write(&batch)?;
+            writer.flush()?;
+        }
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Write any buffered documents and the file footer, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W, ParquetError> {
+        self.write_row_group()?;
+        self.writer.take().unwrap().into_inner() // created by write_row_group
+    }
+}
@@ -0,0 +1,11 @@
+use serde_json::Value;
+
+/// Look up the value at a dot-separated path such as `a.b.c`. Array elements are addressed by
+/// their index, e.g. `tags.0`.
+pub fn lookup<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.').try_fold(value, |current, key| match current {
+        Value::Object(map) => map.get(key),
+        Value::Array(array) => key.parse::<usize>().ok().and_then(|index| array.get(index)),
+        _ => None,
+    })
+}
@@ -0,0 +1,85 @@
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+/// How often the progress line is redrawn. A run that's done sooner never shows it.
+pub const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A line on stderr showing how far through its input a run is: the bytes read of the total, how
+/// many documents a second are being read, and about how long is left. It's cleared when dropped.
+pub struct Progress {
+    total: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(total: u64) -> Self {
+        Progress { total, started: Instant::now(), drawn: None }
+    }
+
+    /// Redraw the line, if it's been long enough since it was last drawn, for `read` bytes and
+    /// `documents` documents read so far.
+    pub fn update(&mut self, read: u64, documents: u64) {
+        let now = Instant::now();
+        if now - self.drawn.unwrap_or(self.started) < REDRAW_INTERVAL {
+            return;
+        }
+        self.drawn = Some(now);
+        let mut stderr = std::io::stderr().lock();
+        // Clear whatever is left of a longer line drawn before.
+        let _ = write!(stderr, "\r{}\x1b[K", line(read, self.total, documents, now - self.started));
+        let _ = stderr.flush();
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.drawn.is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// The progress line for `read` bytes of `total`, and `documents` documents, read in `elapsed`,
+/// e.g. "512.0MB/1.0GB (50%), 10000 docs/s, 10s left".
+pub fn line(read: u64, total: u64, documents: u64, elapsed: Duration) -> String {
+    let read = read.min(total);
+    let percent = (read * 100).checked_div(total).unwrap_or(100);
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 { (documents as f64 / seconds) as u64 } else { 0 };
+    let mut line = format!("{}/{} ({}%), {} docs/s", format_size(read), format_size(total), percent, rate);
+    if read > 0 && seconds > 0.0 {
+        let left = (total - read) as f64 * seconds / read as f64;
+        line.push_str(&format!(", {} left", format_duration(left.round() as u64)));
+    }
+    line
+}
+
+// In the units --maxBsonSize and --splitSize take, which are powers of 1024.
+pub(crate) fn format_size(bytes: u64) -> String {
+    let units = ["KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in units {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    if unit == "B" {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", size, unit)
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use bson::{RawArrayBuf, RawBsonRef, RawDocument, RawDocumentBuf};
+
+// Dot-separated paths split into a tree, e.g. `a.b` and `a.c` share the node for `a`. A leaf
+// covers everything below it, so `a` and `a.b` together are the same as `a`.
+#[derive(Debug, Default)]
+struct PathTree {
+    leaf: bool,
+    children: BTreeMap<String, PathTree>,
+}
+
+impl PathTree {
+    fn new(paths: &[String]) -> Self {
+        let mut tree = PathTree::default();
+        for path in paths {
+            let mut node = &mut tree;
+            for key in path.split('.') {
+                if node.leaf {
+                    break;
+                }
+                node = node.children.entry(key.to_string()).or_default();
+            }
+            node.leaf = true;
+            node.children.clear();
+        }
+        tree
+    }
+}
+
+/// Which fields to keep or strip from each document before it's output. Like a MongoDB
+/// projection, paths into arrays apply to every embedded document in the array, and `_id` is
+/// kept by an inclusion unless it is also excluded.
+#[derive(Debug, Default)]
+pub struct Projection {
+    include: Option<PathTree>,
+    exclude: PathTree,
+}
+
+impl Projection {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        let include = match include {
+            [] => None,
+            paths => Some(PathTree::new(&[paths, &["_id".to_string()]].concat())),
+        };
+        Projection { include, exclude: PathTree::new(exclude) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.children.is_empty()
+    }
+
+    /// Build the projected copy of `document` without converting it out of its raw form.
+    pub fn apply(&self, document: &RawDocument) -> Result<RawDocumentBuf, bson::raw::Error> {
+        let included = match &self.include {
+            None => document.to_raw_document_buf(),
+            Some(tree) => project_document(document, tree, true)?,
+        };
+        project_document(&included, &self.exclude, false)
+    }
+}
+
+fn project_document(
+    document: &RawDocument,
+    tree: &PathTree,
+    include: bool,
+) -> Result<RawDocumentBuf, bson::raw::Error> {
+    let mut projected = RawDocumentBuf::new();
+    for element in document {
+        let (key, value) = element?;
+        match tree.children.get(key) {
+            None if include => (),
+            None => projected.append_ref(key, value),
+            Some(node) if node.leaf => {
+                if include {
+                    projected.append_ref(key, value);
+                }
+            }
+            Some(node) => match value {
+                RawBsonRef::Document(embedded) => {
+                    let embedded = project_document(embedded, node, include)?;
+                    if !include || !embedded.is_empty() {
+                        projected.append(key, embedded);
+                    }
+                }
+                RawBsonRef::Array(array) => {
+                    let mut projected_array = RawArrayBuf::new();
+                    for element in array {
+                        match element? {
+                            RawBsonRef::Document(embedded) => {
+                                projected_array.push(project_document(embedded, node, include)?)
+                            }
+                            // Only embedded documents can contain the rest of an included path.
+                            _ if include => (),
+                            other => projected_array.push(other.to_raw_bson()),
+                        }
+                    }
+                    projected.append(key, projected_array);
+                }
+                _ if include => (),
+                other => projected.append_ref(key, other),
+            },
+        }
+    }
+    Ok(projected)
+}
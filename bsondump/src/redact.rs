@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+
+use bson::{RawArrayBuf, RawBson, RawBsonRef, RawDocument, RawDocumentBuf};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// How a redacted field's value is replaced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Replace the value with null.
+    Null,
+    /// Replace the value with the hex HMAC-SHA-256 of its type and bytes, so that equal values
+    /// still match across documents.
+    Hash,
+    /// Replace each character of a string with `*`. Other values are nulled.
+    Mask,
+    /// Replace a number with a random one of the same type, sign and number of digits. Other
+    /// values are nulled.
+    Randomize,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = ParseRulesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(Strategy::Null),
+            "hash" => Ok(Strategy::Hash),
+            "mask" => Ok(Strategy::Mask),
+            "randomize" => Ok(Strategy::Randomize),
+            other => Err(ParseRulesError(format!("unknown strategy {:?}", other))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseRulesError(String);
+
+impl std::fmt::Display for ParseRulesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid redaction rules: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseRulesError {}
+
+/// The fields to redact and how, one per line: a dot-separated path, then optionally a strategy,
+/// which is `null` if it's left out. Blank lines and lines starting with `#` are ignored, e.g.
+///
+/// ```text
+/// # Contact details
+/// email hash
+/// phones mask
+/// address
+/// ```
+///
+/// As with --fields, paths into arrays apply to every embedded document in the array. Masking or
+/// randomizing an array does each of its elements; nulling or hashing it does the whole array.
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    rules: Vec<(String, Strategy)>,
+}
+
+impl Rules {
+    pub fn uses(&self, strategy: Strategy) -> bool {
+        self.rules.iter().any(|(_, rule)| *rule == strategy)
+    }
+}
+
+impl std::str::FromStr for Rules {
+    type Err = ParseRulesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let path = words.next().unwrap();
+            let strategy = match words.next() {
+                None => Strategy::Null,
+                Some(strategy) => strategy
+                    .parse()
+                    .map_err(|ParseRulesError(reason)| ParseRulesError(format!("{} on line {}", reason, number + 1)))?,
+            };
+            if words.next().is_some() {
+                return Err(ParseRulesError(format!("line {} has more than a path and a strategy", number + 1)));
+            }
+            rules.push((path.to_string(), strategy));
+        }
+        Ok(Rules { rules })
+    }
+}
+
+// Dot-separated paths split into a tree, with the strategy for the ones that end at each node.
+#[derive(Debug, Default)]
+struct Node {
+    strategy: Option<Strategy>,
+    children: BTreeMap<String, Node>,
+}
+
+/// Rewrites documents in their raw form to redact fields, e.g. to share a dump without the
+/// personal information in it.
+pub struct Redactor {
+    tree: Node,
+    key: Vec<u8>,
+    rng: StdRng,
+}
+
+impl Redactor {
+    /// A redactor that hashes with `key` and randomizes with `rng`. A field and a field below it
+    /// are redacted with the field's strategy.
+    pub fn new(rules: &Rules, key: &[u8], rng: StdRng) -> Self {
+        let mut tree = Node::default();
+        for (path, strategy) in &rules.rules {
+            let mut node = &mut tree;
+            for key in path.split('.') {
+                if node.strategy.is_some() {
+                    break;
+                }
+                node = node.children.entry(key.to_string()).or_default();
+            }
+            if node.strategy.is_none() {
+                node.strategy = Some(*strategy);
+                node.children.clear();
+            }
+        }
+        Redactor { tree, key: key.to_vec(), rng }
+    }
+
+    /// A redactor whose random numbers come from `seed`, or from entropy without one.
+    pub fn with_seed(rules: &Rules, key: &[u8], seed: Option<u64>) -> Self {
+        let rng = match seed {
+            None => StdRng::from_entropy(),
+            Some(seed) => StdRng::seed_from_u64(seed),
+        };
+        Redactor::new(rules, key, rng)
+    }
+
+    /// Build the redacted copy of `document`.
+    pub fn apply(&mut self, document: &RawDocument) -> Result<RawDocumentBuf, bson::raw::Error> {
+        let tree = std::mem::take(&mut self.tree);
+        let redacted = self.redact_document(document, &tree);
+        self.tree = tree;
+        redacted
+    }
+
+    fn redact_document(&mut self, document: &RawDocument, tree: &Node) -> Result<RawDocumentBuf, bson::raw::Error> {
+        let mut redacted = RawDocumentBuf::new();
+        for element in document {
+            let (key, value) = element?;
+            let node = match tree.children.get(key) {
+                None => {
+                    redacted.append_ref(key, value);
+                    continue;
+                }
+                Some(node) => node,
+            };
+            match (node.strategy, value) {
+                (Some(strategy), value) => redacted.append(key, self.redact_value(value, strategy)?),
+                (None, RawBsonRef::Document(embedded)) => redacted.append(key, self.redact_document(embedded, node)?),
+                (None, RawBsonRef::Array(array)) => {
+                    let mut redacted_array = RawArrayBuf::new();
+                    for element in array {
+                        match element? {
+                            RawBsonRef::Document(embedded) => {
+                                redacted_array.push(self.redact_document(embedded, node)?)
+                            }
+                            other => redacted_array.push(other.to_raw_bson()),
+                        }
+                    }
+                    redacted.append(key, redacted_array);
+                }
+                (None, other) => redacted.append_ref(key, other),
+            }
+        }
+        Ok(redacted)
+    }
+
+    fn redact_value(&mut self, value: RawBsonRef, strategy: Strategy) -> Result<RawBson, bson::raw::Error> {
+        let redacted = match (strategy, value) {
+            (Strategy::Null, _) => RawBson::Null,
+            (Strategy::Hash, value) => {
+                let mut element = RawDocumentBuf::new();
+                element.append_ref("", value);
+                let digest = crate::checksum::hmac_sha256(&self.key, element.as_bytes());
+                RawBson::String(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+            }
+            (_, RawBsonRef::Array(array)) => {
+                let mut redacted = RawArrayBuf::new();
+                for element in array {
+                    redacted.push(self.redact_value(element?, strategy)?);
+                }
+                RawBson::Array(redacted)
+            }
+            (Strategy::Mask, RawBsonRef::String(string)) => RawBson::String("*".repeat(string.chars().count())),
+            (Strategy::Randomize, RawBsonRef::Int32(int)) => {
+                RawBson::Int32(random_like(&mut self.rng, int as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+            }
+            (Strategy::Randomize, RawBsonRef::Int64(int)) => RawBson::Int64(random_like(&mut self.rng, int)),
+            (Strategy::Randomize, RawBsonRef::Double(double)) if double.is_finite() => {
+                RawBson::Double(double * self.rng.gen_range(0.5..1.5))
+            }
+            _ => RawBson::Null,
+        };
+        Ok(redacted)
+    }
+}
+
+// A random integer with the same sign and number of digits as `int`.
+fn random_like(rng: &mut StdRng, int: i64) -> i64 {
+    let magnitude = int.unsigned_abs();
+    let digits = magnitude.checked_ilog10().unwrap_or(0);
+    let low = if digits == 0 { 0 } else { 10u64.pow(digits) };
+    let high = 10u64.checked_pow(digits + 1).map_or(u64::MAX, |high| high - 1);
+    let random = rng.gen_range(low..=high.min(i64::MAX as u64)) as i64;
+    if int < 0 {
+        -random
+    } else {
+        random
+    }
+}
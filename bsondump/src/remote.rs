@@ -0,0 +1,417 @@
+use std::{
+    env,
+    io::{self, Read, Seek, SeekFrom, Write},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use reqwest::{blocking::Client, Method, StatusCode};
+
+use crate::checksum::{hmac_sha256, sha256};
+
+// Objects are downloaded, and uploaded, in parts of this size.
+const PART_SIZE: usize = 8 << 20;
+// An upload can have at most 10,000 parts, so each 1,000 are twice the size of the ones before.
+const PARTS_PER_SIZE: usize = 1000;
+const MAX_RETRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Whether a path is an S3 object, `s3://bucket/key`.
+pub fn is_s3(path: &str) -> bool {
+    path.starts_with("s3://")
+}
+
+/// AWS credentials.
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Only temporary credentials have one.
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// The credentials in AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN.
+    pub fn from_env() -> io::Result<Credentials> {
+        match (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY")) {
+            (Ok(access_key_id), Ok(secret_access_key)) => Ok(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            }),
+            _ => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "no AWS credentials; set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY",
+            )),
+        }
+    }
+}
+
+/// Signs S3 requests with AWS Signature Version 4.
+pub struct Signer {
+    credentials: Credentials,
+    region: String,
+}
+
+impl Signer {
+    pub fn new(credentials: Credentials, region: String) -> Signer {
+        Signer { credentials, region }
+    }
+
+    /// The Authorization header of a request made at `time`. `path` is URI-encoded already, the
+    /// query isn't, and `headers` are the ones signed, named in lower case, which must include host,
+    /// x-amz-content-sha256 and x-amz-date.
+    pub fn authorization(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        time: DateTime<Utc>,
+    ) -> String {
+        let mut headers = headers.to_vec();
+        headers.sort_unstable();
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers: String =
+            headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let payload_hash = headers.iter().find(|(name, _)| *name == "x-amz-content-sha256");
+        let payload_hash = payload_hash.map_or("", |(_, hash)| hash);
+        let query = canonical_query(query);
+        let canonical_request = [method, path, &query, &canonical_headers, &signed_headers, payload_hash].join("\n");
+
+        let date = time.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            time.format("%Y%m%dT%H%M%SZ"),
+            scope,
+            hex(&sha256(canonical_request.as_bytes()))
+        );
+        let key = format!("AWS4{}", self.credentials.secret_access_key);
+        let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, b"s3");
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{},SignedHeaders={},Signature={}",
+            self.credentials.access_key_id, scope, signed_headers, signature
+        )
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Percent-encode all but the characters URIs leave unreserved, and, in a path, slashes.
+fn uri_encode(s: &str, path: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            b'/' if path => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<String> =
+        query.iter().map(|(name, value)| format!("{}={}", uri_encode(name, false), uri_encode(value, false))).collect();
+    pairs.sort_unstable();
+    pairs.join("&")
+}
+
+// The text of the first `name` element of an XML response.
+fn element(xml: &[u8], name: &str) -> Option<String> {
+    let xml = String::from_utf8_lossy(xml);
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    let text = xml[start..end].replace("&quot;", "\"").replace("&apos;", "'").replace("&lt;", "<").replace("&gt;", ">");
+    Some(text.replace("&amp;", "&"))
+}
+
+// A response, read whole: none is larger than a part.
+struct Reply {
+    status: StatusCode,
+    headers: reqwest::header::HeaderMap,
+    body: Vec<u8>,
+}
+
+// A request that failed, and whether trying it again might succeed.
+struct Failed {
+    err: io::Error,
+    transient: bool,
+}
+
+// An S3 object, and how to reach it: at AWS_ENDPOINT_URL_S3 or AWS_ENDPOINT_URL, with the bucket
+// in the path, when either is set, e.g. for an S3-compatible store, and otherwise in AWS_REGION or
+// AWS_DEFAULT_REGION, or us-east-1.
+struct Object {
+    name: String,
+    client: Client,
+    signer: Signer,
+    // Where requests are sent without their query, and the host and URI-encoded path in it.
+    url: String,
+    host: String,
+    path: String,
+}
+
+impl Object {
+    fn new(name: &str) -> io::Result<Object> {
+        let (bucket, key) = match name.strip_prefix("s3://").and_then(|rest| rest.split_once('/')) {
+            Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => (bucket, uri_encode(key, true)),
+            _ => {
+                let message = format!("invalid S3 object {}; expected s3://bucket/key", name);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        };
+        let region = env::var("AWS_REGION").or_else(|_| env::var("AWS_DEFAULT_REGION"));
+        let region = region.unwrap_or_else(|_| "us-east-1".to_string());
+        let (origin, path) = match env::var("AWS_ENDPOINT_URL_S3").or_else(|_| env::var("AWS_ENDPOINT_URL")) {
+            Ok(endpoint) => (endpoint.trim_end_matches('/').to_string(), format!("/{}/{}", bucket, key)),
+            // A dotted bucket name doesn't match the wildcard certificate of its virtual host.
+            Err(_) if bucket.contains('.') => {
+                (format!("https://s3.{}.amazonaws.com", region), format!("/{}/{}", bucket, key))
+            }
+            Err(_) => (format!("https://{}.s3.{}.amazonaws.com", bucket, region), format!("/{}", key)),
+        };
+        let host = origin.split_once("://").map_or(origin.as_str(), |(_, host)| host).to_string();
+        let client = Client::builder().timeout(TIMEOUT).build().map_err(io::Error::other)?;
+        let signer = Signer::new(Credentials::from_env()?, region);
+        Ok(Object { name: name.to_string(), client, signer, url: format!("{}{}", origin, path), host, path })
+    }
+
+    // Send a signed request, trying it again when it fails in a way that might not the next time.
+    fn send(&self, method: Method, query: &[(&str, &str)], headers: &[(&str, &str)], body: &[u8]) -> io::Result<Reply> {
+        let mut attempt = 1;
+        loop {
+            match self.try_send(method.clone(), query, headers, body) {
+                Ok(reply) => return Ok(reply),
+                Err(Failed { err, transient: true }) if attempt < MAX_RETRIES => {
+                    warn!("{} {} failed; trying again. {}", method, self.name, err);
+                    thread::sleep(RETRY_DELAY * attempt);
+                    attempt += 1;
+                }
+                Err(Failed { err, .. }) => return Err(err),
+            }
+        }
+    }
+
+    fn try_send(
+        &self,
+        method: Method,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Reply, Failed> {
+        let time = Utc::now();
+        let date = time.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex(&sha256(body));
+        let mut signed =
+            vec![("host", self.host.as_str()), ("x-amz-content-sha256", &payload_hash), ("x-amz-date", &date)];
+        if let Some(token) = &self.signer.credentials.session_token {
+            signed.push(("x-amz-security-token", token));
+        }
+        signed.extend_from_slice(headers);
+        let authorization = self.signer.authorization(method.as_str(), &self.path, query, &signed, time);
+        let url = match query.is_empty() {
+            true => self.url.clone(),
+            false => format!("{}?{}", self.url, canonical_query(query)),
+        };
+        let mut request = self.client.request(method.clone(), url).header("authorization", authorization);
+        for (name, value) in signed {
+            request = request.header(name, value);
+        }
+        let transient = |err: reqwest::Error| Failed { err: io::Error::other(err), transient: true };
+        let response = request.body(body.to_vec()).send().map_err(transient)?;
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = response.bytes().map_err(transient)?.to_vec();
+        if status.is_success() {
+            return Ok(Reply { status, headers, body });
+        }
+        let mut message = format!("{} {} returned {}", method, self.name, status);
+        if let (Some(code), Some(reason)) = (element(&body, "Code"), element(&body, "Message")) {
+            message = format!("{}: {}: {}", message, code, reason);
+        }
+        let kind = match status {
+            StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+            StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+            _ => io::ErrorKind::Other,
+        };
+        let transient = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+        Err(Failed { err: io::Error::new(kind, message), transient })
+    }
+}
+
+/// Reads an S3 object, `s3://bucket/key`, a part at a time with ranged GETs, so that a failure
+/// partway through a large object only downloads that part again, and seeking needn't download
+/// what it skips.
+pub struct S3Reader {
+    object: Object,
+    size: u64,
+    position: u64,
+    // The part last downloaded, and where in the object it starts.
+    part: Vec<u8>,
+    part_start: u64,
+}
+
+impl S3Reader {
+    pub fn open(url: &str) -> io::Result<S3Reader> {
+        let object = Object::new(url)?;
+        let reply = object.send(Method::HEAD, &[], &[], &[])?;
+        let size = reply.headers.get("content-length").and_then(|length| length.to_str().ok()?.parse().ok());
+        let size = size.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length for the object"))?;
+        Ok(S3Reader { object, size, position: 0, part: Vec::new(), part_start: 0 })
+    }
+
+    fn download_part(&mut self) -> io::Result<()> {
+        let end = self.size.min(self.position + PART_SIZE as u64);
+        let range = format!("bytes={}-{}", self.position, end - 1);
+        let reply = self.object.send(Method::GET, &[], &[("range", &range)], &[])?;
+        self.part = reply.body;
+        // A server that ignores the range sends the whole object.
+        if reply.status == StatusCode::OK {
+            self.part.truncate(end as usize);
+            self.part.drain(..(self.position as usize).min(self.part.len()));
+        }
+        self.part_start = self.position;
+        Ok(())
+    }
+}
+
+impl Read for S3Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.size {
+            return Ok(0);
+        }
+        let part_end = self.part_start + self.part.len() as u64;
+        if self.position < self.part_start || self.position >= part_end {
+            self.download_part()?;
+            // The object is shorter than it was said to be, which mustn't look like its end.
+            if self.part.is_empty() {
+                let message = format!("{} ended at byte {} of {}", self.object.name, self.position, self.size);
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message));
+            }
+        }
+        let start = (self.position - self.part_start) as usize;
+        let count = buf.len().min(self.part.len() - start);
+        buf[..count].copy_from_slice(&self.part[start..start + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for S3Reader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position")),
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+        }
+    }
+}
+
+/// Writes an S3 object, `s3://bucket/key`, uploading it a part at a time once it's larger than
+/// one, so that it's never held in memory whole. The object is only there once `finish` has
+/// completed the upload; dropped unfinished, the parts uploaded are discarded.
+pub struct S3Writer {
+    object: Object,
+    buffer: Vec<u8>,
+    // Set once the first part is uploaded, with the ETag of each part uploaded.
+    upload_id: Option<String>,
+    etags: Vec<String>,
+}
+
+impl S3Writer {
+    pub fn create(url: &str) -> io::Result<S3Writer> {
+        Ok(S3Writer { object: Object::new(url)?, buffer: Vec::new(), upload_id: None, etags: Vec::new() })
+    }
+
+    fn part_size(&self) -> usize {
+        PART_SIZE << (self.etags.len() / PARTS_PER_SIZE)
+    }
+
+    fn upload_part(&mut self) -> io::Result<()> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let reply = self.object.send(Method::POST, &[("uploads", "")], &[], &[])?;
+                let upload_id = element(&reply.body, "UploadId")
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no UploadId for the upload"))?;
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+        let part_number = (self.etags.len() + 1).to_string();
+        let query = [("partNumber", part_number.as_str()), ("uploadId", &upload_id)];
+        let reply = self.object.send(Method::PUT, &query, &[], &self.buffer)?;
+        let etag = reply.headers.get("etag").and_then(|etag| etag.to_str().ok());
+        let etag = etag.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no ETag for the part"))?;
+        self.etags.push(etag.to_string());
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Write what's left, and complete the upload.
+    pub fn finish(mut self) -> io::Result<()> {
+        let upload_id = match self.upload_id.clone() {
+            // Small enough for one part, so it's put whole.
+            None => return self.object.send(Method::PUT, &[], &[], &self.buffer).map(drop),
+            Some(upload_id) => upload_id,
+        };
+        if !self.buffer.is_empty() {
+            self.upload_part()?;
+        }
+        let mut parts = String::from("<CompleteMultipartUpload>");
+        for (index, etag) in self.etags.iter().enumerate() {
+            parts.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", index + 1, etag));
+        }
+        parts.push_str("</CompleteMultipartUpload>");
+        let reply = self.object.send(Method::POST, &[("uploadId", &upload_id)], &[], parts.as_bytes())?;
+        // Completing can fail after the response has started, with an error in its body.
+        if let Some(code) = element(&reply.body, "Code") {
+            let reason = element(&reply.body, "Message").unwrap_or_default();
+            return Err(io::Error::other(format!("Failed to complete {}: {}: {}", self.object.name, code, reason)));
+        }
+        self.upload_id = None;
+        Ok(())
+    }
+}
+
+impl Write for S3Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() >= self.part_size() {
+            self.upload_part()?;
+        }
+        let count = buf.len().min(self.part_size() - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+        Ok(count)
+    }
+
+    // Parts are uploaded as they fill, and the object written by finish.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for S3Writer {
+    fn drop(&mut self) {
+        if let Some(upload_id) = self.upload_id.take() {
+            if let Err(err) = self.object.send(Method::DELETE, &[("uploadId", &upload_id)], &[], &[]) {
+                warn!("Failed to abort the upload of {}, so its parts may still be stored. {}", self.object.name, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use rand::Rng;
+
+/// Keeps a uniformly random sample of up to `capacity` items from a stream of unknown length
+/// (reservoir sampling), so that sampling a multi-GB dump only holds the sample in memory.
+pub struct Reservoir<T, R: Rng> {
+    capacity: usize,
+    seen: u64,
+    items: Vec<(u64, T)>,
+    rng: R,
+}
+
+impl<T, R: Rng> Reservoir<T, R> {
+    pub fn new(capacity: usize, rng: R) -> Self {
+        Reservoir { capacity, seen: 0, items: Vec::with_capacity(capacity), rng }
+    }
+
+    /// Add an item, returning the one that's left out of the sample for it, if any: the item it
+    /// replaced, or the item itself.
+    pub fn add(&mut self, item: T) -> Option<T> {
+        let index = self.seen;
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push((index, item));
+            return None;
+        }
+        let slot = self.rng.gen_range(0..self.seen);
+        if slot < self.capacity as u64 {
+            return Some(std::mem::replace(&mut self.items[slot as usize], (index, item)).1);
+        }
+        Some(item)
+    }
+
+    /// The sampled items, in the order they were added.
+    pub fn into_items(mut self) -> Vec<T> {
+        self.items.sort_by_key(|(index, _)| *index);
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
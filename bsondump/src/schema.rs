@@ -0,0 +1,159 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use bson::{RawArray, RawBsonRef, RawDocument};
+use serde_json::json;
+
+use crate::{table, type_name, value_preview};
+
+// Array elements are aggregated under a single path component, e.g. `tags.[]`.
+const ARRAY_ELEMENT: &str = "[]";
+
+/// What was observed about a single field path across a stream of documents.
+#[derive(Debug)]
+pub struct FieldSchema {
+    pub path: String,
+    /// The number of values seen for each type, keyed by type name
+    pub types: BTreeMap<&'static str, u64>,
+    /// The number of documents containing the path
+    pub occurrences: u64,
+    /// The number of documents containing a null value at the path
+    pub nulls: u64,
+    /// A rendering of the first value seen
+    pub example: Option<String>,
+    last_document: u64,
+    last_null_document: u64,
+}
+
+/// Infers a schema by aggregating every field path of every document added to it.
+#[derive(Debug, Default)]
+pub struct Schema {
+    documents: u64,
+    fields: Vec<FieldSchema>,
+    index: HashMap<String, usize>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn documents(&self) -> u64 {
+        self.documents
+    }
+
+    pub fn fields(&self) -> &[FieldSchema] {
+        &self.fields
+    }
+
+    pub fn add_document(&mut self, raw_document: &RawDocument) -> std::result::Result<(), bson::raw::Error> {
+        self.documents += 1;
+        self.add_embedded_document(raw_document, "")
+    }
+
+    fn add_embedded_document(
+        &mut self,
+        raw_document: &RawDocument,
+        prefix: &str,
+    ) -> std::result::Result<(), bson::raw::Error> {
+        for element in raw_document {
+            let (name, bson_ref) = element?;
+            self.add_value(&format!("{}{}", prefix, name), &bson_ref)?;
+        }
+        Ok(())
+    }
+
+    fn add_array(&mut self, array: &RawArray, prefix: &str) -> std::result::Result<(), bson::raw::Error> {
+        let path = format!("{}{}", prefix, ARRAY_ELEMENT);
+        for element in array {
+            self.add_value(&path, &element?)?;
+        }
+        Ok(())
+    }
+
+    fn add_value(&mut self, path: &str, bson_ref: &RawBsonRef) -> std::result::Result<(), bson::raw::Error> {
+        let documents = self.documents;
+        let index = match self.index.get(path) {
+            Some(&index) => index,
+            None => {
+                self.fields.push(FieldSchema {
+                    path: path.to_string(),
+                    types: BTreeMap::new(),
+                    occurrences: 0,
+                    nulls: 0,
+                    example: None,
+                    last_document: 0,
+                    last_null_document: 0,
+                });
+                self.index.insert(path.to_string(), self.fields.len() - 1);
+                self.fields.len() - 1
+            }
+        };
+        let field = &mut self.fields[index];
+        *field.types.entry(type_name(bson_ref.element_type())).or_insert(0) += 1;
+        // Values inside arrays may repeat a path within one document; count the document once.
+        if field.last_document != documents {
+            field.last_document = documents;
+            field.occurrences += 1;
+        }
+        if let RawBsonRef::Null = bson_ref {
+            if field.last_null_document != documents {
+                field.last_null_document = documents;
+                field.nulls += 1;
+            }
+        }
+        if field.example.is_none() {
+            field.example = value_preview(bson_ref);
+        }
+
+        match bson_ref {
+            RawBsonRef::Document(embedded) => self.add_embedded_document(embedded, &format!("{}.", path))?,
+            RawBsonRef::Array(embedded) => self.add_array(embedded, &format!("{}.", path))?,
+            _ => (),
+        };
+        Ok(())
+    }
+
+    /// How many documents contain each field path, contain null at it, or are missing it, in the
+    /// order the paths were first seen.
+    pub fn field_stats(&self) -> Vec<serde_json::Value> {
+        self.fields
+            .iter()
+            .map(|field| {
+                json!({
+                    "field": field.path,
+                    "present": field.occurrences,
+                    "null": field.nulls,
+                    "missing": self.documents - field.occurrences,
+                })
+            })
+            .collect()
+    }
+
+    /// Write a table of every field path, most common first.
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> std::result::Result<(), std::io::Error> {
+        let mut fields: Vec<&FieldSchema> = self.fields.iter().collect();
+        fields.sort_by(|a, b| b.occurrences.cmp(&a.occurrences).then_with(|| a.path.cmp(&b.path)));
+        let rows: Vec<serde_json::Value> = fields
+            .iter()
+            .map(|field| {
+                let types: Vec<String> =
+                    field.types.iter().map(|(name, count)| format!("{} ({})", name, count)).collect();
+                let percent =
+                    if self.documents == 0 { 0.0 } else { 100.0 * field.occurrences as f64 / self.documents as f64 };
+                json!({
+                    "key": field.path,
+                    "types": types.join(", "),
+                    "occurrences": field.occurrences,
+                    "percent": format!("{:.1}", percent),
+                    "example": field.example,
+                })
+            })
+            .collect();
+        let columns: Vec<String> =
+            ["key", "types", "occurrences", "percent", "example"].iter().map(|column| column.to_string()).collect();
+        table::write_table(writer, &columns, &rows)
+    }
+}
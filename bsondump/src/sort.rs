@@ -0,0 +1,287 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+use bson::{RawBsonRef, RawDocument, RawDocumentBuf};
+
+/// The field to sort documents by: a dot-separated path, and whether to sort in descending order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub path: String,
+    pub descending: bool,
+}
+
+#[derive(Debug)]
+pub struct ParseSortKeyError(String);
+
+impl std::fmt::Display for ParseSortKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid sort key: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSortKeyError {}
+
+impl std::str::FromStr for SortKey {
+    type Err = ParseSortKeyError;
+
+    /// Parse `path`, `path:asc` or `path:desc`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, descending) = match s.rsplit_once(':') {
+            None => (s, false),
+            Some((path, "asc")) => (path, false),
+            Some((path, "desc")) => (path, true),
+            Some((_, order)) => return Err(ParseSortKeyError(format!("unknown order {:?}", order))),
+        };
+        if path.is_empty() {
+            return Err(ParseSortKeyError("no path".to_string()));
+        }
+        Ok(SortKey { path: path.to_string(), descending })
+    }
+}
+
+// How many bytes of documents are held in memory before they're sorted and spilled to a temporary
+// file.
+const SPILL_AT: usize = 256 << 20;
+
+// A document, the bytes its sort key compares as, and the order it was added in, so that documents
+// with equal keys keep it.
+struct Record {
+    key: Vec<u8>,
+    sequence: u64,
+    document: RawDocumentBuf,
+}
+
+/// Sorts documents by a field, in the order MongoDB sorts values of different types: MinKey, then
+/// null and missing values, numbers, strings, documents, arrays, binary, ObjectIds, booleans,
+/// dates, timestamps, regular expressions and MaxKey. Unlike MongoDB, arrays are compared as a
+/// whole, and numbers as doubles. Documents with equal keys stay in the order they were added.
+/// Once there are too many to hold in memory, they're sorted and spilled to temporary files,
+/// which are merged at the end.
+pub struct Sorter {
+    key: SortKey,
+    records: Vec<Record>,
+    size: usize,
+    runs: Vec<File>,
+    spill_at: usize,
+    sequence: u64,
+    budget: Option<Arc<crate::memory::Budget>>,
+}
+
+impl Sorter {
+    pub fn new(key: SortKey) -> Self {
+        Sorter::with_spill_at(key, SPILL_AT)
+    }
+
+    /// A sorter that spills to disk once it holds `spill_at` bytes of documents.
+    pub fn with_spill_at(key: SortKey, spill_at: usize) -> Self {
+        Sorter {
+            key,
+            records: Vec::new(),
+            size: 0,
+            runs: Vec::new(),
+            spill_at: spill_at.max(1),
+            sequence: 0,
+            budget: None,
+        }
+    }
+
+    /// Also spill once the documents held can't be reserved from `budget`.
+    pub fn budget(mut self, budget: Arc<crate::memory::Budget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn add(&mut self, document: RawDocumentBuf) -> io::Result<()> {
+        let mut key = sort_bytes(lookup(&document, &self.key.path));
+        if self.key.descending {
+            key.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+        let size = document.as_bytes().len() + key.len();
+        if !crate::memory::try_reserve(self.budget.as_deref(), size as u64) {
+            // Holding the document doesn't fit, so the ones held already are let go. It's held
+            // either way.
+            if !self.records.is_empty() {
+                self.spill()?;
+            }
+            if let Some(budget) = &self.budget {
+                budget.reserve(size as u64);
+            }
+        }
+        self.size += size;
+        self.records.push(Record { key, sequence: self.sequence, document });
+        self.sequence += 1;
+        if self.size >= self.spill_at {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.records.sort_unstable_by(|a, b| (&a.key, a.sequence).cmp(&(&b.key, b.sequence)));
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        for record in self.records.drain(..) {
+            writer.write_all(&(record.key.len() as u32).to_le_bytes())?;
+            writer.write_all(&record.key)?;
+            writer.write_all(&record.sequence.to_le_bytes())?;
+            writer.write_all(record.document.as_bytes())?;
+        }
+        crate::memory::release(self.budget.as_deref(), self.size as u64);
+        self.size = 0;
+        let mut file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    /// The documents in sorted order.
+    pub fn finish(mut self) -> io::Result<Sorted> {
+        self.records.sort_unstable_by(|a, b| (&a.key, a.sequence).cmp(&(&b.key, b.sequence)));
+        let mut runs = vec![Run::Memory(std::mem::take(&mut self.records).into_iter())];
+        runs.extend(self.runs.into_iter().map(|file| Run::File(BufReader::new(file))));
+        let mut sorted = Sorted {
+            heads: vec![None; runs.len()],
+            heap: BinaryHeap::new(),
+            runs: Vec::new(),
+            budget: self.budget.take(),
+        };
+        for (index, mut run) in runs.into_iter().enumerate() {
+            let head = run.next_record()?;
+            sorted.push(index, head);
+            sorted.runs.push(run);
+        }
+        Ok(sorted)
+    }
+}
+
+/// Sorted documents, merged from the runs a `Sorter` kept in memory or spilled.
+pub struct Sorted {
+    runs: Vec<Run>,
+    // The next document of each run, and the runs ordered by their next key.
+    heads: Vec<Option<RawDocumentBuf>>,
+    heap: BinaryHeap<Reverse<(Vec<u8>, u64, usize)>>,
+    // What the documents held in memory are reserved from, released as they're returned.
+    budget: Option<Arc<crate::memory::Budget>>,
+}
+
+impl Sorted {
+    fn push(&mut self, run: usize, record: Option<Record>) {
+        if let Some(record) = record {
+            self.heap.push(Reverse((record.key, record.sequence, run)));
+            self.heads[run] = Some(record.document);
+        }
+    }
+
+    /// The next document, or None once they've all been returned.
+    pub fn next_document(&mut self) -> io::Result<Option<RawDocumentBuf>> {
+        let (key, run) = match self.heap.pop() {
+            None => return Ok(None),
+            Some(Reverse((key, _, run))) => (key, run),
+        };
+        let document = self.heads[run].take();
+        // The first run is the one that was never spilled.
+        if let (0, Some(document)) = (run, &document) {
+            crate::memory::release(self.budget.as_deref(), (document.as_bytes().len() + key.len()) as u64);
+        }
+        let record = self.runs[run].next_record()?;
+        self.push(run, record);
+        Ok(document)
+    }
+}
+
+// A sorted run of records, in memory or spilled.
+enum Run {
+    Memory(std::vec::IntoIter<Record>),
+    File(BufReader<File>),
+}
+
+impl Run {
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let reader = match self {
+            Run::Memory(records) => return Ok(records.next()),
+            Run::File(reader) => reader,
+        };
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+            Ok(()) => (),
+        }
+        let mut key = vec![0u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut key)?;
+        let mut sequence = [0u8; 8];
+        reader.read_exact(&mut sequence)?;
+        reader.read_exact(&mut len)?;
+        let mut document = vec![0u8; u32::from_le_bytes(len) as usize];
+        document[..4].copy_from_slice(&len);
+        reader.read_exact(&mut document[4..])?;
+        let document =
+            RawDocumentBuf::from_bytes(document).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Some(Record { key, sequence: u64::from_le_bytes(sequence), document }))
+    }
+}
+
+// The value at a dot-separated path, through embedded documents and, by index, arrays.
+fn lookup<'a>(document: &'a RawDocument, path: &str) -> Option<RawBsonRef<'a>> {
+    let mut keys = path.split('.');
+    let mut value = document.get(keys.next()?).ok()??;
+    for key in keys {
+        let embedded = match value {
+            RawBsonRef::Document(embedded) => embedded,
+            RawBsonRef::Array(array) => RawDocument::from_bytes(array.as_bytes()).ok()?,
+            _ => return None,
+        };
+        value = embedded.get(key).ok()??;
+    }
+    Some(value)
+}
+
+// Bytes that compare the way the value sorts, ending with a 0 so that when they're inverted for a
+// descending sort, a value that's a prefix of another still sorts after it.
+fn sort_bytes(value: Option<RawBsonRef>) -> Vec<u8> {
+    // An f64 whose bytes compare the way it does.
+    let ordered_f64 = |float: f64| {
+        let bits = float.to_bits();
+        let bits = if float.is_sign_negative() { !bits } else { bits | (1 << 63) };
+        bits.to_be_bytes().to_vec()
+    };
+    let (rank, bytes) = match value {
+        Some(RawBsonRef::MinKey) => (0, Vec::new()),
+        None | Some(RawBsonRef::Null) | Some(RawBsonRef::Undefined) => (1, Vec::new()),
+        Some(RawBsonRef::Int32(int)) => (2, ordered_f64(int as f64)),
+        Some(RawBsonRef::Int64(int)) => (2, ordered_f64(int as f64)),
+        Some(RawBsonRef::Double(double)) => (2, ordered_f64(double)),
+        Some(RawBsonRef::Decimal128(decimal)) => (2, ordered_f64(decimal.to_string().parse().unwrap_or(f64::NAN))),
+        Some(RawBsonRef::String(string)) | Some(RawBsonRef::Symbol(string)) => (3, string.as_bytes().to_vec()),
+        Some(RawBsonRef::Document(document)) => (4, document.as_bytes().to_vec()),
+        Some(RawBsonRef::Array(array)) => (5, array.as_bytes().to_vec()),
+        Some(RawBsonRef::Binary(binary)) => {
+            let mut bytes = (binary.bytes.len() as u32).to_be_bytes().to_vec();
+            bytes.push(u8::from(binary.subtype));
+            bytes.extend_from_slice(binary.bytes);
+            (6, bytes)
+        }
+        Some(RawBsonRef::ObjectId(oid)) => (7, oid.bytes().to_vec()),
+        Some(RawBsonRef::Boolean(boolean)) => (8, vec![boolean as u8]),
+        Some(RawBsonRef::DateTime(datetime)) => {
+            (9, ((datetime.timestamp_millis() as u64) ^ (1 << 63)).to_be_bytes().to_vec())
+        }
+        Some(RawBsonRef::Timestamp(timestamp)) => {
+            (10, [timestamp.time.to_be_bytes(), timestamp.increment.to_be_bytes()].concat())
+        }
+        Some(RawBsonRef::RegularExpression(regex)) => (11, [regex.pattern, "\0", regex.options].concat().into_bytes()),
+        Some(RawBsonRef::DbPointer(_)) => (12, Vec::new()),
+        Some(RawBsonRef::JavaScriptCode(code)) => (13, code.as_bytes().to_vec()),
+        Some(RawBsonRef::JavaScriptCodeWithScope(code)) => (14, code.code.as_bytes().to_vec()),
+        Some(RawBsonRef::MaxKey) => (15, Vec::new()),
+    };
+    let mut key = Vec::with_capacity(bytes.len() + 2);
+    key.push(rank);
+    key.extend(bytes);
+    key.push(0);
+    key
+}
@@ -0,0 +1,19 @@
+use std::path::{Path, PathBuf};
+
+/// The path of the `index`th part of an output split across files: `out.json` becomes
+/// `out.0001.json`, and `out.json.gz` becomes `out.0001.json.gz`.
+pub fn split_path(path: &Path, index: u32) -> PathBuf {
+    let (path, compression) = match crate::compression::from_extension(path) {
+        Some(compression) => (path.with_extension(""), Some(compression)),
+        None => (path.to_path_buf(), None),
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}.{:04}", stem, index);
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    if let Some(compression) = compression {
+        name = format!("{}.{}", name, compression.extension());
+    }
+    path.with_file_name(name)
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use bson::{RawBsonRef, RawDocument};
+use serde_json::json;
+
+use crate::{bytes::CountBytes, table, type_name};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SizeStat {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// Accumulates how many bytes each element type and each top-level field take up across a stream
+/// of documents.
+#[derive(Debug, Default)]
+pub struct SizeStats {
+    documents: u64,
+    total_bytes: u64,
+    by_type: BTreeMap<&'static str, SizeStat>,
+    by_field: HashMap<String, SizeStat>,
+}
+
+fn element_size(name: &str, bson_ref: &RawBsonRef) -> u64 {
+    let size_of_type = 1usize;
+    let size_of_name = name.len() + 1; // null terminator
+    (size_of_type + size_of_name + bson_ref.count_bytes()) as u64
+}
+
+fn percent(bytes: u64, total_bytes: u64) -> String {
+    let percent = if total_bytes == 0 { 0.0 } else { 100.0 * bytes as f64 / total_bytes as f64 };
+    format!("{:.1}", percent)
+}
+
+impl SizeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn documents(&self) -> u64 {
+        self.documents
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Bytes per element type. Embedded documents and arrays are only charged for their own
+    /// overhead; their elements are charged to their own types.
+    pub fn by_type(&self) -> &BTreeMap<&'static str, SizeStat> {
+        &self.by_type
+    }
+
+    /// Bytes per top-level field, including everything nested under it.
+    pub fn by_field(&self) -> &HashMap<String, SizeStat> {
+        &self.by_field
+    }
+
+    pub fn add_document(&mut self, raw_document: &RawDocument) -> std::result::Result<(), bson::raw::Error> {
+        self.documents += 1;
+        self.total_bytes += raw_document.as_bytes().len() as u64;
+        for element in raw_document {
+            let (name, bson_ref) = element?;
+            let size = element_size(name, &bson_ref);
+            let stat = self.by_field.entry(name.to_string()).or_default();
+            stat.count += 1;
+            stat.bytes += size;
+            self.add_element(&bson_ref, size)?;
+        }
+        Ok(())
+    }
+
+    fn add_element(&mut self, bson_ref: &RawBsonRef, size: u64) -> std::result::Result<(), bson::raw::Error> {
+        let mut nested_bytes = 0;
+        match bson_ref {
+            RawBsonRef::Document(embedded) => {
+                for element in *embedded {
+                    let (name, nested) = element?;
+                    let nested_size = element_size(name, &nested);
+                    nested_bytes += nested_size;
+                    self.add_element(&nested, nested_size)?;
+                }
+            }
+            RawBsonRef::Array(embedded) => {
+                for (index, element) in embedded.into_iter().enumerate() {
+                    let nested = element?;
+                    let nested_size = element_size(&index.to_string(), &nested);
+                    nested_bytes += nested_size;
+                    self.add_element(&nested, nested_size)?;
+                }
+            }
+            _ => (),
+        };
+        let stat = self.by_type.entry(type_name(bson_ref.element_type())).or_default();
+        stat.count += 1;
+        stat.bytes += size - nested_bytes;
+        Ok(())
+    }
+
+    fn write_table<W: Write>(
+        &self,
+        writer: &mut W,
+        column: &str,
+        stats: Vec<(&str, &SizeStat)>,
+    ) -> std::result::Result<(), std::io::Error> {
+        let mut stats = stats;
+        stats.sort_by(|(a_name, a), (b_name, b)| b.bytes.cmp(&a.bytes).then_with(|| a_name.cmp(b_name)));
+        let rows: Vec<serde_json::Value> = stats
+            .iter()
+            .map(|(name, stat)| {
+                json!({
+                    column: name,
+                    "count": stat.count,
+                    "bytes": stat.bytes,
+                    "percent": percent(stat.bytes, self.total_bytes),
+                })
+            })
+            .collect();
+        let columns: Vec<String> =
+            [column, "count", "bytes", "percent"].iter().map(|column| column.to_string()).collect();
+        table::write_table(writer, &columns, &rows)
+    }
+
+    /// Write a summary followed by tables of bytes per type and per top-level field, largest first.
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> std::result::Result<(), std::io::Error> {
+        writeln!(writer, "{} documents, {} bytes", self.documents, self.total_bytes)?;
+        writeln!(writer)?;
+        self.write_table(writer, "type", self.by_type.iter().map(|(name, stat)| (*name, stat)).collect())?;
+        writeln!(writer)?;
+        self.write_table(writer, "field", self.by_field.iter().map(|(name, stat)| (name.as_str(), stat)).collect())
+    }
+}
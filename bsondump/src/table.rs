@@ -0,0 +1,78 @@
+use std::io::Write;
+
+use serde_json::Value;
+
+use crate::path;
+
+// Longer cells are truncated so that a single large value doesn't push every other column off screen.
+const MAX_CELL_CHARS: usize = 40;
+const COLUMN_SEPARATOR: &str = "  ";
+
+/// Render a value the way it appears in a csv or table cell: strings without quotes, missing and
+/// null values as empty cells, and everything else as JSON.
+pub fn to_cell_string(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(string)) => string.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn truncate(cell: String) -> String {
+    if cell.chars().count() <= MAX_CELL_CHARS {
+        return cell;
+    }
+    let truncated: String = cell.chars().take(MAX_CELL_CHARS - 3).collect();
+    format!("{}...", truncated)
+}
+
+/// The union of the top-level keys of `values`, in the order they were first seen.
+pub fn discover_columns(values: &[Value]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    for map in values.iter().filter_map(Value::as_object) {
+        for key in map.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    columns
+}
+
+fn write_row<W: Write>(writer: &mut W, cells: &[String], widths: &[usize]) -> std::result::Result<(), std::io::Error> {
+    let line = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+        .collect::<Vec<String>>()
+        .join(COLUMN_SEPARATOR);
+    writeln!(writer, "{}", line.trim_end())
+}
+
+/// Write `values` as a table with one row per value and one column per dot-separated path in
+/// `columns`, aligned to the widest cell in each column.
+pub fn write_table<W: Write>(
+    writer: &mut W,
+    columns: &[String],
+    values: &[Value],
+) -> std::result::Result<(), std::io::Error> {
+    let rows: Vec<Vec<String>> = values
+        .iter()
+        .map(|value| columns.iter().map(|column| truncate(to_cell_string(path::lookup(value, column)))).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    write_row(writer, columns, &widths)?;
+    let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+    write_row(writer, &rule, &widths)?;
+    for row in &rows {
+        write_row(writer, row, &widths)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// The stages of a run that --timing breaks its time down into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading, checking and selecting documents.
+    Reading,
+    /// Converting them to the output type.
+    Converting,
+    /// Writing them out.
+    Writing,
+}
+
+/// Where a run's time went, for --timing: how long each stage took, summed across the threads it
+/// ran on, and how many bytes of documents were read and written. It's shared by those threads.
+#[derive(Debug, Default)]
+pub struct Timing {
+    // In nanoseconds.
+    reading: AtomicU64,
+    converting: AtomicU64,
+    writing: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl Timing {
+    fn counter(&self, stage: Stage) -> &AtomicU64 {
+        match stage {
+            Stage::Reading => &self.reading,
+            Stage::Converting => &self.converting,
+            Stage::Writing => &self.writing,
+        }
+    }
+
+    pub fn add(&self, stage: Stage, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.counter(stage).fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// How long `stage` has taken so far.
+    pub fn elapsed(&self, stage: Stage) -> Duration {
+        Duration::from_nanos(self.counter(stage).load(Ordering::Relaxed))
+    }
+
+    pub fn add_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Add another run's times and bytes to these, e.g. for each file of a directory.
+    pub fn merge(&self, other: &Timing) {
+        for stage in [Stage::Reading, Stage::Converting, Stage::Writing] {
+            self.add(stage, other.elapsed(stage));
+        }
+        self.add_read(other.bytes_read.load(Ordering::Relaxed));
+        self.add_written(other.bytes_written.load(Ordering::Relaxed));
+    }
+
+    /// A line reporting a run that output `documents` documents in `elapsed`, e.g. "Took 2.00s:
+    /// 50000 docs/s, 10.0MB/s read, 25.0MB/s written; reading took 0.80s, converting 1.00s and
+    /// writing 0.20s".
+    pub fn report(&self, elapsed: Duration, documents: u64) -> String {
+        let seconds = elapsed.as_secs_f64();
+        let per_second = |count: u64| if seconds > 0.0 { count as f64 / seconds } else { 0.0 };
+        let rate = |bytes: &AtomicU64| crate::progress::format_size(per_second(bytes.load(Ordering::Relaxed)) as u64);
+        format!(
+            concat!(
+                "Took {:.2}s: {} docs/s, {}/s read, {}/s written; ",
+                "reading took {:.2}s, converting {:.2}s and writing {:.2}s"
+            ),
+            seconds,
+            per_second(documents) as u64,
+            rate(&self.bytes_read),
+            rate(&self.bytes_written),
+            self.elapsed(Stage::Reading).as_secs_f64(),
+            self.elapsed(Stage::Converting).as_secs_f64(),
+            self.elapsed(Stage::Writing).as_secs_f64(),
+        )
+    }
+}
+
+impl Clone for Timing {
+    fn clone(&self) -> Timing {
+        let clone = Timing::default();
+        clone.merge(self);
+        clone
+    }
+}
+
+/// Run `f`, adding the time it takes to `stage`, if it's being timed.
+pub fn time<T>(timing: Option<&Timing>, stage: Stage, f: impl FnOnce() -> T) -> T {
+    let timing = match timing {
+        None => return f(),
+        Some(timing) => timing,
+    };
+    let started = Instant::now();
+    let result = f();
+    timing.add(stage, started.elapsed());
+    result
+}
+
+/// Run `f`, which converts documents and writes them as it goes, adding the time it takes to
+/// converting, less the time it spends writing.
+pub fn time_converting<T>(timing: Option<&Timing>, f: impl FnOnce() -> T) -> T {
+    let timing = match timing {
+        None => return f(),
+        Some(timing) => timing,
+    };
+    let (started, writing) = (Instant::now(), timing.elapsed(Stage::Writing));
+    let result = f();
+    let written = timing.elapsed(Stage::Writing) - writing;
+    timing.add(Stage::Converting, started.elapsed().saturating_sub(written));
+    result
+}
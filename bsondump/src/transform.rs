@@ -0,0 +1,170 @@
+use bson::{RawBson, RawBsonRef, RawDocument, RawDocumentBuf};
+
+use crate::projection::Projection;
+
+/// A field to move: its dot-separated path, and the one to move it to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug)]
+pub struct ParseRenameError(String);
+
+impl std::fmt::Display for ParseRenameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid rename: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseRenameError {}
+
+impl std::str::FromStr for Rename {
+    type Err = ParseRenameError;
+
+    /// Parse `old=new`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = match s.split_once('=') {
+            Some((from, to)) if !from.is_empty() && !to.is_empty() => (from, to),
+            _ => return Err(ParseRenameError(format!("{:?} isn't old=new", s))),
+        };
+        let within = |path: &str, other: &str| path.starts_with(&format!("{}.", other));
+        if from == to || within(from, to) || within(to, from) {
+            return Err(ParseRenameError(format!("{} can't be moved to {}", from, to)));
+        }
+        Ok(Rename { from: from.to_string(), to: to.to_string() })
+    }
+}
+
+/// Rewrites documents in their raw form: moves fields, then drops them. Like MongoDB's `$rename`,
+/// a move replaces whatever is already at the new path, creates the embedded documents above it,
+/// and doesn't go through arrays; a field that's missing, or whose new path is below a value that
+/// isn't a document, is left where it is. A field renamed within the same document keeps its
+/// place, while one moved elsewhere is added at the end of its new document. Drops are like
+/// --excludeFields.
+#[derive(Debug, Default)]
+pub struct Transform {
+    renames: Vec<Rename>,
+    drop: Projection,
+}
+
+impl Transform {
+    pub fn new(renames: &[Rename], drop: &[String]) -> Self {
+        Transform { renames: renames.to_vec(), drop: Projection::new(&[], drop) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.drop.is_empty()
+    }
+
+    /// Build the rewritten copy of `document`, applying the renames in order.
+    pub fn apply(&self, document: &RawDocument) -> Result<RawDocumentBuf, bson::raw::Error> {
+        let mut document = document.to_raw_document_buf();
+        for rename in &self.renames {
+            let from: Vec<&str> = rename.from.split('.').collect();
+            let to: Vec<&str> = rename.to.split('.').collect();
+            document = match (from.split_last(), to.split_last()) {
+                (Some((from, parent)), Some((to, to_parent))) if parent == to_parent => {
+                    rename_key(&document, parent, from, to)?
+                }
+                _ => move_field(&document, &from, &to)?,
+            };
+        }
+        self.drop.apply(&document)
+    }
+}
+
+// Rename the key `from` to `to` in the document at `parent`, in place.
+fn rename_key(
+    document: &RawDocument,
+    parent: &[&str],
+    from: &str,
+    to: &str,
+) -> Result<RawDocumentBuf, bson::raw::Error> {
+    let renaming = parent.is_empty() && document.get(from)?.is_some();
+    let mut renamed = RawDocumentBuf::new();
+    for element in document {
+        let (key, value) = element?;
+        match value {
+            _ if renaming && key == from => renamed.append_ref(to, value),
+            // Replaced by the renamed field.
+            _ if renaming && key == to => (),
+            RawBsonRef::Document(embedded) if parent.first() == Some(&key) => {
+                renamed.append(key, rename_key(embedded, &parent[1..], from, to)?)
+            }
+            value => renamed.append_ref(key, value),
+        }
+    }
+    Ok(renamed)
+}
+
+fn move_field(document: &RawDocument, from: &[&str], to: &[&str]) -> Result<RawDocumentBuf, bson::raw::Error> {
+    let (removed, value) = take(document, from)?;
+    let value = match value {
+        None => return Ok(document.to_raw_document_buf()),
+        Some(value) => value,
+    };
+    match insert(&removed, to, value)? {
+        None => Ok(document.to_raw_document_buf()),
+        Some(inserted) => Ok(inserted),
+    }
+}
+
+// Remove the field at `keys`, returning the document without it and its value.
+fn take(document: &RawDocument, keys: &[&str]) -> Result<(RawDocumentBuf, Option<RawBson>), bson::raw::Error> {
+    let mut removed = RawDocumentBuf::new();
+    let mut taken = None;
+    for element in document {
+        let (key, value) = element?;
+        if key != keys[0] || taken.is_some() {
+            removed.append_ref(key, value);
+            continue;
+        }
+        match value {
+            value if keys.len() == 1 => taken = Some(value.to_raw_bson()),
+            RawBsonRef::Document(embedded) => {
+                let (embedded, value) = take(embedded, &keys[1..])?;
+                removed.append(key, embedded);
+                taken = value;
+            }
+            value => removed.append_ref(key, value),
+        }
+    }
+    Ok((removed, taken))
+}
+
+// Set the field at `keys` to `value`, or None if something other than a document is in the way.
+fn insert(document: &RawDocument, keys: &[&str], value: RawBson) -> Result<Option<RawDocumentBuf>, bson::raw::Error> {
+    let mut inserted = RawDocumentBuf::new();
+    let mut value = Some(value);
+    for element in document {
+        let (key, existing) = element?;
+        let new = match value.take() {
+            Some(new) if key == keys[0] => new,
+            new => {
+                value = new;
+                inserted.append_ref(key, existing);
+                continue;
+            }
+        };
+        match existing {
+            _ if keys.len() == 1 => inserted.append(key, new),
+            RawBsonRef::Document(embedded) => match insert(embedded, &keys[1..], new)? {
+                None => return Ok(None),
+                Some(embedded) => inserted.append(key, embedded),
+            },
+            _ => return Ok(None),
+        }
+    }
+    if let Some(value) = value {
+        // Create the embedded documents the field goes in.
+        let nested = keys[1..].iter().rev().fold(value, |value, key| {
+            let mut document = RawDocumentBuf::new();
+            document.append(*key, value);
+            RawBson::Document(document)
+        });
+        inserted.append(keys[0], nested);
+    }
+    Ok(Some(inserted))
+}
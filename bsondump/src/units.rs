@@ -0,0 +1,35 @@
+/// A number of bytes, parsed from e.g. `500MB` or `1GB`. Units are powers of 1024.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+
+#[derive(Debug)]
+pub struct ParseByteSizeError(String);
+
+impl std::fmt::Display for ParseByteSizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid size: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseByteSizeError {}
+
+impl std::str::FromStr for ByteSize {
+    type Err = ParseByteSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "K" | "KB" => 1 << 10,
+            "M" | "MB" => 1 << 20,
+            "G" | "GB" => 1 << 30,
+            "T" | "TB" => 1 << 40,
+            _ => return Err(ParseByteSizeError(s.to_string())),
+        };
+        match number.parse::<u64>().ok().and_then(|number| number.checked_mul(multiplier)) {
+            Some(size) if size > 0 => Ok(ByteSize(size)),
+            _ => Err(ParseByteSizeError(s.to_string())),
+        }
+    }
+}
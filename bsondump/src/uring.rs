@@ -0,0 +1,195 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    os::unix::io::AsRawFd,
+};
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+// How many reads are kept in flight, and how large each one is.
+const BUFFERS: usize = 8;
+const BUFFER_SIZE: usize = 256 << 10;
+
+/// Reads a file through io_uring on Linux, keeping several large reads ahead of the one being
+/// consumed in flight, submitted together. Its buffers are registered with the kernel, where it
+/// allows, so that they needn't be mapped for each read.
+pub struct UringReader {
+    file: File,
+    // Dropped before the buffers, which stay registered with it until then.
+    ring: IoUring,
+    buffers: Vec<Box<[u8]>>,
+    registered: bool,
+    // The buffers being read into, in file order, and the offset each is read from.
+    in_flight: VecDeque<(usize, u64)>,
+    // The results of the reads into each buffer that have completed.
+    results: Vec<Option<i32>>,
+    free: Vec<usize>,
+    // Where the next read is submitted from.
+    next_offset: u64,
+    // The buffer being consumed, how much of it has been, and how much it holds.
+    current: Option<(usize, usize, usize)>,
+    // Where the bytes the next read returns start.
+    position: u64,
+    eof: bool,
+}
+
+impl UringReader {
+    pub fn new(file: File) -> io::Result<UringReader> {
+        let ring = IoUring::new(BUFFERS as u32)?;
+        let mut buffers: Vec<Box<[u8]>> = (0..BUFFERS).map(|_| vec![0u8; BUFFER_SIZE].into_boxed_slice()).collect();
+        let iovecs: Vec<libc::iovec> = buffers
+            .iter_mut()
+            .map(|buffer| libc::iovec { iov_base: buffer.as_mut_ptr() as *mut libc::c_void, iov_len: buffer.len() })
+            .collect();
+        // SAFETY: the buffers are boxed, so they don't move, and they're freed only after the ring
+        // is dropped, which unregisters them. Registering can fail where locked memory is limited,
+        // but plain reads still work.
+        let registered = unsafe { ring.submitter().register_buffers(&iovecs) }.is_ok();
+        Ok(UringReader {
+            file,
+            ring,
+            buffers,
+            registered,
+            in_flight: VecDeque::new(),
+            results: vec![None; BUFFERS],
+            free: (0..BUFFERS).rev().collect(),
+            next_offset: 0,
+            current: None,
+            position: 0,
+            eof: false,
+        })
+    }
+
+    // Read ahead into every free buffer.
+    fn submit(&mut self) -> io::Result<()> {
+        if self.eof || self.free.is_empty() {
+            return Ok(());
+        }
+        let fd = types::Fd(self.file.as_raw_fd());
+        while let Some(index) = self.free.pop() {
+            let buf = self.buffers[index].as_mut_ptr();
+            let entry: squeue::Entry = if self.registered {
+                opcode::ReadFixed::new(fd, buf, BUFFER_SIZE as u32, index as u16).offset(self.next_offset).build()
+            } else {
+                opcode::Read::new(fd, buf, BUFFER_SIZE as u32).offset(self.next_offset).build()
+            };
+            // SAFETY: a buffer is only free once the read into it has completed, and isn't reused,
+            // or freed, until its own read has; Drop waits for it, or leaks the buffers. The file is
+            // held as long as the ring.
+            let pushed = unsafe { self.ring.submission().push(&entry.user_data(index as u64)) };
+            // There's an entry in the queue for every buffer.
+            pushed.expect("the submission queue is full");
+            self.in_flight.push_back((index, self.next_offset));
+            self.next_offset += BUFFER_SIZE as u64;
+        }
+        self.enter(0)
+    }
+
+    // Submit whatever the kernel hasn't taken from the queue yet, and wait for `wait` completions.
+    fn enter(&self, wait: usize) -> io::Result<()> {
+        loop {
+            match self.ring.submit_and_wait(wait) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result.map(drop),
+            }
+        }
+    }
+
+    fn wait_for(&mut self, index: usize) -> io::Result<i32> {
+        loop {
+            for entry in self.ring.completion() {
+                self.results[entry.user_data() as usize] = Some(entry.result());
+            }
+            if let Some(result) = self.results[index].take() {
+                return Ok(result);
+            }
+            self.enter(1)?;
+        }
+    }
+
+    // Wait for the reads in flight, which the kernel may still be writing into their buffers, and
+    // discard them, reading from `offset` next. A read stays in flight until it's been waited for.
+    fn restart(&mut self, offset: u64) -> io::Result<()> {
+        while let Some(&(index, _)) = self.in_flight.front() {
+            self.wait_for(index)?;
+            self.in_flight.pop_front();
+            self.free.push(index);
+        }
+        self.next_offset = offset;
+        Ok(())
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if let Some((index, start, len)) = self.current {
+                if start < len {
+                    let count = (len - start).min(buf.len());
+                    buf[..count].copy_from_slice(&self.buffers[index][start..start + count]);
+                    self.current = Some((index, start + count, len));
+                    self.position += count as u64;
+                    return Ok(count);
+                }
+                self.current = None;
+                self.free.push(index);
+            }
+            self.submit()?;
+            let (index, offset) = match self.in_flight.front() {
+                None => return Ok(0),
+                Some(&read) => read,
+            };
+            let result = self.wait_for(index)?;
+            self.in_flight.pop_front();
+            if result < 0 {
+                self.free.push(index);
+                self.restart(offset)?;
+                return Err(io::Error::from_raw_os_error(-result));
+            }
+            let len = result as usize;
+            if len < BUFFER_SIZE {
+                // The end of the file, or a short read, after which the reads ahead are from the
+                // wrong offsets.
+                self.restart(offset + len as u64)?;
+                self.eof = len == 0;
+            }
+            self.current = Some((index, 0, len));
+        }
+    }
+}
+
+impl Seek for UringReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.file.metadata()?.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let position = match position {
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position")),
+            Some(position) => position,
+        };
+        if let Some((index, ..)) = self.current.take() {
+            self.free.push(index);
+        }
+        self.restart(position)?;
+        self.position = position;
+        self.eof = false;
+        Ok(position)
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // The buffers mustn't be freed while the kernel could still write into them, so if the reads
+        // in flight can't be waited for, they're leaked instead.
+        if let Err(err) = self.restart(0) {
+            log::warn!("leaking io_uring buffers, failed to wait for reads in flight: {}", err);
+            std::mem::forget(std::mem::take(&mut self.buffers));
+        }
+    }
+}
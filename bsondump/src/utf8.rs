@@ -0,0 +1,83 @@
+use bson::{
+    raw::{RawArrayBuf, RawBson, RawDocument, RawDocumentBuf},
+    serde_helpers::Utf8LossyDeserialization,
+    spec::{BinarySubtype, ElementType},
+    Bson,
+};
+
+/// What converting a document does with string values that aren't valid UTF-8.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Replace each invalid sequence with U+FFFD.
+    #[default]
+    Replace,
+    /// Fail to convert the document.
+    Fail,
+    /// Convert the string to binary (subtype 0) instead, so that none of its bytes are lost.
+    Binary,
+}
+
+/// Convert a document to a `Bson` value, handling string values that aren't valid UTF-8 as asked.
+pub fn to_bson(document: &RawDocument, invalid_utf8: InvalidUtf8) -> Result<Bson, bson::ser::Error> {
+    let error = |err: &dyn std::fmt::Display| <bson::ser::Error as serde::ser::Error>::custom(err);
+    match invalid_utf8 {
+        InvalidUtf8::Fail => bson::to_bson(&document),
+        InvalidUtf8::Replace => match bson::from_slice::<Utf8LossyDeserialization<Bson>>(document.as_bytes()) {
+            Err(err) => Err(error(&err)),
+            Ok(Utf8LossyDeserialization(bson)) => Ok(bson),
+        },
+        // Only rebuild the documents that need it.
+        InvalidUtf8::Binary => bson::to_bson(&document).or_else(|_| {
+            let document = strings_to_binary(document).map_err(|err| error(&err))?;
+            bson::to_bson(&document)
+        }),
+    }
+}
+
+/// A copy of the document in which string values that aren't valid UTF-8 are binary instead.
+pub fn strings_to_binary(document: &RawDocument) -> Result<RawDocumentBuf, bson::raw::Error> {
+    let mut rebuilt = RawDocumentBuf::new();
+    for (key, value) in rebuild_elements(document)? {
+        rebuilt.append(key, value);
+    }
+    Ok(rebuilt)
+}
+
+fn rebuild_elements(document: &RawDocument) -> Result<Vec<(&str, RawBson)>, bson::raw::Error> {
+    let mut elements = Vec::new();
+    // Past the document's size.
+    let mut offset = 4;
+    for element in document.iter_elements() {
+        let element = element?;
+        // Past the element's type and its key's null terminator.
+        let start = offset + 1 + element.key().len() + 1;
+        offset = start + element.len();
+        let value = match element.element_type() {
+            ElementType::String => match element.value() {
+                Ok(value) => value.to_raw_bson(),
+                // Between the string's length and its null terminator.
+                Err(_) => RawBson::Binary(bson::Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes: document.as_bytes()[start + 4..offset - 1].to_vec(),
+                }),
+            },
+            ElementType::EmbeddedDocument => match element.value()? {
+                bson::RawBsonRef::Document(embedded) => RawBson::Document(strings_to_binary(embedded)?),
+                value => value.to_raw_bson(),
+            },
+            ElementType::Array => match element.value()? {
+                bson::RawBsonRef::Array(embedded) => {
+                    let mut array = RawArrayBuf::new();
+                    for (_, value) in rebuild_elements(RawDocument::from_bytes(embedded.as_bytes())?)? {
+                        array.push(value);
+                    }
+                    RawBson::Array(array)
+                }
+                value => value.to_raw_bson(),
+            },
+            _ => element.value()?.to_raw_bson(),
+        };
+        elements.push((element.key(), value));
+    }
+    Ok(elements)
+}
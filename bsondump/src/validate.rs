@@ -0,0 +1,368 @@
+use std::io::{self, Write};
+
+use bson::{spec::ElementType, RawBsonRef, RawDocument};
+use serde_json::{json, Map};
+
+use crate::docbytes::Position;
+
+/// Why a document's raw bytes aren't valid BSON, and the dot-separated path of the element where
+/// the problem was found.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "invalid BSON: {}", self.reason),
+            path => write!(f, "invalid BSON at {}: {}", path, self.reason),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+// Binary subtypes defined by the BSON spec, plus the user-defined range.
+fn is_valid_subtype(subtype: u8) -> bool {
+    subtype <= 0x09 || subtype >= 0x80
+}
+
+const REGEX_OPTIONS: &str = "ilmsux";
+
+// Reads the elements of a document in `bytes`, which ends at `end`. Offsets are from the start of
+// the outermost document.
+struct Validator<'a> {
+    bytes: &'a [u8],
+    end: usize,
+    path: &'a str,
+    // Whether string values must be valid UTF-8; keys always must.
+    check_utf8: bool,
+}
+
+impl<'a> Validator<'a> {
+    fn error<T>(&self, reason: String) -> Result<T, ValidationError> {
+        Err(ValidationError { path: self.path.to_string(), reason })
+    }
+
+    fn read_i32(&self, offset: usize) -> Result<i32, ValidationError> {
+        match self.bytes.get(offset..offset + 4).filter(|_| offset + 4 <= self.end) {
+            Some(bytes) => Ok(i32::from_le_bytes(bytes.try_into().unwrap())),
+            None => self.error(format!("length at byte {} runs past the end of the document", offset)),
+        }
+    }
+
+    // Check `len` bytes are left at `offset`, returning the offset after them.
+    fn skip(&self, offset: usize, len: usize) -> Result<usize, ValidationError> {
+        match offset.checked_add(len).filter(|end| *end <= self.end) {
+            Some(end) => Ok(end),
+            None => self.error(format!("{} byte value at byte {} runs past the end of the document", len, offset)),
+        }
+    }
+
+    // A null-terminated UTF-8 string, returning it and the offset after it.
+    fn cstring(&self, offset: usize, what: &str) -> Result<(&'a str, usize), ValidationError> {
+        let rest = self.bytes.get(offset..self.end).unwrap_or_default();
+        let len = match rest.iter().position(|&b| b == 0) {
+            None => return self.error(format!("{} at byte {} has no null terminator", what, offset)),
+            Some(len) => len,
+        };
+        match std::str::from_utf8(&rest[..len]) {
+            Err(_) => self.error(format!("{} at byte {} is not valid UTF-8", what, offset)),
+            Ok(string) => Ok((string, offset + len + 1)),
+        }
+    }
+
+    // A length-prefixed UTF-8 string, returning the offset after it.
+    fn string(&self, offset: usize) -> Result<usize, ValidationError> {
+        let len = self.read_i32(offset)?;
+        if len < 1 {
+            return self.error(format!("string length {} at byte {} is less than 1", len, offset));
+        }
+        let end = self.skip(offset + 4, len as usize)?;
+        if self.bytes[end - 1] != 0 {
+            return self.error(format!("string at byte {} has no null terminator", offset));
+        }
+        if self.check_utf8 && std::str::from_utf8(&self.bytes[offset + 4..end - 1]).is_err() {
+            return self.error(format!("string at byte {} is not valid UTF-8", offset));
+        }
+        Ok(end)
+    }
+
+    // The bounds of an embedded document or array, which is checked once the elements before it
+    // have been.
+    fn document(&self, offset: usize) -> Result<(usize, usize), ValidationError> {
+        let len = self.read_i32(offset)?;
+        if len < 5 {
+            return self.error(format!("document length {} at byte {} is less than 5", len, offset));
+        }
+        Ok((offset, self.skip(offset, len as usize)?))
+    }
+
+    // The bounds of the scope document of code with scope, which ends where the element does.
+    fn code_with_scope(&self, offset: usize) -> Result<(usize, usize), ValidationError> {
+        let len = self.read_i32(offset)?;
+        let (start, end) = self.document(self.string(offset + 4)?)?;
+        if len < 0 || end - offset != len as usize {
+            return self.error(format!("code with scope length {} at byte {} doesn't match its contents", len, offset));
+        }
+        Ok((start, end))
+    }
+
+    // The value of an element of type `element_type` at `offset`, returning the offset after it.
+    // Elements that contain documents are handled by `document` and `code_with_scope` instead.
+    fn value(&self, element_type: u8, offset: usize) -> Result<usize, ValidationError> {
+        match element_type {
+            0x06 | 0x0A | 0x7F | 0xFF => Ok(offset),
+            0x08 => match self.bytes.get(offset) {
+                Some(0) | Some(1) => Ok(offset + 1),
+                Some(value) => self.error(format!("boolean at byte {} is {}, not 0 or 1", offset, value)),
+                None => self.skip(offset, 1),
+            },
+            0x10 => self.skip(offset, 4),
+            0x01 | 0x09 | 0x11 | 0x12 => self.skip(offset, 8),
+            0x07 => self.skip(offset, 12),
+            0x13 => self.skip(offset, 16),
+            0x02 | 0x0D | 0x0E => self.string(offset),
+            0x05 => {
+                let len = self.read_i32(offset)?;
+                if len < 0 {
+                    return self.error(format!("binary length {} at byte {} is negative", len, offset));
+                }
+                let end = self.skip(offset + 5, len as usize)?;
+                let subtype = self.bytes[offset + 4];
+                if !is_valid_subtype(subtype) {
+                    return self.error(format!("binary subtype {:#04x} at byte {} is reserved", subtype, offset));
+                }
+                // The old binary subtype repeats the length of the data inside it.
+                if subtype == 0x02 && (len < 4 || self.read_i32(offset + 5)? != len - 4) {
+                    return self.error(format!("old binary at byte {} has an inconsistent inner length", offset));
+                }
+                Ok(end)
+            }
+            0x0B => {
+                let (_, end) = self.cstring(offset, "regex pattern")?;
+                let (options, end) = self.cstring(end, "regex options")?;
+                if let Some(option) = options.chars().find(|option| !REGEX_OPTIONS.contains(*option)) {
+                    return self.error(format!(
+                        "regex option '{}' at byte {} is not one of {}",
+                        option, offset, REGEX_OPTIONS
+                    ));
+                }
+                if options.as_bytes().windows(2).any(|pair| pair[0] >= pair[1]) {
+                    return self.error(format!("regex options \"{}\" at byte {} are not in order", options, offset));
+                }
+                Ok(end)
+            }
+            0x0C => {
+                let end = self.string(offset)?;
+                self.skip(end, 12)
+            }
+            other => self.error(format!("unknown element type {:#04x} at byte {}", other, offset - 1)),
+        }
+    }
+}
+
+/// Check the structure of a document's raw bytes, all the way down: that every length is
+/// consistent with what it contains, keys are valid UTF-8, binary subtypes and regex options are
+/// valid, and every document ends with its terminator. String values only have to be valid UTF-8
+/// when `invalid_utf8` is `Fail`, since converting the document handles them otherwise. Embedded
+/// documents are kept on a stack rather than recursed into, so that however deeply they're nested
+/// can't overflow the call stack.
+pub fn validate(bytes: &[u8], invalid_utf8: crate::utf8::InvalidUtf8) -> Result<(), ValidationError> {
+    let check_utf8 = invalid_utf8 == crate::utf8::InvalidUtf8::Fail;
+    let mut stack = vec![open_document(bytes, 0, bytes.len(), String::new())?];
+    while let Some(document) = stack.last_mut() {
+        let terminator = document.end - 1;
+        if document.offset >= terminator {
+            stack.pop();
+            continue;
+        }
+        let validator = Validator { bytes, end: document.end, path: &document.path, check_utf8 };
+        let element_type = bytes[document.offset];
+        let (key, value_offset) = validator.cstring(document.offset + 1, "key")?;
+        let element_path = match document.path.as_str() {
+            "" => key.to_string(),
+            path => format!("{}.{}", path, key),
+        };
+        let element = Validator { bytes, end: document.end, path: &element_path, check_utf8 };
+        let embedded = match element_type {
+            0x03 | 0x04 => Some(element.document(value_offset)?),
+            0x0F => Some(element.code_with_scope(value_offset)?),
+            _ => None,
+        };
+        document.offset = match embedded {
+            None => element.value(element_type, value_offset)?,
+            Some((_, end)) => end,
+        };
+        if document.offset > terminator {
+            return element.error("value runs over the document's terminator".to_string());
+        }
+        if let Some((start, end)) = embedded {
+            stack.push(open_document(bytes, start, end, element_path)?);
+        }
+    }
+    Ok(())
+}
+
+// A document being checked, from its start to `end`, and the offset of its next element.
+struct Document {
+    path: String,
+    end: usize,
+    offset: usize,
+}
+
+// Check the length and terminator of the document in `bytes` from `start` to `end`.
+fn open_document(bytes: &[u8], start: usize, end: usize, path: String) -> Result<Document, ValidationError> {
+    let document = Validator { bytes, end, path: &path, check_utf8: false };
+    let len = document.read_i32(start)?;
+    if len < 5 || len as usize != end - start {
+        return document.error(format!("document length {} doesn't match its {} bytes", len, end - start));
+    }
+    if bytes[end - 1] != 0 {
+        return document.error("document has no null terminator".to_string());
+    }
+    Ok(Document { path, end, offset: start + 4 })
+}
+
+/// Check that no document or array is nested more than `max_depth` levels deep, counting
+/// `document` itself as the first, before it's converted or printed by code that recurses into
+/// each level.
+pub fn check_depth(document: &RawDocument, max_depth: usize) -> Result<(), ValidationError> {
+    let error = |path: &str, reason: String| ValidationError { path: path.to_string(), reason };
+    let mut stack = vec![(document.iter_elements(), String::new(), 1)];
+    while let Some((elements, path, depth)) = stack.last_mut() {
+        let element = match elements.next() {
+            None => {
+                stack.pop();
+                continue;
+            }
+            Some(element) => element.map_err(|err| error(path, err.to_string()))?,
+        };
+        // Other values, e.g. strings that aren't valid UTF-8, are left to converting the document.
+        let nested = [ElementType::EmbeddedDocument, ElementType::Array, ElementType::JavaScriptCodeWithScope];
+        if !nested.contains(&element.element_type()) {
+            continue;
+        }
+        let value = element.value().map_err(|err| error(path, err.to_string()))?;
+        let embedded = match value {
+            RawBsonRef::Document(embedded) => embedded,
+            RawBsonRef::Array(embedded) => {
+                RawDocument::from_bytes(embedded.as_bytes()).map_err(|err| error(path, err.to_string()))?
+            }
+            RawBsonRef::JavaScriptCodeWithScope(code) => code.scope,
+            _ => continue,
+        };
+        let element_path = match path.as_str() {
+            "" => element.key().to_string(),
+            path => format!("{}.{}", path, element.key()),
+        };
+        if *depth >= max_depth {
+            return Err(error(&element_path, format!("nested more than {} levels deep", max_depth)));
+        }
+        let depth = *depth + 1;
+        stack.push((embedded.iter_elements(), element_path, depth));
+    }
+    Ok(())
+}
+
+/// What stage of reading a document failed, as reported in a validation report.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The document's size was out of range, so the rest of its input can't be read.
+    Size,
+    /// The input ended, or couldn't be read, partway through the document.
+    Read,
+    /// The document's bytes aren't structurally valid BSON.
+    Structure,
+    /// The document is nested more deeply than --maxDepth.
+    Depth,
+    /// The document is valid BSON but couldn't be converted.
+    Conversion,
+}
+
+impl ErrorKind {
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Size => "size",
+            ErrorKind::Read => "read",
+            ErrorKind::Structure => "structure",
+            ErrorKind::Depth => "depth",
+            ErrorKind::Conversion => "conversion",
+        }
+    }
+}
+
+/// A JSON report of the validity of every document read, written as it goes:
+///
+/// ```json
+/// {"documents": [
+/// {"index": 0, "offset": 0, "status": "valid"},
+/// {"index": 1, "offset": 82, "status": "invalid", "error": "structure", "path": "a.c", "reason": "..."}
+/// ],
+/// "valid": 1, "invalid": 1}
+/// ```
+///
+/// Documents from a named file also have its `file`.
+pub struct Report<W: Write> {
+    writer: W,
+    valid: u64,
+    invalid: u64,
+}
+
+impl<W: Write> Report<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(b"{\"documents\": [")?;
+        Ok(Report { writer, valid: 0, invalid: 0 })
+    }
+
+    pub fn valid(&mut self, file: Option<&str>, position: Position) -> io::Result<()> {
+        self.valid += 1;
+        self.write_entry(file, position, Map::new())
+    }
+
+    pub fn invalid(
+        &mut self,
+        file: Option<&str>,
+        position: Position,
+        kind: ErrorKind,
+        path: &str,
+        reason: &str,
+    ) -> io::Result<()> {
+        self.invalid += 1;
+        let mut error = Map::new();
+        error.insert("error".to_string(), json!(kind.name()));
+        if !path.is_empty() {
+            error.insert("path".to_string(), json!(path));
+        }
+        error.insert("reason".to_string(), json!(reason));
+        self.write_entry(file, position, error)
+    }
+
+    fn write_entry(
+        &mut self,
+        file: Option<&str>,
+        position: Position,
+        error: Map<String, serde_json::Value>,
+    ) -> io::Result<()> {
+        let mut entry = Map::new();
+        if let Some(file) = file {
+            entry.insert("file".to_string(), json!(file));
+        }
+        entry.insert("index".to_string(), json!(position.index));
+        entry.insert("offset".to_string(), json!(position.offset));
+        entry.insert("status".to_string(), json!(if error.is_empty() { "valid" } else { "invalid" }));
+        entry.extend(error);
+        let separator = if self.valid + self.invalid == 1 { "\n" } else { ",\n" };
+        write!(self.writer, "{}{}", separator, serde_json::Value::Object(entry))
+    }
+
+    /// Finish the report, returning how many documents were invalid and the writer it was written
+    /// to.
+    pub fn finish(mut self) -> io::Result<(u64, W)> {
+        write!(self.writer, "\n],\n\"valid\": {}, \"invalid\": {}}}\n", self.valid, self.invalid)?;
+        self.writer.flush()?;
+        Ok((self.invalid, self.writer))
+    }
+}
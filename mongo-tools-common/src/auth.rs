@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+
+use clap::{ArgEnum, Args};
+use log::warn;
+use mongodb::{
+    bson::Document,
+    options::{AuthMechanism, ConnectionString, Credential, Tls},
+};
+
+use crate::{
+    error::{conflict, merge},
+    Error,
+};
+
+// The database of users defined outside MongoDB.
+const EXTERNAL: &str = "$external";
+
+/// How to authenticate, for --authenticationMechanism.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Mechanism {
+    #[clap(name = "SCRAM-SHA-1")]
+    ScramSha1,
+    #[clap(name = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[clap(name = "MONGODB-AWS")]
+    MongoDbAws,
+    #[clap(name = "PLAIN")]
+    Plain,
+}
+
+impl From<Mechanism> for AuthMechanism {
+    fn from(mechanism: Mechanism) -> Self {
+        match mechanism {
+            Mechanism::ScramSha1 => AuthMechanism::ScramSha1,
+            Mechanism::ScramSha256 => AuthMechanism::ScramSha256,
+            Mechanism::MongoDbAws => AuthMechanism::MongoDbAws,
+            Mechanism::Plain => AuthMechanism::Plain,
+        }
+    }
+}
+
+/// Who to authenticate as, and how. With --uri, they fill in what its credentials leave out, and
+/// it's an error for them to differ from what it gives.
+#[derive(Args, Clone, Default)]
+pub struct AuthOptions {
+    #[clap(long, short = 'u')]
+    /// Username to authenticate as
+    pub username: Option<String>,
+
+    #[clap(long, short = 'p')]
+    /// Password to authenticate with; with no value, it's prompted for. Without it or
+    /// --passwordFile, the password is read from $MONGO_PASSWORD
+    pub password: Option<Option<String>>,
+
+    #[clap(long = "passwordFile", name = "passwordFile", conflicts_with = "password")]
+    /// File whose first line is the password to authenticate with
+    pub password_file: Option<PathBuf>,
+
+    #[clap(long = "authenticationDatabase", name = "authenticationDatabase")]
+    /// Database the user is defined in; defaults to --uri's database, or admin, or $external for
+    /// PLAIN and MONGODB-AWS
+    pub authentication_database: Option<String>,
+
+    #[clap(long = "authenticationMechanism", name = "authenticationMechanism", arg_enum)]
+    /// How to authenticate; defaults to the strongest SCRAM mechanism the server supports for the
+    /// user. MONGODB-AWS takes an access key ID and secret access key as the username and password,
+    /// and otherwise finds them in the environment, the shared AWS credentials and config files, or
+    /// the ECS or EC2 instance metadata service. PLAIN authenticates users defined outside MongoDB,
+    /// e.g. in LDAP, in the $external database, and sends the password as it is, so needs --tls
+    pub authentication_mechanism: Option<Mechanism>,
+
+    #[clap(long = "awsSessionToken", name = "awsSessionToken")]
+    /// Session token to go with the temporary AWS credentials MONGODB-AWS is given
+    pub aws_session_token: Option<String>,
+}
+
+// Passwords stay out of logs.
+impl std::fmt::Debug for AuthOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthOptions")
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|password| password.as_ref().map(|_| "REDACTED")))
+            .field("password_file", &self.password_file)
+            .field("authentication_database", &self.authentication_database)
+            .field("authentication_mechanism", &self.authentication_mechanism)
+            .field("aws_session_token", &self.aws_session_token.as_ref().map(|_| "REDACTED"))
+            .finish()
+    }
+}
+
+impl AuthOptions {
+    fn is_empty(&self) -> bool {
+        self.username.is_none()
+            && self.password.is_none()
+            && self.password_file.is_none()
+            && self.authentication_database.is_none()
+            && self.authentication_mechanism.is_none()
+            && self.aws_session_token.is_none()
+    }
+
+    /// The password --password or --passwordFile gives, prompting for it if --password has no value.
+    pub fn password(&self) -> Result<Option<String>, Error> {
+        if let Some(path) = &self.password_file {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|err| Error::Io(format!("Failed to read --passwordFile {}", path.display()), err))?;
+            return match contents.lines().next() {
+                None | Some("") => Err(Error::InvalidOptions(format!("--passwordFile {} is empty", path.display()))),
+                Some(password) => Ok(Some(password.to_string())),
+            };
+        }
+        match &self.password {
+            None => Ok(None),
+            Some(Some(password)) => Ok(Some(password.clone())),
+            Some(None) => rpassword::prompt_password("Enter password: ")
+                .map(Some)
+                .map_err(|err| Error::Io("Failed to prompt for --password".to_string(), err)),
+        }
+    }
+
+    /// Add the options to `conn_str`'s credential, prompting for the password if --password has no
+    /// value. A credential with a username and without a password takes $MONGO_PASSWORD's, and a
+    /// MONGODB-AWS one without keys takes the shared AWS files'.
+    pub(crate) fn apply(&self, conn_str: &mut ConnectionString) -> Result<(), Error> {
+        if !self.is_empty() {
+            let credential = conn_str.credential.get_or_insert_with(Credential::default);
+            merge(&mut credential.username, &self.username, |uri, flag| {
+                conflict("usernames", uri, "--username", flag)
+            })?;
+            merge(&mut credential.password, &self.password()?, |_, _| {
+                Error::InvalidOptions("--uri and --password give different passwords".to_string())
+            })?;
+            merge(&mut credential.source, &self.authentication_database, |uri, flag| {
+                conflict("authentication databases", uri, "--authenticationDatabase", flag)
+            })?;
+            let mechanism = self.authentication_mechanism.map(AuthMechanism::from);
+            merge(&mut credential.mechanism, &mechanism, |uri, flag| {
+                conflict("authentication mechanisms", uri.as_str(), "--authenticationMechanism", flag.as_str())
+            })?;
+            if let Some(session_token) = &self.aws_session_token {
+                let properties = credential.mechanism_properties.get_or_insert_with(Document::new);
+                if properties.get_str("AWS_SESSION_TOKEN").is_ok_and(|uri_token| uri_token != session_token) {
+                    return Err(Error::InvalidOptions(
+                        "--uri and --awsSessionToken give different session tokens".to_string(),
+                    ));
+                }
+                properties.insert("AWS_SESSION_TOKEN", session_token);
+            }
+        }
+        if let Some(credential) = &mut conn_str.credential {
+            crate::aws::add_shared_credentials(credential)?;
+        }
+        add_env_password(conn_str);
+        Ok(())
+    }
+}
+
+// Give a credential with a username and without a password $MONGO_PASSWORD, if it's set and the
+// mechanism takes a password.
+fn add_env_password(conn_str: &mut ConnectionString) {
+    let credential = match &mut conn_str.credential {
+        Some(credential) if credential.username.is_some() && credential.password.is_none() => credential,
+        _ => return,
+    };
+    let takes_password = matches!(
+        credential.mechanism,
+        None | Some(AuthMechanism::ScramSha1 | AuthMechanism::ScramSha256 | AuthMechanism::Plain)
+    );
+    if takes_password {
+        credential.password = std::env::var("MONGO_PASSWORD").ok();
+    }
+}
+
+/// Authenticate PLAIN credentials in $external, where users defined outside MongoDB are, rather than
+/// --uri's database as the driver would, and warn that without TLS their passwords are sent as they
+/// are. It's an error for them to name another database.
+pub(crate) fn check_plain(conn_str: &mut ConnectionString) -> Result<(), Error> {
+    let credential = match &mut conn_str.credential {
+        Some(credential) if credential.mechanism == Some(AuthMechanism::Plain) => credential,
+        _ => return Ok(()),
+    };
+    let source = credential.source.get_or_insert_with(|| EXTERNAL.to_string());
+    if source != EXTERNAL {
+        let message = format!("PLAIN authenticates users in {}, not the authentication database {}", EXTERNAL, source);
+        return Err(Error::InvalidOptions(message));
+    }
+    if !matches!(conn_str.tls, Some(Tls::Enabled(_))) {
+        warn!("PLAIN authentication without TLS sends the password unencrypted; connect with --tls");
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::{collections::HashMap, env, path::PathBuf};
+
+use mongodb::{
+    bson::Document,
+    options::{AuthMechanism, Credential},
+};
+
+use crate::Error;
+
+/// Give a MONGODB-AWS credential without an access key the one in the shared AWS credentials or
+/// config file for $AWS_PROFILE, or the default profile, unless the environment has one. The
+/// driver reads the environment itself as it authenticates, and otherwise asks the ECS or EC2
+/// instance metadata service, using IMDSv2, but doesn't read the files.
+pub(crate) fn add_shared_credentials(credential: &mut Credential) -> Result<(), Error> {
+    let needs_key = credential.mechanism == Some(AuthMechanism::MongoDbAws)
+        && credential.username.is_none()
+        && credential.password.is_none()
+        && env::var_os("AWS_ACCESS_KEY_ID").is_none()
+        && env::var_os("AWS_SECRET_ACCESS_KEY").is_none();
+    if !needs_key {
+        return Ok(());
+    }
+    let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+    let mut keys = read_profile(shared_file("AWS_SHARED_CREDENTIALS_FILE", "credentials"), &profile, &profile)?;
+    // The config file names sections other than the default "profile NAME".
+    let config_section = if profile == "default" { profile.clone() } else { format!("profile {}", profile) };
+    if keys.is_empty() {
+        keys = read_profile(shared_file("AWS_CONFIG_FILE", "config"), &config_section, &profile)?;
+    }
+    let (access_key, secret_key) = match (keys.remove("aws_access_key_id"), keys.remove("aws_secret_access_key")) {
+        (Some(access_key), Some(secret_key)) => (access_key, secret_key),
+        (None, None) => return Ok(()),
+        _ => {
+            return Err(Error::InvalidOptions(format!(
+                "AWS profile {} needs both aws_access_key_id and aws_secret_access_key",
+                profile
+            )))
+        }
+    };
+    credential.username = Some(access_key);
+    credential.password = Some(secret_key);
+    if let Some(session_token) = keys.remove("aws_session_token") {
+        let properties = credential.mechanism_properties.get_or_insert_with(Document::new);
+        if !properties.contains_key("AWS_SESSION_TOKEN") {
+            properties.insert("AWS_SESSION_TOKEN", session_token);
+        }
+    }
+    Ok(())
+}
+
+// The file $`variable` names, or ~/.aws/`name`.
+fn shared_file(variable: &str, name: &str) -> Option<PathBuf> {
+    if let Some(path) = env::var_os(variable) {
+        return Some(path.into());
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".aws").join(name))
+}
+
+// The keys in `section` of the INI file at `path`, or none if there's no such file.
+fn read_profile(path: Option<PathBuf>, section: &str, profile: &str) -> Result<HashMap<String, String>, Error> {
+    let path = match path {
+        None => return Ok(HashMap::new()),
+        Some(path) => path,
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => {
+            let context = format!("Failed to read AWS profile {} from {}", profile, path.display());
+            return Err(Error::Io(context, err));
+        }
+        Ok(contents) => contents,
+    };
+    let mut keys = HashMap::new();
+    let mut in_section = false;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            in_section = name.trim() == section;
+        } else if let Some((key, value)) = line.split_once('=').filter(|_| in_section) {
+            keys.insert(key.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    Ok(keys)
+}
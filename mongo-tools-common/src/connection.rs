@@ -0,0 +1,139 @@
+use clap::Args;
+use mongodb::{
+    options::{ClientOptions, ConnectionString, HostInfo, ServerAddress},
+    sync::Client,
+};
+
+use crate::{auth::AuthOptions, error::conflict, tls::TlsOptions, Error};
+
+// What the tools connect to without --uri, --host or --port.
+const DEFAULT_URI: &str = "mongodb://localhost/";
+const DEFAULT_PORT: u16 = 27017;
+
+/// Which deployment to connect to, given as a connection string with --uri or as hosts with --host
+/// and --port. Both may be given as long as they agree.
+#[derive(Args, Clone, Debug, Default)]
+pub struct ConnectionOptions {
+    #[clap(long)]
+    /// MongoDB connection string, e.g. mongodb://host1:27017,host2/?replicaSet=rs0
+    pub uri: Option<String>,
+
+    #[clap(long)]
+    /// Host to connect to: host, host:port, a comma-separated list of them, or a list prefixed with
+    /// its replica set's name and a slash, e.g. rs0/host1:27017,host2
+    pub host: Option<String>,
+
+    #[clap(long)]
+    /// Port to connect to on --host's hosts that don't name one
+    pub port: Option<u16>,
+
+    #[clap(flatten)]
+    pub auth: AuthOptions,
+
+    #[clap(flatten)]
+    pub tls: TlsOptions,
+}
+
+impl ConnectionOptions {
+    /// The connection string the options make up: --uri's, or one for localhost, with the hosts and
+    /// replica set --host and --port name and the credentials and TLS options the other flags give.
+    /// It's an error for them to name different ones than --uri.
+    pub fn connection_string(&self) -> Result<ConnectionString, Error> {
+        let mut conn_str = ConnectionString::parse(self.uri.as_deref().unwrap_or(DEFAULT_URI))?;
+        self.apply_hosts(&mut conn_str)?;
+        self.auth.apply(&mut conn_str)?;
+        self.tls.apply(&mut conn_str)?;
+        crate::auth::check_plain(&mut conn_str)?;
+        Ok(conn_str)
+    }
+
+    fn apply_hosts(&self, conn_str: &mut ConnectionString) -> Result<(), Error> {
+        let (replica_set, hosts) = match (&self.host, self.port) {
+            (None, None) => return Ok(()),
+            (Some(host), port) => parse_host(host, port)?,
+            (None, port) => (None, vec![ServerAddress::Tcp { host: "localhost".to_string(), port }]),
+        };
+        if let Some(uri) = &self.uri {
+            check_uri_hosts(uri, &conn_str.host_info, &hosts, self.host.is_some(), self.port)?;
+        } else {
+            conn_str.host_info = HostInfo::HostIdentifiers(hosts);
+        }
+        match (&conn_str.replica_set, replica_set) {
+            (Some(uri_set), Some(host_set)) if *uri_set != host_set => {
+                return Err(conflict("replica set", uri_set, "--host", &host_set));
+            }
+            (_, Some(host_set)) => conn_str.replica_set = Some(host_set),
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    /// The driver's options for the connection string, naming the connections `app_name`, the
+    /// tool's name, in the server's logs unless --uri sets appName. They leave the certificate key
+    /// file encrypted; only `connect` decrypts it with --tlsCertificateKeyFilePassword.
+    pub fn client_options(&self, app_name: &str) -> Result<ClientOptions, Error> {
+        let mut options = ClientOptions::parse_connection_string_sync(self.connection_string()?)?;
+        options.app_name.get_or_insert_with(|| app_name.to_string());
+        Ok(options)
+    }
+
+    /// A client for the deployment. It connects as it's first used.
+    pub fn connect(&self, app_name: &str) -> Result<Client, Error> {
+        let mut options = self.client_options(app_name)?;
+        let _decrypted_key = self.tls.decrypt_key(&mut options)?;
+        Ok(Client::with_options(options)?)
+    }
+}
+
+// Split a --host value into its replica set's name, if it has one, and its hosts, giving the ones
+// without a port `port`. A value starting with a slash is a Unix socket's path rather than a name.
+fn parse_host(host: &str, port: Option<u16>) -> Result<(Option<String>, Vec<ServerAddress>), Error> {
+    let (replica_set, list) = match host.split_once('/') {
+        Some((name, list)) if !name.is_empty() && !name.contains([':', ',']) => (Some(name.to_string()), list),
+        _ => (None, host),
+    };
+    let mut hosts = Vec::new();
+    for address in list.split(',') {
+        if address.is_empty() {
+            return Err(Error::InvalidOptions(format!("--host {:?} has an empty host", host)));
+        }
+        let mut address = ServerAddress::parse(address)?;
+        if let ServerAddress::Tcp { port: address_port @ None, .. } = &mut address {
+            *address_port = port;
+        }
+        hosts.push(address);
+    }
+    Ok((replica_set, hosts))
+}
+
+// Check that --host names the hosts of --uri, or without it that --port is their port.
+fn check_uri_hosts(
+    uri: &str,
+    uri_hosts: &HostInfo,
+    hosts: &[ServerAddress],
+    host_given: bool,
+    port: Option<u16>,
+) -> Result<(), Error> {
+    let uri_hosts = match uri_hosts {
+        HostInfo::HostIdentifiers(uri_hosts) => uri_hosts,
+        _ => return Err(Error::InvalidOptions("--host and --port can't be used with a mongodb+srv:// --uri".into())),
+    };
+    if host_given {
+        // Addresses without a port are equal to ones with the default port.
+        if uri_hosts.len() != hosts.len() || !hosts.iter().all(|host| uri_hosts.contains(host)) {
+            let hosts: Vec<String> = hosts.iter().map(ToString::to_string).collect();
+            return Err(conflict("hosts", uri, "--host", &hosts.join(",")));
+        }
+        return Ok(());
+    }
+    if let Some(port) = port {
+        let other_port = |host: &ServerAddress| match host {
+            ServerAddress::Tcp { port: uri_port, .. } => uri_port.unwrap_or(DEFAULT_PORT) != port,
+            _ => false,
+        };
+        if uri_hosts.iter().any(other_port) {
+            return Err(conflict("ports", uri, "--port", &port.to_string()));
+        }
+    }
+    Ok(())
+}
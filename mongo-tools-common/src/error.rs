@@ -0,0 +1,60 @@
+/// Why a tool couldn't build a client from its options.
+#[derive(Debug)]
+pub enum Error {
+    /// The options contradict each other, e.g. --host naming a different host than --uri.
+    InvalidOptions(String),
+    /// The driver rejected the options or couldn't build the client.
+    Driver(mongodb::error::Error),
+    /// Reading something the options name failed, e.g. --passwordFile.
+    Io(String, std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidOptions(message) => write!(f, "invalid options: {}", message),
+            Error::Driver(err) => write!(f, "{}", err),
+            Error::Io(context, err) => write!(f, "{}: {}", context, err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidOptions(_) => None,
+            Error::Driver(err) => Some(err),
+            Error::Io(_, err) => Some(err),
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for Error {
+    fn from(err: mongodb::error::Error) -> Self {
+        Error::Driver(err)
+    }
+}
+
+// The error for a flag naming something different than --uri does.
+pub(crate) fn conflict(what: &str, uri_value: &str, flag: &str, flag_value: &str) -> Error {
+    Error::InvalidOptions(format!(
+        "--uri and {} name different {}: {:?} in --uri and {:?} in {}",
+        flag, what, uri_value, flag_value, flag
+    ))
+}
+
+// Fill in `uri_value` with `flag_value`, unless they're both given and differ.
+pub(crate) fn merge<T: Clone + PartialEq>(
+    uri_value: &mut Option<T>,
+    flag_value: &Option<T>,
+    conflict: impl FnOnce(&T, &T) -> Error,
+) -> Result<(), Error> {
+    match (&*uri_value, flag_value) {
+        (Some(uri), Some(flag)) if uri != flag => Err(conflict(uri, flag)),
+        (_, Some(flag)) => {
+            *uri_value = Some(flag.clone());
+            Ok(())
+        }
+        (_, None) => Ok(()),
+    }
+}
@@ -0,0 +1,14 @@
+//! What the networked MongoDB tools share: the flags that say which deployment to connect to, who
+//! to authenticate as and how to use TLS, named as the Go tools name them, and the client they
+//! build. A tool flattens [`ConnectionOptions`] into its own arguments.
+
+pub mod auth;
+mod aws;
+pub mod connection;
+mod error;
+pub mod tls;
+
+pub use auth::AuthOptions;
+pub use connection::ConnectionOptions;
+pub use error::Error;
+pub use tls::TlsOptions;